@@ -0,0 +1,11 @@
+#!/bin/sh
+
+listen_addr="127.0.0.1:9115"
+data_dir="./db2q.seglog"
+fsync="always"
+
+RUST_LOG=info \
+ENV_DATA_DIR="${data_dir}" \
+ENV_FSYNC="${fsync}" \
+ENV_LISTEN_ADDR="${listen_addr}" \
+	./simple
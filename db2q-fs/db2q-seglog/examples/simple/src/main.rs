@@ -0,0 +1,93 @@
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use db2q_seglog::db2q::queue::st::svc::locked_q_topic_svc_new;
+
+use db2q_seglog::tonic;
+
+use db2q_seglog::db2q::queue::rw::svc::rw_q_svc_new;
+use db2q_seglog::db2q::queue::rw::svc::RwQueueSvc;
+
+use tonic::transport::{server::Router, Server};
+
+use db2q_seglog::common::minimal::log::Fsync;
+use db2q_seglog::common::minimal::store::{store_open, Store, SEGMENT_BYTES_DEFAULT};
+
+use db2q_seglog::count_service_server::CountServiceServer;
+use db2q_seglog::queue_service_server::QueueServiceServer;
+use db2q_seglog::topic_service_server::TopicServiceServer;
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    env_logger::Builder::new()
+        .default_format()
+        .parse_default_env()
+        .format_timestamp_micros()
+        .init();
+
+    let listen_addr: String =
+        env::var("ENV_LISTEN_ADDR").unwrap_or_else(|_| "127.0.0.1:50051".into());
+    let listen: SocketAddr = str::parse(&listen_addr).map_err(|e| format!("Invalid addr: {e}"))?;
+
+    let data_dir: String = env::var("ENV_DATA_DIR").unwrap_or_else(|_| "./db2q.seglog".into());
+
+    let segment_bytes: u64 = env::var("ENV_SEGMENT_BYTES")
+        .ok()
+        .and_then(|s| str::parse(&s).ok())
+        .unwrap_or(SEGMENT_BYTES_DEFAULT);
+
+    let fsync: Fsync = match env::var("ENV_FSYNC").as_deref() {
+        Ok("deferred") => Fsync::Deferred,
+        _ => Fsync::Always,
+    };
+
+    // replays all segments before listening
+    let store: Arc<Store> = store_open(data_dir, segment_bytes, fsync)
+        .map_err(|e| format!("Unable to open a store: {e}"))?;
+
+    db2q_seglog::sweep::minimal::retention::retention_worker_new(
+        &store,
+        db2q_seglog::sweep::minimal::retention::INTERVAL_DEFAULT,
+    );
+    if let Fsync::Deferred = fsync {
+        db2q_seglog::sweep::minimal::fsync::syncer_new(
+            &store,
+            db2q_seglog::sweep::minimal::fsync::INTERVAL_DEFAULT,
+        );
+    }
+
+    let topic_svc = db2q_seglog::topic::minimal::svc::topic_svc_new(&store);
+    let topic_svc_shared: Arc<_> = Arc::new(topic_svc);
+
+    let count_svc = db2q_seglog::count::minimal::svc::count_svc_new(&store);
+    let count_svr: CountServiceServer<_> = CountServiceServer::new(count_svc);
+
+    let queue_svc = db2q_seglog::queue::minimal::svc::queue_svc_new(&store);
+    let queue_svc_shared: Arc<_> = Arc::new(queue_svc);
+
+    let locked_q_topic_svc = locked_q_topic_svc_new(&queue_svc_shared, &topic_svc_shared);
+    let lqts_shared: Arc<_> = Arc::new(locked_q_topic_svc);
+
+    let topic_svr: TopicServiceServer<_> = TopicServiceServer::new(lqts_shared.clone());
+
+    let rw_q_svc: RwQueueSvc<_> = rw_q_svc_new(&lqts_shared);
+    let queue_svr: QueueServiceServer<_> = QueueServiceServer::new(rw_q_svc.clone());
+
+    rw_q_svc
+        .make_writable()
+        .await
+        .map_err(|e| format!("Unable to make writable queue: {e}"))?;
+
+    let mut sv: Server = Server::builder();
+    let router: Router<_> = sv
+        .add_service(topic_svr)
+        .add_service(queue_svr)
+        .add_service(count_svr);
+
+    router
+        .serve(listen)
+        .await
+        .map_err(|e| format!("Unable to listen: {e}"))?;
+    Ok(())
+}
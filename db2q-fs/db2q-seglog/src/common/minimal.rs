@@ -0,0 +1,6 @@
+pub mod config;
+pub mod log;
+pub mod record;
+pub mod segment;
+pub mod store;
+pub mod time;
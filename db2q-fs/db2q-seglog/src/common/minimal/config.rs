@@ -0,0 +1,129 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use tonic::Status;
+
+use crate::common::minimal::segment::io2status;
+
+const CONFIG_NAME: &str = "topic.conf";
+
+// (name=value) lines; an empty value for an unset one
+#[derive(Clone)]
+pub struct Config {
+    pub created: i64,
+    pub dead_letter: Option<(u128, u64)>,
+    pub safe_tail: bool,
+    pub default_ttl_us: Option<i64>,
+    pub priority: bool,
+    pub retain_messages: Option<u64>,
+    pub retain_age_us: Option<i64>,
+    pub retain_bytes: Option<u64>,
+}
+
+fn opt<T: ToString>(o: Option<T>) -> String {
+    o.map(|t| t.to_string()).unwrap_or_default()
+}
+
+#[allow(clippy::result_large_err)]
+fn parsed<T: core::str::FromStr>(name: &str, value: &str) -> Result<Option<T>, Status> {
+    match value.is_empty() {
+        true => Ok(None),
+        false => str::parse(value)
+            .map(Some)
+            .map_err(|_| Status::data_loss(format!("invalid config. {name}: {value}"))),
+    }
+}
+
+impl Config {
+    fn encode(&self) -> String {
+        let (dlq, max_attempts) = match self.dead_letter {
+            None => (String::new(), String::new()),
+            Some((dlq, m)) => (format!("{dlq:032x}"), m.to_string()),
+        };
+        [
+            format!("created={}", self.created),
+            format!("dead_letter={dlq}"),
+            format!("max_attempts={max_attempts}"),
+            format!("safe_tail={}", self.safe_tail),
+            format!("default_ttl_us={}", opt(self.default_ttl_us)),
+            format!("priority={}", self.priority),
+            format!("retain_messages={}", opt(self.retain_messages)),
+            format!("retain_age_us={}", opt(self.retain_age_us)),
+            format!("retain_bytes={}", opt(self.retain_bytes)),
+        ]
+        .iter()
+        .map(|line: &String| format!("{line}\n"))
+        .collect()
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn decode(encoded: &str) -> Result<Self, Status> {
+        let mut c = Self {
+            created: 0,
+            dead_letter: None,
+            safe_tail: false,
+            default_ttl_us: None,
+            priority: false,
+            retain_messages: None,
+            retain_age_us: None,
+            retain_bytes: None,
+        };
+        let mut dlq: Option<u128> = None;
+        let mut max_attempts: Option<u64> = None;
+        for line in encoded.lines().filter(|l: &&str| !l.is_empty()) {
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| Status::data_loss(format!("invalid config line: {line}")))?;
+            match name {
+                "created" => c.created = parsed(name, value)?.unwrap_or_default(),
+                "dead_letter" => {
+                    dlq = match value.is_empty() {
+                        true => None,
+                        false => Some(u128::from_str_radix(value, 16).map_err(|e| {
+                            Status::data_loss(format!("invalid dead letter topic: {e}"))
+                        })?),
+                    }
+                }
+                "max_attempts" => max_attempts = parsed(name, value)?,
+                "safe_tail" => c.safe_tail = parsed(name, value)?.unwrap_or_default(),
+                "default_ttl_us" => c.default_ttl_us = parsed(name, value)?,
+                "priority" => c.priority = parsed(name, value)?.unwrap_or_default(),
+                "retain_messages" => c.retain_messages = parsed(name, value)?,
+                "retain_age_us" => c.retain_age_us = parsed(name, value)?,
+                "retain_bytes" => c.retain_bytes = parsed(name, value)?,
+                _ => log::warn!("unknown config ignored: {name}"),
+            }
+        }
+        c.dead_letter = dlq.map(|d| (d, max_attempts.unwrap_or_default()));
+        Ok(c)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn load(dir: &Path) -> Result<Self, Status> {
+        let encoded: String = fs::read_to_string(dir.join(CONFIG_NAME))
+            .map_err(|e| io2status(e, "Unable to read a config"))?;
+        Self::decode(&encoded)
+    }
+
+    // replaced atomically(a crash leaves the old or the new one)
+    #[allow(clippy::result_large_err)]
+    pub fn save(&self, dir: &Path) -> Result<(), Status> {
+        let tmp: PathBuf = dir.join(format!("{CONFIG_NAME}.tmp"));
+        let mut f: File =
+            File::create(&tmp).map_err(|e| io2status(e, "Unable to save a config"))?;
+        f.write_all(self.encode().as_bytes())
+            .and_then(|_| f.sync_all())
+            .map_err(|e| io2status(e, "Unable to save a config"))?;
+        fs::rename(&tmp, dir.join(CONFIG_NAME))
+            .map_err(|e| io2status(e, "Unable to save a config"))?;
+        sync_dir(dir)
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn sync_dir(dir: &Path) -> Result<(), Status> {
+    File::open(dir)
+        .and_then(|d: File| d.sync_all())
+        .map_err(|e| io2status(e, "Unable to sync a directory"))
+}
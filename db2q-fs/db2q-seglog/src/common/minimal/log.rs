@@ -0,0 +1,720 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+use tonic::Status;
+
+use db2q::queue::cmd::filter::{Condition, Filter};
+
+use db2q::db2q::proto::queue::v1::q_svc::NextResponse;
+
+use crate::common::minimal::config::{sync_dir, Config};
+use crate::common::minimal::record::{Pushed, Record};
+use crate::common::minimal::segment::{io2status, segment_ids, Segment};
+
+#[derive(Clone, Copy)]
+pub enum Fsync {
+    // before replying
+    Always,
+    // by the syncer(or the os)
+    Deferred,
+}
+
+// the index of a live message(the value is read from its segment)
+pub struct Entry {
+    segment: u64,
+    offset: u64,
+    len: usize,
+    val_len: u64,
+    headers: HashMap<String, Vec<u8>>,
+    pub priority: i32,
+    pub pushed: i64,
+    pub visible_at: i64,
+    pub expires_at: Option<i64>,
+    pub leased_until: Option<i64>,
+    pub lease_id: Option<u128>,
+    pub attempts: u64,
+    pub last_error: Option<String>,
+}
+
+impl Entry {
+    pub fn as_val_len(&self) -> u64 {
+        self.val_len
+    }
+
+    pub fn as_headers(&self) -> &HashMap<String, Vec<u8>> {
+        &self.headers
+    }
+
+    pub fn alive(&self, now: i64) -> bool {
+        self.expires_at.map(|t| now < t).unwrap_or(true)
+    }
+
+    pub fn visible(&self, now: i64) -> bool {
+        self.visible_at <= now && self.alive(now)
+    }
+
+    pub fn leasable(&self, now: i64) -> bool {
+        self.visible(now) && self.leased_until.map(|t| t <= now).unwrap_or(true)
+    }
+
+    pub fn leased(&self, lease_id: u128, now: i64) -> bool {
+        self.lease_id == Some(lease_id) && self.leased_until.map(|t| now < t).unwrap_or_default()
+    }
+}
+
+pub struct TopicLog {
+    dir: PathBuf,
+    config: Config,
+    segment_bytes: u64,
+    fsync: Fsync,
+    // the last one is the active segment
+    segments: BTreeMap<u64, Segment>,
+    entries: BTreeMap<i64, Entry>,
+    last_key: i64,
+    // the key and the pushed time; kept for the dedup window even after the items are removed
+    req_ids: HashMap<u128, (i64, i64)>,
+    dirty: bool,
+    notify: Arc<Notify>,
+}
+
+impl TopicLog {
+    fn new(dir: PathBuf, config: Config, segment_bytes: u64, fsync: Fsync) -> Self {
+        Self {
+            dir,
+            config,
+            segment_bytes,
+            fsync,
+            segments: BTreeMap::new(),
+            entries: BTreeMap::new(),
+            last_key: 0,
+            req_ids: HashMap::new(),
+            dirty: false,
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn create(
+        dir: PathBuf,
+        config: Config,
+        segment_bytes: u64,
+        fsync: Fsync,
+    ) -> Result<Self, Status> {
+        fs::create_dir(&dir).map_err(|e| io2status(e, "Unable to create a topic"))?;
+        config.save(&dir)?;
+        let mut created = Self::new(dir, config, segment_bytes, fsync);
+        created.roll()?;
+        if let Some(parent) = created.dir.parent() {
+            sync_dir(parent)?;
+        }
+        Ok(created)
+    }
+
+    // replays all segments; a torn tail of the active segment is truncated
+    #[allow(clippy::result_large_err)]
+    pub fn open(dir: PathBuf, segment_bytes: u64, fsync: Fsync) -> Result<Self, Status> {
+        let config: Config = Config::load(&dir)?;
+        let ids: Vec<u64> = segment_ids(&dir)?;
+        let mut opened = Self::new(dir, config, segment_bytes, fsync);
+        let last: Option<u64> = ids.last().copied();
+        for id in ids {
+            let mut segment: Segment = Segment::open(&opened.dir, id)?;
+            // applied while scanning(live counts are recounted below)
+            let valid: u64 = segment.scan(|offset, len, rec| {
+                opened.apply(id, offset, len, rec);
+                Ok(())
+            })?;
+            let size: u64 = segment.as_size();
+            match (valid < size, Some(id) == last) {
+                (false, _) => {}
+                (true, true) => {
+                    log::warn!(
+                        "torn tail truncated. dir: {:?}, segment: {id}, {size} -> {valid}",
+                        opened.dir
+                    );
+                    segment.truncate(valid)?;
+                }
+                (true, false) => {
+                    return Err(Status::data_loss(format!(
+                        "broken segment. dir: {:?}, segment: {id}, offset: {valid}",
+                        opened.dir
+                    )))
+                }
+            }
+            opened.segments.insert(id, segment);
+        }
+        for s in opened.segments.values_mut() {
+            s.reset_live();
+        }
+        for e in opened.entries.values() {
+            if let Some(s) = opened.segments.get_mut(&e.segment) {
+                s.inc_live();
+            }
+        }
+        if opened.segments.is_empty() {
+            opened.roll()?;
+        }
+        Ok(opened)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn remove_all(self) -> Result<(), Status> {
+        fs::remove_dir_all(&self.dir).map_err(|e| io2status(e, "Unable to drop a topic"))?;
+        self.notify.notify_waiters();
+        Ok(())
+    }
+
+    pub fn as_config(&self) -> &Config {
+        &self.config
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn set_config(&mut self, config: Config) -> Result<(), Status> {
+        config.save(&self.dir)?;
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn as_entries(&self) -> &BTreeMap<i64, Entry> {
+        &self.entries
+    }
+
+    pub fn as_notify(&self) -> &Arc<Notify> {
+        &self.notify
+    }
+
+    pub fn next_key(&self) -> i64 {
+        self.last_key + 1
+    }
+
+    // the key and the pushed time of a request pushed since the oldest
+    pub fn pushed_by_request(&self, req_id: u128, oldest: i64) -> Option<(i64, i64)> {
+        self.req_ids
+            .get(&req_id)
+            .copied()
+            .filter(|(_, pushed)| oldest <= *pushed)
+    }
+
+    pub fn sweep_requests(&mut self, oldest: i64) {
+        self.req_ids.retain(|_, (_, pushed)| oldest <= *pushed);
+    }
+
+    // bytes of all segments
+    pub fn size(&self) -> u64 {
+        self.segments.values().map(|s: &Segment| s.as_size()).sum()
+    }
+
+    fn apply(&mut self, segment: u64, offset: u64, len: usize, rec: Record) {
+        self.last_key = self.last_key.max(rec.key());
+        match rec {
+            Record::Head { .. } => {}
+            Record::Push(p) => {
+                if let Some(s) = self.segments.get_mut(&segment) {
+                    s.inc_live();
+                    if p.req_id.is_some() {
+                        s.set_requested(p.pushed);
+                    }
+                }
+                if let Some(req_id) = p.req_id {
+                    self.req_ids.insert(req_id, (p.key, p.pushed));
+                }
+                let entry = Entry {
+                    segment,
+                    offset,
+                    len,
+                    val_len: p.val.len() as u64,
+                    headers: p.headers,
+                    priority: p.priority,
+                    pushed: p.pushed,
+                    visible_at: p.visible_at,
+                    expires_at: p.expires_at,
+                    leased_until: None,
+                    lease_id: None,
+                    attempts: p.attempts,
+                    last_error: p.last_error,
+                };
+                self.entries.insert(p.key, entry);
+            }
+            Record::Delete { key } => {
+                if let Some(removed) = self.entries.remove(&key) {
+                    if let Some(s) = self.segments.get_mut(&removed.segment) {
+                        s.dec_live();
+                    }
+                }
+            }
+            Record::Lease {
+                key,
+                lease_id,
+                until,
+            } => {
+                if let Some(e) = self.entries.get_mut(&key) {
+                    e.leased_until = Some(until);
+                    e.lease_id = lease_id;
+                }
+            }
+            Record::Fail {
+                key,
+                attempts,
+                error,
+            } => {
+                if let Some(e) = self.entries.get_mut(&key) {
+                    e.attempts = attempts;
+                    e.last_error = Some(error);
+                }
+            }
+        }
+    }
+
+    // a new active segment starting with the last key
+    #[allow(clippy::result_large_err)]
+    fn roll(&mut self) -> Result<(), Status> {
+        if let Some(active) = self.segments.values().next_back() {
+            active.sync()?;
+        }
+        let id: u64 = self.segments.keys().next_back().map(|i| i + 1).unwrap_or(1);
+        let mut created: Segment = Segment::create(&self.dir, id)?;
+        let head = Record::Head {
+            last_key: self.last_key,
+        };
+        created.append(&head.frame())?;
+        created.sync()?;
+        sync_dir(&self.dir)?;
+        self.segments.insert(id, created);
+        self.dirty = false;
+        Ok(())
+    }
+
+    // written before applied
+    #[allow(clippy::result_large_err)]
+    pub fn append(&mut self, rec: Record) -> Result<(), Status> {
+        let full: bool = self
+            .segments
+            .values()
+            .next_back()
+            .map(|s: &Segment| self.segment_bytes <= s.as_size())
+            .unwrap_or(true);
+        if full {
+            self.roll()?;
+        }
+        let framed: Vec<u8> = rec.frame();
+        let (id, offset) = match self.segments.iter_mut().next_back() {
+            None => return Err(Status::internal("no active segment")),
+            Some((id, active)) => (*id, active.append(&framed)?),
+        };
+        self.dirty = true;
+        self.apply(id, offset, framed.len(), rec);
+        Ok(())
+    }
+
+    // makes the appended records durable(if required)
+    #[allow(clippy::result_large_err)]
+    pub fn commit(&mut self) -> Result<(), Status> {
+        match self.fsync {
+            Fsync::Always => self.sync(),
+            Fsync::Deferred => Ok(()),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn sync(&mut self) -> Result<(), Status> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(active) = self.segments.values().next_back() {
+            active.sync()?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    // the oldest segments without live messages nor requests pushed since the oldest(never the
+    // active one)
+    #[allow(clippy::result_large_err)]
+    pub fn remove_dead_segments(&mut self, oldest: i64) -> Result<u64, Status> {
+        let mut removed: u64 = 0;
+        while 1 < self.segments.len() {
+            let dead: bool = self
+                .segments
+                .values()
+                .next()
+                .map(|s: &Segment| {
+                    0 == s.as_live() && s.as_requested().map(|t| t < oldest).unwrap_or(true)
+                })
+                .unwrap_or_default();
+            if !dead {
+                break;
+            }
+            if let Some((_, s)) = self.segments.pop_first() {
+                log::debug!(
+                    "segment removed. dir: {:?}, segment: {}",
+                    self.dir,
+                    s.as_id()
+                );
+                s.remove()?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn read(&self, key: i64) -> Result<Pushed, Status> {
+        let e: &Entry = self
+            .entries
+            .get(&key)
+            .ok_or_else(|| Status::not_found(format!("No such queue item. key: {key}")))?;
+        let s: &Segment = self.segments.get(&e.segment).ok_or_else(|| {
+            Status::data_loss(format!(
+                "segment missing. key: {key}, segment: {}",
+                e.segment
+            ))
+        })?;
+        match s.read(e.offset, e.len)? {
+            Record::Push(p) => Ok(p),
+            _ => Err(Status::data_loss(format!(
+                "not a pushed record. key: {key}"
+            ))),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn item(&self, key: i64) -> Result<NextResponse, Status> {
+        let p: Pushed = self.read(key)?;
+        // failures are recorded after the push
+        let (attempts, last_error) = self
+            .entries
+            .get(&key)
+            .map(|e: &Entry| (e.attempts, e.last_error.clone()))
+            .unwrap_or_default();
+        Ok(NextResponse {
+            next: key,
+            value: p.val,
+            priority: p.priority,
+            headers: p.headers,
+            attempts,
+            last_error: last_error.unwrap_or_default(),
+        })
+    }
+
+    // values are read only for value conditions
+    #[allow(clippy::result_large_err)]
+    pub fn matches(&self, key: i64, filter: Option<&Filter>) -> Result<bool, Status> {
+        let conditions: &[Condition] = match filter {
+            None => return Ok(true),
+            Some(f) => f.as_conditions(),
+        };
+        let headers: &HashMap<String, Vec<u8>> = match self.entries.get(&key) {
+            None => return Ok(false),
+            Some(e) => &e.headers,
+        };
+        let mut val: Option<Vec<u8>> = None;
+        for c in conditions {
+            let matched: bool = match c.matches_headers(headers) {
+                Some(m) => m,
+                None => {
+                    if val.is_none() {
+                        val = Some(self.read(key)?.val);
+                    }
+                    c.matches(val.as_deref().unwrap_or_default(), headers)
+                }
+            };
+            if !matched {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+pub fn topic_dir(root: &Path, topic_id: u128) -> PathBuf {
+    root.join(format!("{topic_id:032x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::OpenOptions;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use tonic::{Code, Status};
+
+    use crate::common::minimal::config::Config;
+    use crate::common::minimal::record::{Pushed, Record};
+    use crate::common::minimal::segment::segment_ids;
+
+    use super::{Fsync, TopicLog};
+
+    // a segment per record(rolled when the active one is not empty)
+    const SEGMENT_BYTES_TINY: u64 = 1;
+
+    fn config() -> Config {
+        Config {
+            created: 0,
+            dead_letter: None,
+            safe_tail: false,
+            default_ttl_us: None,
+            priority: false,
+            retain_messages: None,
+            retain_age_us: None,
+            retain_bytes: None,
+        }
+    }
+
+    fn pushed(key: i64, val: &[u8]) -> Record {
+        Record::Push(Pushed {
+            key,
+            pushed: key,
+            visible_at: 0,
+            expires_at: None,
+            priority: 0,
+            req_id: None,
+            attempts: 0,
+            last_error: None,
+            headers: HashMap::new(),
+            val: val.into(),
+        })
+    }
+
+    fn topic_dir(root: &TempDir) -> PathBuf {
+        root.path().join("t")
+    }
+
+    fn keys(log: &TopicLog) -> Vec<i64> {
+        log.as_entries().keys().copied().collect()
+    }
+
+    fn cut(dir: &Path, id: u64, len: u64) {
+        let path: PathBuf = dir.join(format!("{id:020}.seg"));
+        let f = OpenOptions::new().write(true).open(path).unwrap();
+        f.set_len(len).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn torn_tail_truncated() -> Result<(), Status> {
+        let root: TempDir = tempfile::tempdir().unwrap();
+        let dir: PathBuf = topic_dir(&root);
+        let mut log = TopicLog::create(dir.clone(), config(), 1 << 20, Fsync::Always)?;
+        for key in 1..=3 {
+            log.append(pushed(key, b"value"))?;
+        }
+        log.commit()?;
+        let torn = &log.as_entries()[&3];
+        let (valid, cut_at) = (torn.offset, torn.offset + torn.len as u64 - 3);
+        let id: u64 = torn.segment;
+        drop(log);
+
+        cut(&dir, id, cut_at);
+        let mut log = TopicLog::open(dir.clone(), 1 << 20, Fsync::Always)?;
+        assert_eq!(keys(&log), vec![1, 2]);
+        assert_eq!(log.size(), valid);
+        assert_eq!(
+            std::fs::metadata(dir.join(format!("{id:020}.seg")))
+                .unwrap()
+                .len(),
+            valid
+        );
+        assert_eq!(log.next_key(), 3);
+
+        // the torn tail is overwritten
+        log.append(pushed(3, b"rewritten"))?;
+        log.commit()?;
+        drop(log);
+        let log = TopicLog::open(dir, 1 << 20, Fsync::Always)?;
+        assert_eq!(keys(&log), vec![1, 2, 3]);
+        assert_eq!(log.read(3)?.val, b"rewritten".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn broken_sealed_segment_rejected() -> Result<(), Status> {
+        let root: TempDir = tempfile::tempdir().unwrap();
+        let dir: PathBuf = topic_dir(&root);
+        let mut log = TopicLog::create(dir.clone(), config(), SEGMENT_BYTES_TINY, Fsync::Always)?;
+        log.append(pushed(1, b"sealed"))?;
+        log.append(pushed(2, b"active"))?;
+        log.commit()?;
+        let sealed = &log.as_entries()[&1];
+        let (id, cut_at) = (sealed.segment, sealed.offset + 1);
+        drop(log);
+
+        cut(&dir, id, cut_at);
+        let opened = TopicLog::open(dir, SEGMENT_BYTES_TINY, Fsync::Always);
+        assert_eq!(opened.err().map(|e| e.code()), Some(Code::DataLoss));
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn index_rebuilt() -> Result<(), Status> {
+        let root: TempDir = tempfile::tempdir().unwrap();
+        let dir: PathBuf = topic_dir(&root);
+        let mut log = TopicLog::create(dir.clone(), config(), SEGMENT_BYTES_TINY, Fsync::Always)?;
+        log.append(Record::Push(Pushed {
+            key: 1,
+            pushed: 10,
+            visible_at: 20,
+            expires_at: Some(30),
+            priority: 7,
+            req_id: Some(42),
+            attempts: 0,
+            last_error: None,
+            headers: HashMap::from([("content-type".into(), b"text/plain".to_vec())]),
+            val: b"typed".to_vec(),
+        }))?;
+        log.append(pushed(2, b"deleted"))?;
+        log.append(pushed(3, b"leased"))?;
+        log.append(Record::Lease {
+            key: 3,
+            lease_id: Some(99),
+            until: 100,
+        })?;
+        log.append(Record::Fail {
+            key: 3,
+            attempts: 2,
+            error: "failed".into(),
+        })?;
+        log.append(Record::Delete { key: 2 })?;
+        log.commit()?;
+        let live: Vec<(u64, u64)> = log
+            .segments
+            .values()
+            .map(|s| (s.as_id(), s.as_live()))
+            .collect();
+        drop(log);
+
+        let log = TopicLog::open(dir, SEGMENT_BYTES_TINY, Fsync::Always)?;
+        assert_eq!(keys(&log), vec![1, 3]);
+        assert_eq!(log.next_key(), 4);
+        assert_eq!(log.pushed_by_request(42, 0), Some((1, 10)));
+        let typed = &log.as_entries()[&1];
+        assert_eq!(
+            (
+                typed.priority,
+                typed.pushed,
+                typed.visible_at,
+                typed.expires_at
+            ),
+            (7, 10, 20, Some(30))
+        );
+        assert_eq!(typed.as_headers()["content-type"], b"text/plain".to_vec());
+        assert_eq!(typed.as_val_len(), 5);
+        let leased = &log.as_entries()[&3];
+        assert_eq!(
+            (leased.lease_id, leased.leased_until, leased.attempts),
+            (Some(99), Some(100), 2)
+        );
+        assert_eq!(leased.last_error.as_deref(), Some("failed"));
+        assert_eq!(log.read(1)?.val, b"typed".to_vec());
+        assert_eq!(log.read(3)?.val, b"leased".to_vec());
+        let relive: Vec<(u64, u64)> = log
+            .segments
+            .values()
+            .map(|s| (s.as_id(), s.as_live()))
+            .collect();
+        assert_eq!(relive, live);
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn fsync_policy() -> Result<(), Status> {
+        let root: TempDir = tempfile::tempdir().unwrap();
+        let mut always = TopicLog::create(root.path().join("a"), config(), 1 << 20, Fsync::Always)?;
+        always.append(pushed(1, b"a"))?;
+        assert!(always.dirty);
+        always.commit()?;
+        assert!(!always.dirty);
+
+        let mut deferred =
+            TopicLog::create(root.path().join("d"), config(), 1 << 20, Fsync::Deferred)?;
+        deferred.append(pushed(1, b"d"))?;
+        deferred.commit()?;
+        assert!(deferred.dirty);
+        deferred.sync()?;
+        assert!(!deferred.dirty);
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn dead_segments_removed() -> Result<(), Status> {
+        let root: TempDir = tempfile::tempdir().unwrap();
+        let dir: PathBuf = topic_dir(&root);
+        let mut log = TopicLog::create(dir.clone(), config(), SEGMENT_BYTES_TINY, Fsync::Always)?;
+        for key in 1..=3 {
+            log.append(pushed(key, b"value"))?;
+        }
+        // (head), (head, 1), (head, 2), (head, 3)
+        assert_eq!(segment_ids(&dir)?, vec![1, 2, 3, 4]);
+        assert_eq!(log.remove_dead_segments(0)?, 1);
+        assert_eq!(segment_ids(&dir)?, vec![2, 3, 4]);
+
+        // the segment of 2 is kept until the older one removed
+        log.append(Record::Delete { key: 2 })?;
+        assert_eq!(log.remove_dead_segments(0)?, 0);
+        log.append(Record::Delete { key: 1 })?;
+        assert_eq!(log.remove_dead_segments(0)?, 2);
+        assert_eq!(segment_ids(&dir)?, vec![4, 5, 6]);
+
+        // the active segment is never removed
+        log.append(Record::Delete { key: 3 })?;
+        assert_eq!(log.remove_dead_segments(0)?, 3);
+        assert_eq!(segment_ids(&dir)?, vec![7]);
+        log.commit()?;
+        drop(log);
+
+        // keys are not reused
+        let log = TopicLog::open(dir, SEGMENT_BYTES_TINY, Fsync::Always)?;
+        assert!(log.as_entries().is_empty());
+        assert_eq!(log.next_key(), 4);
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn requests_kept_after_delete() -> Result<(), Status> {
+        let root: TempDir = tempfile::tempdir().unwrap();
+        let dir: PathBuf = topic_dir(&root);
+        let mut log = TopicLog::create(dir.clone(), config(), SEGMENT_BYTES_TINY, Fsync::Always)?;
+        log.append(Record::Push(Pushed {
+            key: 1,
+            pushed: 10,
+            visible_at: 0,
+            expires_at: None,
+            priority: 0,
+            req_id: Some(42),
+            attempts: 0,
+            last_error: None,
+            headers: HashMap::new(),
+            val: b"requested".to_vec(),
+        }))?;
+        log.append(pushed(2, b"active"))?;
+        log.append(Record::Delete { key: 1 })?;
+        log.commit()?;
+        assert_eq!(log.pushed_by_request(42, 10), Some((1, 10)));
+
+        // the segment of the request is kept within the window
+        assert_eq!(log.remove_dead_segments(10)?, 1);
+        assert_eq!(segment_ids(&dir)?, vec![2, 3, 4]);
+        drop(log);
+        let mut log = TopicLog::open(dir.clone(), SEGMENT_BYTES_TINY, Fsync::Always)?;
+        assert_eq!(keys(&log), vec![2]);
+        assert_eq!(log.pushed_by_request(42, 10), Some((1, 10)));
+
+        // out of the window
+        assert_eq!(log.pushed_by_request(42, 11), None);
+        log.sweep_requests(11);
+        assert_eq!(log.pushed_by_request(42, 0), None);
+        assert_eq!(log.remove_dead_segments(11)?, 1);
+        assert_eq!(segment_ids(&dir)?, vec![3, 4]);
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use tonic::Status;
+
+// a frame: (payload length: u32 BE, crc32 of the payload: u32 BE, payload)
+// a payload: (kind: u8, fields in BE)
+
+pub const FRAME_HEADER_LEN: usize = 8;
+
+// keeps a broken length from allocating the whole memory
+pub const PAYLOAD_LEN_MAX: usize = 1 << 30;
+
+const KIND_HEAD: u8 = 1;
+const KIND_PUSH: u8 = 2;
+const KIND_DELETE: u8 = 3;
+const KIND_LEASE: u8 = 4;
+const KIND_FAIL: u8 = 5;
+
+pub struct Pushed {
+    pub key: i64,
+    pub pushed: i64,
+    pub visible_at: i64,
+    pub expires_at: Option<i64>,
+    pub priority: i32,
+    pub req_id: Option<u128>,
+    pub attempts: u64,
+    pub last_error: Option<String>,
+    pub headers: HashMap<String, Vec<u8>>,
+    pub val: Vec<u8>,
+}
+
+pub enum Record {
+    // the first record of a segment(keys are never reused after the older segments removed)
+    Head {
+        last_key: i64,
+    },
+    Push(Pushed),
+    Delete {
+        key: i64,
+    },
+    // lease id: None for a nack
+    Lease {
+        key: i64,
+        lease_id: Option<u128>,
+        until: i64,
+    },
+    Fail {
+        key: i64,
+        attempts: u64,
+        error: String,
+    },
+}
+
+struct Fields<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    #[allow(clippy::result_large_err)]
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Status> {
+        if self.rest.len() < len {
+            return Err(Status::data_loss("broken record"));
+        }
+        let (taken, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Ok(taken)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Status> {
+        let mut a: [u8; N] = [0; N];
+        a.copy_from_slice(self.take(N)?);
+        Ok(a)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn u8(&mut self) -> Result<u8, Status> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn i32(&mut self) -> Result<i32, Status> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn i64(&mut self) -> Result<i64, Status> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn u64(&mut self) -> Result<u64, Status> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn u128(&mut self) -> Result<u128, Status> {
+        Ok(u128::from_be_bytes(self.array()?))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn bytes(&mut self) -> Result<&'a [u8], Status> {
+        let len: usize = u32::from_be_bytes(self.array()?) as usize;
+        self.take(len)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn string(&mut self) -> Result<String, Status> {
+        String::from_utf8(self.bytes()?.into())
+            .map_err(|e| Status::data_loss(format!("broken string: {e}")))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn optional<T, F>(&mut self, f: F) -> Result<Option<T>, Status>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Status>,
+    {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => f(self).map(Some),
+        }
+    }
+}
+
+fn put_bytes(buf: &mut Vec<u8>, b: &[u8]) {
+    buf.extend_from_slice(&(b.len() as u32).to_be_bytes());
+    buf.extend_from_slice(b);
+}
+
+fn put_optional<T, F>(buf: &mut Vec<u8>, o: Option<T>, f: F)
+where
+    F: FnOnce(&mut Vec<u8>, T),
+{
+    match o {
+        None => buf.push(0),
+        Some(t) => {
+            buf.push(1);
+            f(buf, t)
+        }
+    }
+}
+
+impl Record {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Head { last_key } => {
+                buf.push(KIND_HEAD);
+                buf.extend_from_slice(&last_key.to_be_bytes());
+            }
+            Self::Push(p) => {
+                buf.push(KIND_PUSH);
+                buf.extend_from_slice(&p.key.to_be_bytes());
+                buf.extend_from_slice(&p.pushed.to_be_bytes());
+                buf.extend_from_slice(&p.visible_at.to_be_bytes());
+                put_optional(buf, p.expires_at, |b, t| {
+                    b.extend_from_slice(&t.to_be_bytes())
+                });
+                buf.extend_from_slice(&p.priority.to_be_bytes());
+                put_optional(buf, p.req_id, |b, r| b.extend_from_slice(&r.to_be_bytes()));
+                buf.extend_from_slice(&p.attempts.to_be_bytes());
+                put_optional(buf, p.last_error.as_ref(), |b, e| {
+                    put_bytes(b, e.as_bytes())
+                });
+                let mut sorted: Vec<(&String, &Vec<u8>)> = p.headers.iter().collect();
+                sorted.sort_unstable();
+                buf.extend_from_slice(&(sorted.len() as u32).to_be_bytes());
+                for (name, value) in sorted {
+                    put_bytes(buf, name.as_bytes());
+                    put_bytes(buf, value);
+                }
+                put_bytes(buf, &p.val);
+            }
+            Self::Delete { key } => {
+                buf.push(KIND_DELETE);
+                buf.extend_from_slice(&key.to_be_bytes());
+            }
+            Self::Lease {
+                key,
+                lease_id,
+                until,
+            } => {
+                buf.push(KIND_LEASE);
+                buf.extend_from_slice(&key.to_be_bytes());
+                put_optional(buf, *lease_id, |b, l| b.extend_from_slice(&l.to_be_bytes()));
+                buf.extend_from_slice(&until.to_be_bytes());
+            }
+            Self::Fail {
+                key,
+                attempts,
+                error,
+            } => {
+                buf.push(KIND_FAIL);
+                buf.extend_from_slice(&key.to_be_bytes());
+                buf.extend_from_slice(&attempts.to_be_bytes());
+                put_bytes(buf, error.as_bytes());
+            }
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn decode(payload: &[u8]) -> Result<Self, Status> {
+        let mut f = Fields { rest: payload };
+        match f.u8()? {
+            KIND_HEAD => Ok(Self::Head { last_key: f.i64()? }),
+            KIND_PUSH => {
+                let key: i64 = f.i64()?;
+                let pushed: i64 = f.i64()?;
+                let visible_at: i64 = f.i64()?;
+                let expires_at: Option<i64> = f.optional(Fields::i64)?;
+                let priority: i32 = f.i32()?;
+                let req_id: Option<u128> = f.optional(Fields::u128)?;
+                let attempts: u64 = f.u64()?;
+                let last_error: Option<String> = f.optional(Fields::string)?;
+                let cnt: u32 = u32::from_be_bytes(f.array()?);
+                let mut headers: HashMap<String, Vec<u8>> = HashMap::new();
+                for _ in 0..cnt {
+                    let name: String = f.string()?;
+                    headers.insert(name, f.bytes()?.into());
+                }
+                let val: Vec<u8> = f.bytes()?.into();
+                Ok(Self::Push(Pushed {
+                    key,
+                    pushed,
+                    visible_at,
+                    expires_at,
+                    priority,
+                    req_id,
+                    attempts,
+                    last_error,
+                    headers,
+                    val,
+                }))
+            }
+            KIND_DELETE => Ok(Self::Delete { key: f.i64()? }),
+            KIND_LEASE => Ok(Self::Lease {
+                key: f.i64()?,
+                lease_id: f.optional(Fields::u128)?,
+                until: f.i64()?,
+            }),
+            KIND_FAIL => Ok(Self::Fail {
+                key: f.i64()?,
+                attempts: f.u64()?,
+                error: f.string()?,
+            }),
+            kind => Err(Status::data_loss(format!("unknown record kind: {kind}"))),
+        }
+    }
+
+    pub fn frame(&self) -> Vec<u8> {
+        let mut payload: Vec<u8> = Vec::new();
+        self.encode(&mut payload);
+        let mut framed: Vec<u8> = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        framed.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        framed.extend_from_slice(&crc32fast::hash(&payload).to_be_bytes());
+        framed.extend_from_slice(&payload);
+        framed
+    }
+
+    pub fn key(&self) -> i64 {
+        match self {
+            Self::Head { last_key } => *last_key,
+            Self::Push(p) => p.key,
+            Self::Delete { key } => *key,
+            Self::Lease { key, .. } => *key,
+            Self::Fail { key, .. } => *key,
+        }
+    }
+}
+
+// (payload length, crc32)
+pub fn frame_header(header: [u8; FRAME_HEADER_LEN]) -> (usize, u32) {
+    let (len, crc) = header.split_at(4);
+    let len: u32 = u32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+    let crc: u32 = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+    (len as usize, crc)
+}
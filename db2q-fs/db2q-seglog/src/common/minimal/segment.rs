@@ -0,0 +1,223 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use tonic::Status;
+
+use crate::common::minimal::record::{self, Record, FRAME_HEADER_LEN, PAYLOAD_LEN_MAX};
+
+const SUFFIX: &str = ".seg";
+
+pub fn io2status(e: std::io::Error, msg: &str) -> Status {
+    match e.kind() {
+        ErrorKind::StorageFull => Status::resource_exhausted(format!("{msg}: {e}")),
+        _ => Status::internal(format!("{msg}: {e}")),
+    }
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:020}{SUFFIX}"))
+}
+
+// ascending
+#[allow(clippy::result_large_err)]
+pub fn segment_ids(dir: &Path) -> Result<Vec<u64>, Status> {
+    let mut ids: Vec<u64> = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| io2status(e, "Unable to list segments"))? {
+        let entry = entry.map_err(|e| io2status(e, "Unable to list segments"))?;
+        let name: String = entry.file_name().to_string_lossy().into_owned();
+        if let Some(id) = name
+            .strip_suffix(SUFFIX)
+            .and_then(|s: &str| str::parse(s).ok())
+        {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+// reads one frame; None if torn or broken
+#[allow(clippy::result_large_err)]
+fn read_frame<R: Read>(r: &mut R) -> Result<Option<(usize, Record)>, Status> {
+    let mut header: [u8; FRAME_HEADER_LEN] = [0; FRAME_HEADER_LEN];
+    match r.read_exact(&mut header) {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(io2status(e, "Unable to read a segment")),
+    }
+    let (len, crc) = record::frame_header(header);
+    if PAYLOAD_LEN_MAX < len {
+        return Ok(None);
+    }
+    let mut payload: Vec<u8> = vec![0; len];
+    match r.read_exact(&mut payload) {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(io2status(e, "Unable to read a segment")),
+    }
+    if crc32fast::hash(&payload) != crc {
+        return Ok(None);
+    }
+    Ok(Record::decode(&payload)
+        .ok()
+        .map(|rec: Record| (FRAME_HEADER_LEN + len, rec)))
+}
+
+pub struct Segment {
+    id: u64,
+    path: PathBuf,
+    file: File,
+    size: u64,
+    // pushed to this segment and not deleted yet
+    live: u64,
+    // the newest push with a request id(kept for the dedup window)
+    requested: Option<i64>,
+}
+
+impl Segment {
+    fn open_options() -> OpenOptions {
+        let mut o: OpenOptions = OpenOptions::new();
+        o.read(true).write(true);
+        o
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn create(dir: &Path, id: u64) -> Result<Self, Status> {
+        let path: PathBuf = segment_path(dir, id);
+        let file: File = Self::open_options()
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| io2status(e, "Unable to create a segment"))?;
+        Ok(Self {
+            id,
+            path,
+            file,
+            size: 0,
+            live: 0,
+            requested: None,
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn open(dir: &Path, id: u64) -> Result<Self, Status> {
+        let path: PathBuf = segment_path(dir, id);
+        let file: File = Self::open_options()
+            .open(&path)
+            .map_err(|e| io2status(e, "Unable to open a segment"))?;
+        let size: u64 = file
+            .metadata()
+            .map_err(|e| io2status(e, "Unable to get a segment size"))?
+            .len();
+        Ok(Self {
+            id,
+            path,
+            file,
+            size,
+            live: 0,
+            requested: None,
+        })
+    }
+
+    pub fn as_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn as_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn as_live(&self) -> u64 {
+        self.live
+    }
+
+    pub fn inc_live(&mut self) {
+        self.live += 1;
+    }
+
+    pub fn reset_live(&mut self) {
+        self.live = 0;
+    }
+
+    pub fn dec_live(&mut self) {
+        self.live = self.live.saturating_sub(1);
+    }
+
+    pub fn as_requested(&self) -> Option<i64> {
+        self.requested
+    }
+
+    pub fn set_requested(&mut self, pushed: i64) {
+        self.requested = self.requested.max(Some(pushed));
+    }
+
+    // the offset of the appended frame(a torn tail is overwritten)
+    #[allow(clippy::result_large_err)]
+    pub fn append(&mut self, framed: &[u8]) -> Result<u64, Status> {
+        let offset: u64 = self.size;
+        self.file
+            .write_all_at(framed, offset)
+            .map_err(|e| io2status(e, "Unable to append a record"))?;
+        self.size += framed.len() as u64;
+        Ok(offset)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn sync(&self) -> Result<(), Status> {
+        self.file
+            .sync_data()
+            .map_err(|e| io2status(e, "Unable to sync a segment"))
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn read(&self, offset: u64, len: usize) -> Result<Record, Status> {
+        let mut framed: Vec<u8> = vec![0; len];
+        self.file
+            .read_exact_at(&mut framed, offset)
+            .map_err(|e| io2status(e, "Unable to read a record"))?;
+        read_frame(&mut framed.as_slice())?
+            .map(|(_, rec)| rec)
+            .ok_or_else(|| {
+                Status::data_loss(format!(
+                    "broken record. segment: {}, offset: {offset}",
+                    self.id
+                ))
+            })
+    }
+
+    // (offset, frame length, record) for each complete record; the length of the valid prefix
+    #[allow(clippy::result_large_err)]
+    pub fn scan<F>(&self, mut f: F) -> Result<u64, Status>
+    where
+        F: FnMut(u64, usize, Record) -> Result<(), Status>,
+    {
+        let file: File =
+            File::open(&self.path).map_err(|e| io2status(e, "Unable to open a segment"))?;
+        let mut r = BufReader::new(file);
+        let mut offset: u64 = 0;
+        while let Some((len, rec)) = read_frame(&mut r)? {
+            f(offset, len, rec)?;
+            offset += len as u64;
+        }
+        Ok(offset)
+    }
+
+    // drops a torn tail
+    #[allow(clippy::result_large_err)]
+    pub fn truncate(&mut self, len: u64) -> Result<(), Status> {
+        self.file
+            .set_len(len)
+            .map_err(|e| io2status(e, "Unable to truncate a segment"))?;
+        self.file
+            .sync_all()
+            .map_err(|e| io2status(e, "Unable to sync a segment"))?;
+        self.size = len;
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn remove(self) -> Result<(), Status> {
+        fs::remove_file(&self.path).map_err(|e| io2status(e, "Unable to remove a segment"))
+    }
+}
@@ -0,0 +1,792 @@
+use core::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use tokio::sync::Notify;
+
+use tonic::Status;
+
+use db2q::uuid::Uuid;
+
+use db2q::queue::cmd::filter::Filter;
+use db2q::queue::cmd::keys::KeysReq;
+use db2q::queue::cmd::push::{PushBackReq, DEDUP_WINDOW};
+use db2q::topic::cmd::create::CreateReq;
+use db2q::topic::cmd::retention::Retention;
+
+use db2q::db2q::proto::queue::v1::q_svc::{KeysResponse, NextResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{
+    DeadLetter as DeadLetterPb, DescribeResponse, Retention as RetentionPb,
+};
+
+use crate::common::minimal::config::Config;
+use crate::common::minimal::log::{topic_dir, Entry, Fsync, TopicLog};
+use crate::common::minimal::record::{Pushed, Record};
+use crate::common::minimal::segment::io2status;
+use crate::common::minimal::time::{dur2micros, micros2dur, micros2time, now_micros, time2micros};
+
+pub const SEGMENT_BYTES_DEFAULT: u64 = 64 * 1024 * 1024;
+
+// the same codes as a missing table of the rdb backends
+fn missing(topic_id: Uuid, msg: &str) -> Status {
+    Status::internal(format!("{msg}: no such topic: {topic_id}"))
+}
+
+fn missing_to_push(topic_id: Uuid) -> Status {
+    Status::not_found(format!("No such topic: {topic_id}"))
+}
+
+fn no_lease(key: i64, lease_id: Uuid) -> Status {
+    Status::not_found(format!(
+        "No such lease(expired?). key: {key}, lease id: {lease_id}"
+    ))
+}
+
+fn retain(config: &mut Config, r: Option<&Retention>) {
+    config.retain_messages = r.and_then(|r| r.as_max_messages());
+    config.retain_age_us = r.and_then(|r| r.as_max_age()).map(dur2micros);
+    config.retain_bytes = r.and_then(|r| r.as_max_bytes());
+}
+
+// the highest key to be trimmed(if any)
+fn boundary(t: &TopicLog, now: i64) -> Option<i64> {
+    let c: &Config = t.as_config();
+    let entries: &BTreeMap<i64, Entry> = t.as_entries();
+    let by_count: Option<i64> = c.retain_messages.and_then(|n| {
+        let skip: usize = n.try_into().unwrap_or(usize::MAX);
+        entries.keys().rev().nth(skip).copied()
+    });
+    let by_age: Option<i64> = c.retain_age_us.and_then(|age| {
+        let oldest: i64 = now.saturating_sub(age);
+        entries
+            .iter()
+            .rev()
+            .find(|(_, e)| e.pushed < oldest)
+            .map(|(k, _)| *k)
+    });
+    let by_bytes: Option<i64> = c.retain_bytes.and_then(|max| {
+        let mut total: u64 = 0;
+        entries.iter().rev().find_map(|(k, e)| {
+            total = total.saturating_add(e.as_val_len());
+            (max < total).then_some(*k)
+        })
+    });
+    [by_count, by_age, by_bytes].into_iter().flatten().max()
+}
+
+pub struct Store {
+    root: PathBuf,
+    segment_bytes: u64,
+    fsync: Fsync,
+    topics: Mutex<BTreeMap<u128, TopicLog>>,
+}
+
+impl Store {
+    #[allow(clippy::result_large_err)]
+    fn lock(&self) -> Result<MutexGuard<'_, BTreeMap<u128, TopicLog>>, Status> {
+        self.topics
+            .lock()
+            .map_err(|e| Status::internal(format!("Unable to lock: {e}")))
+    }
+
+    // file io is done outside of the async runtime
+    #[allow(clippy::result_large_err)]
+    pub async fn run<F, R>(self: &Arc<Self>, f: F) -> Result<R, Status>
+    where
+        F: FnOnce(&Store) -> Result<R, Status> + Send + 'static,
+        R: Send + 'static,
+    {
+        let store: Arc<Store> = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|e| Status::internal(format!("Unable to join: {e}")))?
+    }
+
+    // appended records are committed even on errors
+    #[allow(clippy::result_large_err)]
+    fn with_topic<F, R>(&self, topic_id: Uuid, msg: &str, f: F) -> Result<R, Status>
+    where
+        F: FnOnce(&mut TopicLog, i64) -> Result<R, Status>,
+    {
+        self.with_topic_or(topic_id, || missing(topic_id, msg), f)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn with_topic_or<M, F, R>(&self, topic_id: Uuid, missing: M, f: F) -> Result<R, Status>
+    where
+        M: FnOnce() -> Status,
+        F: FnOnce(&mut TopicLog, i64) -> Result<R, Status>,
+    {
+        let mut topics = self.lock()?;
+        let t: &mut TopicLog = topics.get_mut(&topic_id.as_u128()).ok_or_else(missing)?;
+        let rslt: Result<R, Status> = f(t, now_micros());
+        t.commit()?;
+        rslt
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn create(&self, checked: &CreateReq) -> Result<(), Status> {
+        let topic_id: Uuid = checked.as_topic_id();
+        let mut topics = self.lock()?;
+        if topics.contains_key(&topic_id.as_u128()) {
+            return Err(Status::internal(format!(
+                "Unexpected error: topic already exists: {topic_id}"
+            )));
+        }
+        let dead_letter: Option<(u128, u64)> = checked
+            .as_dead_letter()
+            .map(|d| (d.as_topic_id().as_u128(), d.as_max_attempts()));
+        if let Some((dlq, _)) = dead_letter {
+            let found: bool = dlq == topic_id.as_u128() || topics.contains_key(&dlq);
+            if !found {
+                return Err(Status::failed_precondition(format!(
+                    "dead letter topic missing: {}",
+                    Uuid::from(dlq)
+                )));
+            }
+        }
+        let mut config = Config {
+            created: now_micros(),
+            dead_letter,
+            safe_tail: checked.as_safe_tail(),
+            default_ttl_us: checked.as_default_ttl().map(dur2micros),
+            priority: checked.as_priority(),
+            retain_messages: None,
+            retain_age_us: None,
+            retain_bytes: None,
+        };
+        retain(&mut config, checked.as_retention());
+        let dir: PathBuf = topic_dir(&self.root, topic_id.as_u128());
+        let created = TopicLog::create(dir, config, self.segment_bytes, self.fsync)?;
+        topics.insert(topic_id.as_u128(), created);
+        Ok(())
+    }
+
+    // waiters of the dropped topic get an error on their next check
+    #[allow(clippy::result_large_err)]
+    pub fn drop_topic(&self, topic_id: Uuid) -> Result<(), Status> {
+        let mut topics = self.lock()?;
+        let used: bool = topics.iter().any(|(id, t)| {
+            *id != topic_id.as_u128()
+                && t.as_config()
+                    .dead_letter
+                    .map(|(dlq, _)| dlq == topic_id.as_u128())
+                    .unwrap_or_default()
+        });
+        if used {
+            return Err(Status::failed_precondition(format!(
+                "still used as a dead letter topic: {topic_id}"
+            )));
+        }
+        match topics.remove(&topic_id.as_u128()) {
+            None => Ok(()),
+            Some(dropped) => dropped.remove_all(),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn list(&self) -> Result<Vec<Uuid>, Status> {
+        let topics = self.lock()?;
+        Ok(topics.keys().map(|id: &u128| Uuid::from(*id)).collect())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn update_retention(
+        &self,
+        topic_id: Uuid,
+        retention: Option<&Retention>,
+    ) -> Result<(), Status> {
+        let mut topics = self.lock()?;
+        let t: &mut TopicLog = topics
+            .get_mut(&topic_id.as_u128())
+            .ok_or_else(|| Status::not_found(format!("No such topic: {topic_id}")))?;
+        let mut config: Config = t.as_config().clone();
+        retain(&mut config, retention);
+        t.set_config(config)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn describe(&self, topic_id: Uuid) -> Result<DescribeResponse, Status> {
+        let topics = self.lock()?;
+        let t: &TopicLog = topics
+            .get(&topic_id.as_u128())
+            .ok_or_else(|| Status::not_found(format!("No such topic: {topic_id}")))?;
+        let c: &Config = t.as_config();
+        let entries: &BTreeMap<i64, Entry> = t.as_entries();
+        let us2dur = |us: i64| micros2dur(us).try_into().ok();
+        let retention: Option<RetentionPb> =
+            match (c.retain_messages, c.retain_age_us, c.retain_bytes) {
+                (None, None, None) => None,
+                _ => Some(RetentionPb {
+                    max_messages: c.retain_messages.unwrap_or_default(),
+                    max_age: c.retain_age_us.and_then(us2dur),
+                    max_bytes: c.retain_bytes.unwrap_or_default(),
+                }),
+            };
+        let described = DescribeResponse {
+            min_key: entries.keys().next().copied().unwrap_or(-1),
+            max_key: entries.keys().next_back().copied().unwrap_or(-1),
+            count_estimate: entries.len() as u64,
+            payload_bytes: entries.values().map(Entry::as_val_len).sum(),
+            relation_size: t.size(),
+            created: Some(micros2time(c.created).into()),
+            dead_letter: c.dead_letter.map(|(dlq, max_attempts)| DeadLetterPb {
+                topic_id: Some(Uuid::from(dlq).into()),
+                max_attempts,
+            }),
+            safe_tail: c.safe_tail,
+            default_ttl: c.default_ttl_us.and_then(us2dur),
+            retention,
+            priority: c.priority,
+        };
+        Ok(described)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn watch(&self, topic_id: Uuid) -> Result<Arc<Notify>, Status> {
+        let topics = self.lock()?;
+        topics
+            .get(&topic_id.as_u128())
+            .map(|t: &TopicLog| t.as_notify().clone())
+            .ok_or_else(|| missing(topic_id, "Unable to select"))
+    }
+
+    // the original key and the pushed time for a duplicate request
+    #[allow(clippy::result_large_err)]
+    pub fn push(&self, checked: &PushBackReq) -> Result<(i64, SystemTime), Status> {
+        let req_id: u128 = checked.as_request_id().as_u128();
+        let topic_id: Uuid = checked.as_topic_id();
+        self.with_topic_or(
+            topic_id,
+            || missing_to_push(topic_id),
+            |t, now| {
+                let oldest: i64 = now.saturating_sub(dur2micros(DEDUP_WINDOW));
+                if let Some((key, pushed)) = t.pushed_by_request(req_id, oldest) {
+                    return Ok((key, micros2time(pushed)));
+                }
+                let visible_at: i64 =
+                    checked.as_not_before().map(time2micros).unwrap_or_else(|| {
+                        now.saturating_add(checked.as_delay().map(dur2micros).unwrap_or_default())
+                    });
+                let ttl_us: Option<i64> = checked
+                    .as_ttl()
+                    .map(dur2micros)
+                    .or(t.as_config().default_ttl_us);
+                let key: i64 = t.next_key();
+                t.append(Record::Push(Pushed {
+                    key,
+                    pushed: now,
+                    visible_at,
+                    expires_at: ttl_us.map(|us| now.saturating_add(us)),
+                    priority: checked.as_priority(),
+                    req_id: Some(req_id),
+                    attempts: 0,
+                    last_error: None,
+                    headers: checked.as_headers().clone(),
+                    val: checked.as_value().into(),
+                }))?;
+                t.as_notify().notify_waiters();
+                Ok((key, micros2time(now)))
+            },
+        )
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn push_batch(
+        &self,
+        topic_id: Uuid,
+        vals: &[Vec<u8>],
+    ) -> Result<(Vec<i64>, Option<SystemTime>), Status> {
+        self.with_topic_or(
+            topic_id,
+            || missing_to_push(topic_id),
+            |t, now| {
+                let expires_at: Option<i64> = t
+                    .as_config()
+                    .default_ttl_us
+                    .map(|us| now.saturating_add(us));
+                let mut keys: Vec<i64> = Vec::with_capacity(vals.len());
+                for v in vals {
+                    let key: i64 = t.next_key();
+                    t.append(Record::Push(Pushed {
+                        key,
+                        pushed: now,
+                        visible_at: now,
+                        expires_at,
+                        priority: 0,
+                        req_id: None,
+                        attempts: 0,
+                        last_error: None,
+                        headers: HashMap::new(),
+                        val: v.clone(),
+                    }))?;
+                    keys.push(key);
+                }
+                t.as_notify().notify_waiters();
+                let pushed: Option<SystemTime> = (!keys.is_empty()).then(|| micros2time(now));
+                Ok((keys, pushed))
+            },
+        )
+    }
+
+    // unset bounds are the widest ones
+    #[allow(clippy::result_large_err)]
+    pub fn count(
+        &self,
+        topic_id: Uuid,
+        keys: (Option<i64>, Option<i64>),
+        pushed: (Option<SystemTime>, Option<SystemTime>),
+    ) -> Result<u64, Status> {
+        self.with_topic(topic_id, "Unable to count", |t, now| {
+            let lower: i64 = keys.0.unwrap_or(0);
+            let upper: i64 = keys.1.unwrap_or(i64::MAX);
+            let since: Option<i64> = pushed.0.map(time2micros);
+            let until: Option<i64> = pushed.1.map(time2micros);
+            if upper <= lower {
+                return Ok(0);
+            }
+            let cnt: usize = t
+                .as_entries()
+                .range(lower..upper)
+                .filter(|(_, e)| e.alive(now))
+                .filter(|(_, e)| since.map(|s| s <= e.pushed).unwrap_or(true))
+                .filter(|(_, e)| until.map(|u| e.pushed < u).unwrap_or(true))
+                .count();
+            Ok(cnt as u64)
+        })
+    }
+
+    // (remaining, age of the oldest remaining, newest key)
+    #[allow(clippy::result_large_err)]
+    pub fn lag(&self, topic_id: Uuid, prev: i64) -> Result<(u64, Option<Duration>, i64), Status> {
+        self.with_topic(topic_id, "Unable to get a lag", |t, now| {
+            let entries: &BTreeMap<i64, Entry> = t.as_entries();
+            let mut remaining = entries
+                .range(prev.saturating_add(1)..)
+                .filter(|(_, e)| e.alive(now))
+                .peekable();
+            let oldest_age: Option<Duration> = remaining
+                .peek()
+                .map(|(_, e)| micros2dur(now.saturating_sub(e.pushed)));
+            let cnt: u64 = remaining.count() as u64;
+            let newest: i64 = entries.keys().next_back().copied().unwrap_or(-1);
+            Ok((cnt, oldest_age, newest))
+        })
+    }
+
+    // the first visible key which matches the filter
+    #[allow(clippy::result_large_err)]
+    fn first_match<'a, I>(
+        t: &TopicLog,
+        candidates: I,
+        filter: Option<&Filter>,
+        now: i64,
+    ) -> Result<Option<i64>, Status>
+    where
+        I: Iterator<Item = (&'a i64, &'a Entry)>,
+    {
+        for (key, e) in candidates {
+            if e.visible(now) && t.matches(*key, filter)? {
+                return Ok(Some(*key));
+            }
+        }
+        Ok(None)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn next(
+        &self,
+        topic_id: Uuid,
+        prev: Option<i64>,
+        filter: Option<&Filter>,
+    ) -> Result<NextResponse, Status> {
+        self.with_topic(topic_id, "Unable to select", |t, now| {
+            let lower: i64 = prev.unwrap_or(-1).saturating_add(1);
+            let found: Option<i64> =
+                Self::first_match(t, t.as_entries().range(lower..), filter, now)?;
+            match (found, prev) {
+                (Some(key), _) => t.item(key),
+                (None, None) => Err(Status::not_found("Empty queue")),
+                (None, Some(prev)) => Err(Status::not_found(format!(
+                    "No more queue items. previous key: {prev}"
+                ))),
+            }
+        })
+    }
+
+    // (priority desc, key) order
+    #[allow(clippy::result_large_err)]
+    pub fn next_prioritized(
+        &self,
+        topic_id: Uuid,
+        prev: Option<(i32, i64)>,
+        filter: Option<&Filter>,
+    ) -> Result<NextResponse, Status> {
+        self.with_topic(topic_id, "Unable to select", |t, now| {
+            let after = |priority: i32, key: i64| match prev {
+                None => true,
+                Some((p, k)) => priority < p || (priority == p && k < key),
+            };
+            let mut sorted: Vec<(&i64, &Entry)> = t
+                .as_entries()
+                .iter()
+                .filter(|(k, e)| after(e.priority, **k))
+                .collect();
+            sorted.sort_by_key(|(k, e)| (-(e.priority as i64), **k));
+            let found: Option<i64> = Self::first_match(t, sorted.into_iter(), filter, now)?;
+            match found {
+                Some(key) => t.item(key),
+                None => Err(Status::not_found(format!(
+                    "No more queue items. previous: {prev:?}"
+                ))),
+            }
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn next_batch(
+        &self,
+        topic_id: Uuid,
+        prev: i64,
+        limit: usize,
+    ) -> Result<Vec<NextResponse>, Status> {
+        self.with_topic(topic_id, "Unable to select", |t, now| {
+            let keys: Vec<i64> = t
+                .as_entries()
+                .range(prev.saturating_add(1)..)
+                .filter(|(_, e)| e.visible(now))
+                .take(limit)
+                .map(|(k, _)| *k)
+                .collect();
+            keys.into_iter().map(|k: i64| t.item(k)).collect()
+        })
+    }
+
+    // the continuation is set on the last key of a full page
+    #[allow(clippy::result_large_err)]
+    pub fn keys(&self, checked: &KeysReq) -> Result<Vec<KeysResponse>, Status> {
+        self.with_topic(checked.as_topic_id(), "Unable to get keys", |t, now| {
+            let (start, end) = (
+                checked.as_start_after().map(|u| u as i64),
+                checked.as_end_before().map(|u| u as i64),
+            );
+            let (lower, upper) = match checked.as_descending() {
+                false => (start, end),
+                true => (end, start),
+            };
+            let lower: i64 = lower.unwrap_or(0);
+            let upper: i64 = upper.unwrap_or(i64::MAX);
+            if upper <= lower.saturating_add(1) {
+                return Ok(Vec::new());
+            }
+            let range = t.as_entries().range(lower + 1..upper);
+            let candidates: Box<dyn Iterator<Item = (&i64, &Entry)>> = match checked.as_descending()
+            {
+                false => Box::new(range),
+                true => Box::new(range.rev()),
+            };
+            let limit: u64 = checked.as_max_keys();
+            let mut keys: Vec<KeysResponse> = Vec::new();
+            for (key, e) in candidates {
+                if !e.visible(now) || !t.matches(*key, checked.as_filter())? {
+                    continue;
+                }
+                if limit <= keys.len() as u64 {
+                    if let Some(last) = keys.last_mut() {
+                        last.continuation = last.key as i64;
+                    }
+                    break;
+                }
+                keys.push(KeysResponse {
+                    key: *key as u64,
+                    continuation: -1,
+                });
+            }
+            Ok(keys)
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn pop(&self, topic_id: Uuid, priority_order: bool) -> Result<NextResponse, Status> {
+        self.with_topic(topic_id, "Unable to delete", |t, now| {
+            let leasable = t.as_entries().iter().filter(|(_, e)| e.leasable(now));
+            let found: Option<i64> = match priority_order {
+                false => leasable.map(|(k, _)| *k).next(),
+                true => leasable
+                    .min_by_key(|(k, e)| (-(e.priority as i64), **k))
+                    .map(|(k, _)| *k),
+            };
+            let key: i64 = found.ok_or_else(|| Status::not_found("Empty queue"))?;
+            let item: NextResponse = t.item(key)?;
+            t.append(Record::Delete { key })?;
+            Ok(item)
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn lease(
+        &self,
+        topic_id: Uuid,
+        lease_id: Uuid,
+        timeout: Duration,
+    ) -> Result<(NextResponse, SystemTime), Status> {
+        self.with_topic(topic_id, "Unable to lease", |t, now| {
+            let until: i64 = now.saturating_add(dur2micros(timeout));
+            let key: i64 = t
+                .as_entries()
+                .iter()
+                .find(|(_, e)| e.leasable(now))
+                .map(|(k, _)| *k)
+                .ok_or_else(|| Status::not_found("No visible queue items"))?;
+            let item: NextResponse = t.item(key)?;
+            t.append(Record::Lease {
+                key,
+                lease_id: Some(lease_id.as_u128()),
+                until,
+            })?;
+            Ok((item, micros2time(until)))
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn check_lease(t: &TopicLog, key: i64, lease_id: Uuid, now: i64) -> Result<(), Status> {
+        match t.as_entries().get(&key) {
+            Some(e) if e.leased(lease_id.as_u128(), now) => Ok(()),
+            _ => Err(no_lease(key, lease_id)),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn ack(&self, topic_id: Uuid, key: i64, lease_id: Uuid) -> Result<(), Status> {
+        self.with_topic(topic_id, "Unable to ack", |t, now| {
+            Self::check_lease(t, key, lease_id, now)?;
+            t.append(Record::Delete { key })
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn nack(
+        &self,
+        topic_id: Uuid,
+        key: i64,
+        lease_id: Uuid,
+        delay: Option<Duration>,
+    ) -> Result<(), Status> {
+        self.with_topic(topic_id, "Unable to nack", |t, now| {
+            Self::check_lease(t, key, lease_id, now)?;
+            let until: i64 = now.saturating_add(delay.map(dur2micros).unwrap_or_default());
+            t.append(Record::Lease {
+                key,
+                lease_id: None,
+                until,
+            })
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn extend_lease(
+        &self,
+        topic_id: Uuid,
+        key: i64,
+        lease_id: Uuid,
+        extension: Duration,
+    ) -> Result<SystemTime, Status> {
+        self.with_topic(topic_id, "Unable to extend a lease", |t, now| {
+            Self::check_lease(t, key, lease_id, now)?;
+            let until: i64 = now.saturating_add(dur2micros(extension));
+            t.append(Record::Lease {
+                key,
+                lease_id: Some(lease_id.as_u128()),
+                until,
+            })?;
+            Ok(micros2time(until))
+        })
+    }
+
+    // a copy pushed to another topic(dead letters, redrives)
+    #[allow(clippy::result_large_err)]
+    fn moved(t: &mut TopicLog, mut p: Pushed, now: i64) -> Result<(), Status> {
+        p.key = t.next_key();
+        p.pushed = now;
+        p.visible_at = now;
+        p.expires_at = t
+            .as_config()
+            .default_ttl_us
+            .map(|us| now.saturating_add(us));
+        p.req_id = None;
+        t.append(Record::Push(p))?;
+        t.commit()?;
+        t.as_notify().notify_waiters();
+        Ok(())
+    }
+
+    // the copy is committed before the delete: a crash between leaves a duplicate, not a loss
+    #[allow(clippy::result_large_err)]
+    fn move_to(
+        topics: &mut BTreeMap<u128, TopicLog>,
+        src: Uuid,
+        dst: Uuid,
+        key: i64,
+        p: Pushed,
+        msg: &str,
+    ) -> Result<(), Status> {
+        let now: i64 = now_micros();
+        let d: &mut TopicLog = topics
+            .get_mut(&dst.as_u128())
+            .ok_or_else(|| missing(dst, msg))?;
+        Self::moved(d, p, now)?;
+        let s: &mut TopicLog = topics
+            .get_mut(&src.as_u128())
+            .ok_or_else(|| missing(src, msg))?;
+        s.append(Record::Delete { key })?;
+        s.commit()
+    }
+
+    // (attempts, dead lettered)
+    #[allow(clippy::result_large_err)]
+    pub fn report_failure(
+        &self,
+        topic_id: Uuid,
+        key: i64,
+        error: &str,
+    ) -> Result<(u64, bool), Status> {
+        let mut topics = self.lock()?;
+        let topic: &mut TopicLog = topics
+            .get_mut(&topic_id.as_u128())
+            .ok_or_else(|| missing(topic_id, "Unable to record a failure"))?;
+        let attempts: u64 = topic
+            .as_entries()
+            .get(&key)
+            .map(|e: &Entry| e.attempts + 1)
+            .ok_or_else(|| Status::not_found(format!("No such queue item. key: {key}")))?;
+        topic.append(Record::Fail {
+            key,
+            attempts,
+            error: error.into(),
+        })?;
+        topic.commit()?;
+        let dlq: Uuid = match topic.as_config().dead_letter {
+            Some((dlq, max_attempts)) if max_attempts <= attempts => Uuid::from(dlq),
+            _ => return Ok((attempts, false)),
+        };
+        let mut failed: Pushed = topic.read(key)?;
+        failed.attempts = attempts;
+        failed.last_error = Some(error.into());
+        Self::move_to(
+            &mut topics,
+            topic_id,
+            dlq,
+            key,
+            failed,
+            "Unable to move to a dead letter topic",
+        )?;
+        Ok((attempts, true))
+    }
+
+    // oldest dead letters first
+    #[allow(clippy::result_large_err)]
+    pub fn redrive(&self, topic_id: Uuid, limit: Option<u64>) -> Result<u64, Status> {
+        let mut topics = self.lock()?;
+        let dlq: Uuid = topics
+            .get(&topic_id.as_u128())
+            .and_then(|t| t.as_config().dead_letter)
+            .map(|(dlq, _)| Uuid::from(dlq))
+            .ok_or_else(|| {
+                Status::failed_precondition(format!("No dead letter topic configured: {topic_id}"))
+            })?;
+        let limit: usize = limit
+            .map(|u| u.try_into().unwrap_or(usize::MAX))
+            .unwrap_or(usize::MAX);
+        let keys: Vec<i64> = topics
+            .get(&dlq.as_u128())
+            .map(|d| d.as_entries().keys().take(limit).copied().collect())
+            .ok_or_else(|| missing(dlq, "Unable to redrive"))?;
+        let mut redriven: u64 = 0;
+        for key in keys {
+            let mut p: Pushed = topics
+                .get(&dlq.as_u128())
+                .ok_or_else(|| missing(dlq, "Unable to redrive"))?
+                .read(key)?;
+            p.attempts = 0;
+            p.last_error = None;
+            Self::move_to(&mut topics, dlq, topic_id, key, p, "Unable to redrive")?;
+            redriven += 1;
+        }
+        Ok(redriven)
+    }
+
+    // expired messages and messages out of the retention; then request ids out of the dedup window
+    // and segments without live messages nor those request ids
+    #[allow(clippy::result_large_err)]
+    pub fn sweep(&self) -> Result<(u64, u64), Status> {
+        let mut topics = self.lock()?;
+        let now: i64 = now_micros();
+        let mut swept: u64 = 0;
+        let mut removed: u64 = 0;
+        for t in topics.values_mut() {
+            let upper: i64 = boundary(t, now).unwrap_or(-1);
+            let keys: Vec<i64> = t
+                .as_entries()
+                .iter()
+                .filter(|(k, e)| **k <= upper || !e.alive(now))
+                .map(|(k, _)| *k)
+                .collect();
+            for key in &keys {
+                t.append(Record::Delete { key: *key })?;
+            }
+            t.commit()?;
+            swept += keys.len() as u64;
+            let oldest: i64 = now.saturating_sub(dur2micros(DEDUP_WINDOW));
+            t.sweep_requests(oldest);
+            removed += t.remove_dead_segments(oldest)?;
+        }
+        Ok((swept, removed))
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn sync(&self) -> Result<(), Status> {
+        let mut topics = self.lock()?;
+        for t in topics.values_mut() {
+            t.sync()?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn topic_ids(root: &Path) -> Result<Vec<u128>, Status> {
+    let mut ids: Vec<u128> = Vec::new();
+    for entry in fs::read_dir(root).map_err(|e| io2status(e, "Unable to list topics"))? {
+        let entry = entry.map_err(|e| io2status(e, "Unable to list topics"))?;
+        let name: String = entry.file_name().to_string_lossy().into_owned();
+        match (name.len(), u128::from_str_radix(&name, 16)) {
+            (32, Ok(id)) => ids.push(id),
+            _ => log::debug!("not a topic: {name}"),
+        }
+    }
+    Ok(ids)
+}
+
+// opens(and recovers) all topics under the root
+#[allow(clippy::result_large_err)]
+pub fn store_open<P>(root: P, segment_bytes: u64, fsync: Fsync) -> Result<Arc<Store>, Status>
+where
+    P: AsRef<Path>,
+{
+    let root: PathBuf = root.as_ref().into();
+    fs::create_dir_all(&root).map_err(|e| io2status(e, "Unable to create a root"))?;
+    let mut topics: BTreeMap<u128, TopicLog> = BTreeMap::new();
+    for id in topic_ids(&root)? {
+        let opened = TopicLog::open(topic_dir(&root, id), segment_bytes, fsync)?;
+        topics.insert(id, opened);
+    }
+    Ok(Arc::new(Store {
+        root,
+        segment_bytes,
+        fsync,
+        topics: Mutex::new(topics),
+    }))
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use db2q::uuid::Uuid;
+
+use db2q::count::cmd::exact::ExactReq;
+use db2q::count::cmd::fast::FastReq;
+use db2q::count::cmd::lag::LagReq;
+
+use db2q::db2q::proto::queue::v1::cnt_svc::{ExactRequest, ExactResponse};
+use db2q::db2q::proto::queue::v1::cnt_svc::{FastRequest, FastResponse};
+use db2q::db2q::proto::queue::v1::cnt_svc::{LagRequest, LagResponse};
+use db2q::db2q::proto::queue::v1::count_service_server::CountService;
+
+use crate::common::minimal::store::Store;
+
+pub struct Svc {
+    store: Arc<Store>,
+}
+
+#[tonic::async_trait]
+impl CountService for Svc {
+    #[allow(clippy::result_large_err)]
+    async fn exact(&self, req: Request<ExactRequest>) -> Result<Response<ExactResponse>, Status> {
+        let er: ExactRequest = req.into_inner();
+        let checked: ExactReq = (&er).try_into()?;
+        let keys = (
+            checked.as_lower().map(|u| u as i64),
+            checked.as_upper().map(|u| u as i64),
+        );
+        let pushed = (checked.as_since(), checked.as_until());
+        let topic_id: Uuid = checked.as_topic();
+        let cnt: u64 = self
+            .store
+            .run(move |s| s.count(topic_id, keys, pushed))
+            .await?;
+        let reply = ExactResponse { count: cnt };
+        Ok(Response::new(reply))
+    }
+
+    // counted exactly: the index is in memory
+    #[allow(clippy::result_large_err)]
+    async fn fast(&self, req: Request<FastRequest>) -> Result<Response<FastResponse>, Status> {
+        let fr: FastRequest = req.into_inner();
+        let checked: FastReq = (&fr).try_into()?;
+        let topic_id: Uuid = checked.as_topic();
+        let cnt: u64 = self
+            .store
+            .run(move |s| s.count(topic_id, (None, None), (None, None)))
+            .await?;
+        let reply = FastResponse {
+            count_estimate: cnt,
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn lag(&self, req: Request<LagRequest>) -> Result<Response<LagResponse>, Status> {
+        let lr: LagRequest = req.into_inner();
+        let checked: LagReq = (&lr).try_into()?;
+        if checked.as_group().is_some() {
+            return Err(Status::unimplemented("consumer groups not supported"));
+        }
+        let prev: i64 = checked.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let topic_id: Uuid = checked.as_topic();
+        let (remaining, oldest_age, newest) =
+            self.store.run(move |s| s.lag(topic_id, prev)).await?;
+        let reply = LagResponse {
+            remaining,
+            oldest_age: oldest_age.and_then(|d| d.try_into().ok()),
+            newest,
+        };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn count_svc_new(store: &Arc<Store>) -> impl CountService {
+    Svc {
+        store: store.clone(),
+    }
+}
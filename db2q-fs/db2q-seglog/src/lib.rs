@@ -0,0 +1,14 @@
+pub mod common;
+pub mod topic;
+
+pub mod count;
+pub mod queue;
+pub mod sweep;
+
+pub use tonic;
+
+pub use db2q;
+
+pub use db2q::db2q::proto::queue::v1::count_service_server;
+pub use db2q::db2q::proto::queue::v1::queue_service_server;
+pub use db2q::db2q::proto::queue::v1::topic_service_server;
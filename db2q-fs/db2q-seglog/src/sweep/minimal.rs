@@ -0,0 +1,2 @@
+pub mod fsync;
+pub mod retention;
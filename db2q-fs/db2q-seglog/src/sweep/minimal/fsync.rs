@@ -0,0 +1,22 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+
+use crate::common::minimal::store::Store;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(1);
+
+// for the deferred fsync: records written within the interval may be lost on a crash
+pub fn syncer_new(store: &Arc<Store>, interval: Duration) -> JoinHandle<()> {
+    let store: Arc<Store> = store.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match store.run(Store::sync).await {
+                Ok(_) => {}
+                Err(e) => log::warn!("Unable to sync: {e}"),
+            }
+        }
+    })
+}
@@ -0,0 +1,25 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+
+use crate::common::minimal::store::Store;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+
+// expired messages and retentions; segments are removed once all of their messages are gone
+pub fn retention_worker_new(store: &Arc<Store>, interval: Duration) -> JoinHandle<()> {
+    let store: Arc<Store> = store.clone();
+    tokio::spawn(async move {
+        loop {
+            match store.run(Store::sweep).await {
+                Ok((0, 0)) => {}
+                Ok((swept, removed)) => {
+                    log::debug!("Messages swept: {swept}, segments removed: {removed}")
+                }
+                Err(e) => log::warn!("Unable to sweep: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
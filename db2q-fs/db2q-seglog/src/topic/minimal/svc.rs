@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tonic::{Request, Response, Status};
+
+use db2q::uuid::Uuid;
+
+use db2q::topic::cmd::create::CreateReq;
+use db2q::topic::cmd::describe::DescribeReq;
+use db2q::topic::cmd::drop::DropReq;
+use db2q::topic::cmd::list::ListReq;
+use db2q::topic::cmd::update_retention::UpdateRetentionReq;
+
+use db2q::db2q::proto::queue::v1::Uuid as Guid;
+
+use db2q::db2q::proto::queue::v1::topic_service_server::TopicService;
+use db2q::db2q::proto::queue::v1::topic_svc::{CreateRequest, CreateResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{DescribeRequest, DescribeResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{DropRequest, DropResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{ListRequest, ListResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{UpdateRetentionRequest, UpdateRetentionResponse};
+
+use crate::common::minimal::store::Store;
+
+pub struct Svc {
+    store: Arc<Store>,
+}
+
+#[tonic::async_trait]
+impl TopicService for Svc {
+    #[allow(clippy::result_large_err)]
+    async fn create(
+        &self,
+        req: Request<CreateRequest>,
+    ) -> Result<Response<CreateResponse>, Status> {
+        let cr: CreateRequest = req.into_inner();
+        let checked: CreateReq = (&cr).try_into()?;
+        self.store.run(move |s| s.create(&checked)).await?;
+        let created: SystemTime = SystemTime::now();
+        let reply = CreateResponse {
+            created: Some(created.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn drop(&self, req: Request<DropRequest>) -> Result<Response<DropResponse>, Status> {
+        let cr: DropRequest = req.into_inner();
+        let checked: DropReq = (&cr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        self.store.run(move |s| s.drop_topic(topic_id)).await?;
+        let dropped: SystemTime = SystemTime::now();
+        let reply = DropResponse {
+            dropped: Some(dropped.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn list(&self, req: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        let lr: ListRequest = req.into_inner();
+        let _checked: ListReq = (&lr).try_into()?;
+        let topics: Vec<Guid> = self
+            .store
+            .run(|s| s.list())
+            .await?
+            .into_iter()
+            .map(|id: Uuid| id.into())
+            .collect();
+        let reply = ListResponse { topics };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn update_retention(
+        &self,
+        req: Request<UpdateRetentionRequest>,
+    ) -> Result<Response<UpdateRetentionResponse>, Status> {
+        let ur: UpdateRetentionRequest = req.into_inner();
+        let checked: UpdateRetentionReq = (&ur).try_into()?;
+        self.store
+            .run(move |s| s.update_retention(checked.as_topic_id(), checked.as_retention()))
+            .await?;
+        let updated: SystemTime = SystemTime::now();
+        let reply = UpdateRetentionResponse {
+            updated: Some(updated.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn describe(
+        &self,
+        req: Request<DescribeRequest>,
+    ) -> Result<Response<DescribeResponse>, Status> {
+        let dr: DescribeRequest = req.into_inner();
+        let checked: DescribeReq = (&dr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let reply: DescribeResponse = self.store.run(move |s| s.describe(topic_id)).await?;
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn topic_svc_new(store: &Arc<Store>) -> impl TopicService {
+    Svc {
+        store: store.clone(),
+    }
+}
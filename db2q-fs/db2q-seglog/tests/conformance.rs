@@ -0,0 +1,29 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use db2q_seglog::db2q::conformance::{backend_new, run_all};
+
+use db2q_seglog::common::minimal::log::Fsync;
+use db2q_seglog::common::minimal::store::{store_open, Store, SEGMENT_BYTES_DEFAULT};
+use db2q_seglog::count::minimal::svc::count_svc_new;
+use db2q_seglog::queue::minimal::svc::queue_svc_new;
+use db2q_seglog::sweep::minimal::retention::retention_worker_new;
+use db2q_seglog::topic::minimal::svc::topic_svc_new;
+
+use tempfile::TempDir;
+
+const WORKER_INTERVAL: Duration = Duration::from_millis(100);
+
+#[tokio::test(flavor = "multi_thread")]
+async fn seglog() -> Result<(), String> {
+    let dir: TempDir = tempfile::tempdir().map_err(|e| format!("Unable to create a dir: {e}"))?;
+    let store: Arc<Store> = store_open(dir.path(), SEGMENT_BYTES_DEFAULT, Fsync::Always)
+        .map_err(|e| format!("Unable to open a store: {e}"))?;
+    retention_worker_new(&store, WORKER_INTERVAL);
+    let backend = backend_new(
+        queue_svc_new(&store),
+        topic_svc_new(&store),
+        count_svc_new(&store),
+    );
+    run_all(&backend).await
+}
@@ -0,0 +1,11 @@
+#!/bin/sh
+
+listen_addr="127.0.0.1:9115"
+wait_next_min_interval_ns=$( echo 1,000 | tr -d , )
+mysql_url="mysql://root@127.0.0.1:3306/db2q"
+
+RUST_LOG=info \
+ENV_INTERVAL_NS_MINIMUM="${wait_next_min_interval_ns}" \
+ENV_MYSQL_URL="${mysql_url}" \
+ENV_LISTEN_ADDR="${listen_addr}" \
+	./simple
@@ -0,0 +1,90 @@
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use db2q_mysql::db2q::queue::st::svc::locked_q_topic_svc_new;
+
+use db2q_mysql::tonic;
+
+use db2q_mysql::db2q::queue::rw::svc::rw_q_svc_new;
+use db2q_mysql::db2q::queue::rw::svc::RwQueueSvc;
+
+use tonic::transport::{server::Router, Server};
+
+use db2q_mysql::mysql_async;
+use mysql_async::{Opts, Pool};
+
+use db2q_mysql::count_service_server::CountServiceServer;
+use db2q_mysql::queue_service_server::QueueServiceServer;
+use db2q_mysql::topic_service_server::TopicServiceServer;
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    env_logger::Builder::new()
+        .default_format()
+        .parse_default_env()
+        .format_timestamp_micros()
+        .init();
+
+    let listen_addr: String =
+        env::var("ENV_LISTEN_ADDR").unwrap_or_else(|_| "127.0.0.1:50051".into());
+    let listen: SocketAddr = str::parse(&listen_addr).map_err(|e| format!("Invalid addr: {e}"))?;
+
+    let mysql_url: String =
+        env::var("ENV_MYSQL_URL").unwrap_or_else(|_| "mysql://root@127.0.0.1:3306/db2q".into());
+    let opts: Opts = Opts::from_url(&mysql_url).map_err(|e| format!("Invalid url: {e}"))?;
+
+    let pool: Pool = Pool::new(opts);
+
+    db2q_mysql::common::minimal::catalog::init_pool(&pool)
+        .await
+        .map_err(|e| format!("Unable to create a catalog: {e}"))?;
+
+    db2q_mysql::sweep::minimal::ttl::sweeper_new(
+        &pool,
+        db2q_mysql::sweep::minimal::ttl::INTERVAL_DEFAULT,
+        db2q_mysql::sweep::minimal::ttl::BATCH_SIZE_DEFAULT,
+    );
+    db2q_mysql::sweep::minimal::retention::retention_worker_new(
+        &pool,
+        db2q_mysql::sweep::minimal::retention::INTERVAL_DEFAULT,
+        db2q_mysql::sweep::minimal::retention::BATCH_SIZE_DEFAULT,
+    );
+
+    let t2t = db2q_mysql::topic::minimal::topic2table::topic2table_prefix_default();
+    let topic_svc = db2q_mysql::topic::minimal::svc::topic_svc_new(&pool, t2t);
+    let topic_svc_shared: Arc<_> = Arc::new(topic_svc);
+
+    let t2t = db2q_mysql::topic::minimal::topic2table::topic2table_prefix_default();
+    let count_svc = db2q_mysql::count::minimal::svc::count_svc_new(&pool, t2t);
+    let count_svr: CountServiceServer<_> = CountServiceServer::new(count_svc);
+
+    let t2t = db2q_mysql::topic::minimal::topic2table::topic2table_prefix_default();
+    let queue_svc = db2q_mysql::queue::minimal::svc::queue_svc_new(&pool, t2t);
+    let queue_svc_shared: Arc<_> = Arc::new(queue_svc);
+
+    let locked_q_topic_svc = locked_q_topic_svc_new(&queue_svc_shared, &topic_svc_shared);
+    let lqts_shared: Arc<_> = Arc::new(locked_q_topic_svc);
+
+    let topic_svr: TopicServiceServer<_> = TopicServiceServer::new(lqts_shared.clone());
+
+    let rw_q_svc: RwQueueSvc<_> = rw_q_svc_new(&lqts_shared);
+    let queue_svr: QueueServiceServer<_> = QueueServiceServer::new(rw_q_svc.clone());
+
+    rw_q_svc
+        .make_writable()
+        .await
+        .map_err(|e| format!("Unable to make writable queue: {e}"))?;
+
+    let mut sv: Server = Server::builder();
+    let router: Router<_> = sv
+        .add_service(topic_svr)
+        .add_service(queue_svr)
+        .add_service(count_svr);
+
+    router
+        .serve(listen)
+        .await
+        .map_err(|e| format!("Unable to listen: {e}"))?;
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub mod catalog;
+pub mod client;
+pub mod notifier;
+pub mod time;
+pub mod topic2table;
@@ -0,0 +1,159 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{params, Conn, Pool};
+
+use super::client::{execute, get_conn, my2status};
+use super::time::{dur2micros, now_micros};
+
+pub const TOPIC_CONFIG: &str = "db2q_topic_config";
+pub const PUSH_DEDUP: &str = "db2q_push_dedup";
+
+pub async fn init<Q>(conn: &mut Q) -> Result<(), Status>
+where
+    Q: Queryable,
+{
+    // durations: microseconds, timestamps: microseconds since the unix epoch
+    let query: String = format!(
+        r#"
+            CREATE TABLE IF NOT EXISTS {TOPIC_CONFIG} (
+                name VARCHAR(64) PRIMARY KEY,
+                dead_letter VARCHAR(64),
+                max_attempts BIGINT NOT NULL DEFAULT 0,
+                safe_tail BOOLEAN NOT NULL DEFAULT FALSE,
+                default_ttl BIGINT,
+                retain_messages BIGINT,
+                retain_age BIGINT,
+                retain_bytes BIGINT,
+                created BIGINT NOT NULL,
+                FOREIGN KEY (dead_letter) REFERENCES {TOPIC_CONFIG} (name)
+            ) ENGINE = InnoDB
+        "#
+    );
+    conn.query_drop(query)
+        .await
+        .map_err(|e| my2status(e, "Unable to create a catalog"))?;
+    // the key is 0 while the push is in progress
+    let query: String = format!(
+        r#"
+            CREATE TABLE IF NOT EXISTS {PUSH_DEDUP} (
+                name VARCHAR(64) NOT NULL,
+                req_id CHAR(36) NOT NULL,
+                `key` BIGINT NOT NULL,
+                pushed BIGINT NOT NULL,
+                PRIMARY KEY (name, req_id),
+                INDEX {PUSH_DEDUP}_pushed (pushed),
+                FOREIGN KEY (name) REFERENCES {TOPIC_CONFIG} (name) ON DELETE CASCADE
+            ) ENGINE = InnoDB
+        "#
+    );
+    conn.query_drop(query)
+        .await
+        .map_err(|e| my2status(e, "Unable to create a catalog"))
+}
+
+pub async fn init_pool(pool: &Pool) -> Result<(), Status> {
+    let mut conn: Conn = get_conn(pool).await?;
+    init(&mut conn).await
+}
+
+pub async fn dead_letter<Q>(
+    checked_name: &str,
+    conn: &mut Q,
+) -> Result<Option<(String, u64)>, Status>
+where
+    Q: Queryable,
+{
+    let query: String = format!(
+        r#"
+            SELECT
+                dead_letter,
+                max_attempts
+            FROM {TOPIC_CONFIG}
+            WHERE
+                name = :name
+                AND dead_letter IS NOT NULL
+        "#
+    );
+    let orow: Option<(String, i64)> = conn
+        .exec_first(query, params! { "name" => checked_name })
+        .await
+        .map_err(|e| my2status(e, "Unable to get a topic config"))?;
+    Ok(orow.map(|(dlq, max_attempts)| (dlq, max_attempts.max(0) as u64)))
+}
+
+// false if not configured
+pub async fn safe_tail<Q>(checked_name: &str, conn: &mut Q) -> Result<bool, Status>
+where
+    Q: Queryable,
+{
+    let query: String = format!(
+        r#"
+            SELECT safe_tail
+            FROM {TOPIC_CONFIG}
+            WHERE name = :name
+        "#
+    );
+    let osafe: Option<bool> = conn
+        .exec_first(query, params! { "name" => checked_name })
+        .await
+        .map_err(|e| my2status(e, "Unable to get a topic config"))?;
+    Ok(osafe.unwrap_or(false))
+}
+
+// keys are committed in key order while the row lock is held until the end of the transaction
+pub async fn tail_lock<Q>(checked_name: &str, conn: &mut Q) -> Result<(), Status>
+where
+    Q: Queryable,
+{
+    let query: String = format!(
+        r#"
+            SELECT name
+            FROM {TOPIC_CONFIG}
+            WHERE
+                name = :name
+                AND safe_tail
+            FOR UPDATE
+        "#
+    );
+    let _locked: Option<String> = conn
+        .exec_first(query, params! { "name" => checked_name })
+        .await
+        .map_err(|e| my2status(e, "Unable to lock a topic"))?;
+    Ok(())
+}
+
+pub async fn names<Q>(conn: &mut Q) -> Result<Vec<String>, Status>
+where
+    Q: Queryable,
+{
+    let query: String = format!(
+        r#"
+            SELECT name
+            FROM {TOPIC_CONFIG}
+            ORDER BY name
+        "#
+    );
+    conn.query(query)
+        .await
+        .map_err(|e| my2status(e, "Unable to get topic names"))
+}
+
+// twice the window: a request id is never swept while a push is checking it
+pub async fn sweep_dedup<Q>(conn: &mut Q, window: Duration) -> Result<u64, Status>
+where
+    Q: Queryable,
+{
+    let query: String = format!(
+        r#"
+            DELETE FROM {PUSH_DEDUP}
+            WHERE pushed < :expired
+        "#
+    );
+    let expired: i64 = now_micros().saturating_sub(dur2micros(window).saturating_mul(2));
+    execute(conn, &query, params! { "expired" => expired })
+        .await
+        .map_err(|e| my2status(e, "Unable to delete old request ids"))
+}
@@ -0,0 +1,86 @@
+use tonic::Status;
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{Conn, DriverError, Params, Pool, Transaction, TxOpts};
+
+pub const ER_ROW_IS_REFERENCED: u16 = 1451;
+pub const ER_NO_REFERENCED_ROW: u16 = 1452;
+pub const ER_NO_SUCH_TABLE: u16 = 1146;
+
+const ER_LOCK_WAIT_TIMEOUT: u16 = 1205;
+const ER_LOCK_DEADLOCK: u16 = 1213;
+
+pub fn is_server_error(e: &mysql_async::Error, code: u16) -> bool {
+    match e {
+        mysql_async::Error::Server(s) => s.code == code,
+        _ => false,
+    }
+}
+
+pub fn my2status(e: mysql_async::Error, message: &str) -> Status {
+    match &e {
+        mysql_async::Error::Io(_) | mysql_async::Error::Driver(DriverError::ConnectionClosed) => {
+            Status::unavailable(format!("connection closed: {e}"))
+        }
+        mysql_async::Error::Driver(DriverError::PoolDisconnected) => {
+            Status::failed_precondition("All connection closed")
+        }
+        mysql_async::Error::Server(s) if s.code == ER_LOCK_WAIT_TIMEOUT => {
+            Status::unavailable(format!("{message}: {e}"))
+        }
+        mysql_async::Error::Server(s) if s.code == ER_LOCK_DEADLOCK => {
+            Status::aborted(format!("{message}: {e}"))
+        }
+        _ => Status::internal(format!("{message}: {e}")),
+    }
+}
+
+// a missing table is a dropped topic for pushes
+pub fn push2status(e: mysql_async::Error, message: &str) -> Status {
+    match is_server_error(&e, ER_NO_SUCH_TABLE) {
+        true => Status::not_found(format!("{message}: {e}")),
+        false => my2status(e, message),
+    }
+}
+
+pub async fn get_conn(pool: &Pool) -> Result<Conn, Status> {
+    pool.get_conn()
+        .await
+        .map_err(|e| my2status(e, "Unable to get a connection"))
+}
+
+pub async fn begin(pool: &Pool) -> Result<Transaction<'static>, Status> {
+    pool.start_transaction(TxOpts::default())
+        .await
+        .map_err(|e| Status::unavailable(format!("Unable to start a transaction: {e}")))
+}
+
+pub async fn commit(tx: Transaction<'_>) -> Result<(), Status> {
+    tx.commit()
+        .await
+        .map_err(|e| Status::internal(format!("Unable to commit: {e}")))
+}
+
+// the number of rows changed(a transaction does not expose the ok packet)
+pub async fn execute<Q, P>(conn: &mut Q, query: &str, params: P) -> Result<u64, mysql_async::Error>
+where
+    Q: Queryable,
+    P: Into<Params> + Send,
+{
+    let rslt = conn.exec_iter(query, params).await?;
+    let affected: u64 = rslt.affected_rows();
+    rslt.drop_result().await?;
+    Ok(affected)
+}
+
+// the key generated by AUTO_INCREMENT
+pub async fn insert<Q, P>(conn: &mut Q, query: &str, params: P) -> Result<i64, mysql_async::Error>
+where
+    Q: Queryable,
+    P: Into<Params> + Send,
+{
+    let rslt = conn.exec_iter(query, params).await?;
+    let key: Option<u64> = rslt.last_insert_id();
+    rslt.drop_result().await?;
+    Ok(key.unwrap_or_default() as i64)
+}
@@ -0,0 +1,26 @@
+use core::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// timestamps are stored as microseconds since the unix epoch
+
+pub fn time2micros(t: SystemTime) -> i64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(dur2micros)
+        .unwrap_or_default()
+}
+
+pub fn micros2time(us: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_micros(us.max(0) as u64)
+}
+
+pub fn dur2micros(d: Duration) -> i64 {
+    d.as_micros().min(i64::MAX as u128) as i64
+}
+
+pub fn micros2dur(us: i64) -> Duration {
+    Duration::from_micros(us.max(0) as u64)
+}
+
+pub fn now_micros() -> i64 {
+    time2micros(SystemTime::now())
+}
@@ -0,0 +1,3 @@
+pub use db2q::topic::topic2table::{
+    topic2table_prefix_default, Prefix, Table2Topic, Topic2Table, TopicConv,
+};
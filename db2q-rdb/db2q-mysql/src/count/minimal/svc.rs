@@ -0,0 +1,203 @@
+use std::time::SystemTime;
+
+use tonic::{Request, Response, Status};
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{params, Conn, Pool};
+
+use db2q::uuid::Uuid;
+
+use db2q::count::cmd::exact::ExactReq;
+use db2q::count::cmd::fast::FastReq;
+use db2q::count::cmd::lag::LagReq;
+
+use db2q::db2q::proto::queue::v1::cnt_svc::{ExactRequest, ExactResponse};
+use db2q::db2q::proto::queue::v1::cnt_svc::{FastRequest, FastResponse};
+use db2q::db2q::proto::queue::v1::cnt_svc::{LagRequest, LagResponse};
+use db2q::db2q::proto::queue::v1::count_service_server::CountService;
+
+use crate::common::minimal::client::{get_conn, my2status};
+use crate::common::minimal::time::{micros2dur, now_micros, time2micros};
+use crate::common::minimal::topic2table::Topic2Table;
+
+pub struct Svc<T> {
+    pool: Pool,
+    topic2table: T,
+}
+
+impl<T> Svc<T> {
+    // unset bounds are replaced with the widest ones to keep the key range scan
+    pub async fn count<Q>(
+        &self,
+        checked_name: &str,
+        keys: (Option<i64>, Option<i64>),
+        pushed: (Option<SystemTime>, Option<SystemTime>),
+        conn: &mut Q,
+    ) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    COUNT(*) AS cnt
+                FROM {checked_name}
+                WHERE
+                    `key` >= COALESCE(:lower, 0)
+                    AND `key` < COALESCE(:upper, 9223372036854775807)
+                    AND pushed >= COALESCE(:since, -9223372036854775808)
+                    AND pushed < COALESCE(:until, 9223372036854775807)
+                    AND (expires_at IS NULL OR expires_at > :now)
+            "#
+        );
+        let (lower, upper) = keys;
+        let params = params! {
+            "lower" => lower,
+            "upper" => upper,
+            "since" => pushed.0.map(time2micros),
+            "until" => pushed.1.map(time2micros),
+            "now" => now_micros(),
+        };
+        let cnt: Option<i64> = conn
+            .exec_first(query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to count"))?;
+        Ok(cnt.unwrap_or_default() as u64)
+    }
+
+    // the estimate of the storage engine
+    pub async fn fast<Q>(&self, checked_name: &str, conn: &mut Q) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query: &str = r#"
+            SELECT
+                CAST(COALESCE(table_rows, 0) AS UNSIGNED) AS cnt_estimate
+            FROM information_schema.tables
+            WHERE
+                table_schema = DATABASE()
+                AND table_name = :name
+        "#;
+        let estimate: Option<u64> = conn
+            .exec_first(query, params! { "name" => checked_name })
+            .await
+            .map_err(|e| my2status(e, "Unable to get a count estimate"))?;
+        estimate.ok_or_else(|| {
+            Status::not_found(format!(
+                "No estimate available for this table: {checked_name}"
+            ))
+        })
+    }
+
+    // (remaining, age of the oldest remaining(micros), newest key)
+    pub async fn lag<Q>(
+        &self,
+        checked_name: &str,
+        prev: i64,
+        conn: &mut Q,
+    ) -> Result<(u64, Option<i64>, i64), Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    (
+                        SELECT COUNT(*)
+                        FROM {checked_name}
+                        WHERE
+                            `key` > :prev
+                            AND (expires_at IS NULL OR expires_at > :now)
+                    ) AS remaining,
+                    (
+                        SELECT :now - pushed
+                        FROM {checked_name}
+                        WHERE
+                            `key` > :prev
+                            AND (expires_at IS NULL OR expires_at > :now)
+                        ORDER BY `key`
+                        LIMIT 1
+                    ) AS oldest_age,
+                    COALESCE(
+                        (
+                            SELECT `key`
+                            FROM {checked_name}
+                            ORDER BY `key` DESC
+                            LIMIT 1
+                        ),
+                        -1
+                    ) AS newest
+            "#
+        );
+        let row: Option<(i64, Option<i64>, i64)> = conn
+            .exec_first(query, params! { "prev" => prev, "now" => now_micros() })
+            .await
+            .map_err(|e| my2status(e, "Unable to get a lag"))?;
+        let (remaining, oldest_age, newest) = row.unwrap_or((0, None, -1));
+        Ok((remaining.max(0) as u64, oldest_age, newest))
+    }
+}
+
+#[tonic::async_trait]
+impl<T> CountService for Svc<T>
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    async fn exact(&self, req: Request<ExactRequest>) -> Result<Response<ExactResponse>, Status> {
+        let er: ExactRequest = req.into_inner();
+        let checked: ExactReq = (&er).try_into()?;
+        let topic_id: Uuid = checked.as_topic();
+        let name: String = self.topic2table.id2name(topic_id);
+        let keys = (
+            checked.as_lower().map(|u| u as i64),
+            checked.as_upper().map(|u| u as i64),
+        );
+        let pushed = (checked.as_since(), checked.as_until());
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let cnt: u64 = self.count(name.as_str(), keys, pushed, &mut conn).await?;
+        let reply = ExactResponse { count: cnt };
+        Ok(Response::new(reply))
+    }
+
+    async fn fast(&self, req: Request<FastRequest>) -> Result<Response<FastResponse>, Status> {
+        let fr: FastRequest = req.into_inner();
+        let checked: FastReq = (&fr).try_into()?;
+        let topic_id: Uuid = checked.as_topic();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let estimate: u64 = self.fast(name.as_str(), &mut conn).await?;
+        let reply = FastResponse {
+            count_estimate: estimate,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn lag(&self, req: Request<LagRequest>) -> Result<Response<LagResponse>, Status> {
+        let lr: LagRequest = req.into_inner();
+        let checked: LagReq = (&lr).try_into()?;
+        if checked.as_group().is_some() {
+            return Err(Status::unimplemented("consumer groups not supported"));
+        }
+        let topic_id: Uuid = checked.as_topic();
+        let name: String = self.topic2table.id2name(topic_id);
+        let prev: i64 = checked.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let (remaining, age_us, newest) = self.lag(name.as_str(), prev, &mut conn).await?;
+        let reply = LagResponse {
+            remaining,
+            oldest_age: age_us.and_then(|us| micros2dur(us).try_into().ok()),
+            newest,
+        };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn count_svc_new<T>(pool: &Pool, topic2table: T) -> impl CountService
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    Svc {
+        pool: pool.clone(),
+        topic2table,
+    }
+}
@@ -0,0 +1,3 @@
+pub mod filter;
+pub mod svc;
+pub use crate::common::minimal::topic2table;
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use mysql_async::{Params, Value};
+
+use db2q::queue::cmd::filter::{Condition, Filter};
+use db2q::queue::cmd::headers::{encode_name, encode_one};
+
+pub type Param = (String, Vec<u8>);
+
+// the encoded headers have the bytes(may be a part of another header)
+fn contains(n: usize) -> String {
+    format!("LOCATE(CAST(:f{n} AS BINARY), headers) > 0")
+}
+
+fn prefixed(column: &str, n: usize) -> String {
+    format!("LEFT({column}, LENGTH(CAST(:f{n} AS BINARY))) = CAST(:f{n} AS BINARY)")
+}
+
+// AND-ed predicate using the placeholders :f0, :f1, ...
+// header conditions only narrow the rows: the items must be matched again
+pub fn filter2sql(filter: Option<&Filter>) -> (String, Vec<Param>) {
+    let mut params: Vec<Param> = Vec::new();
+    let mut preds: Vec<String> = Vec::new();
+    let conditions: &[Condition] = filter.map(|f| f.as_conditions()).unwrap_or_default();
+    for c in conditions {
+        let n: usize = params.len();
+        match c {
+            Condition::HeaderEq { name, value } => {
+                preds.push(contains(n));
+                params.push((format!("f{n}"), encode_one(name, value)));
+            }
+            Condition::HeaderPrefix { name, .. } => {
+                preds.push(contains(n));
+                params.push((format!("f{n}"), encode_name(name)));
+            }
+            Condition::HeaderIn { name, values } => {
+                let any: Vec<String> = (n..n + values.len()).map(contains).collect();
+                preds.push(format!("({})", any.join(" OR ")));
+                params.extend(
+                    values
+                        .iter()
+                        .zip(n..)
+                        .map(|(v, i)| (format!("f{i}"), encode_one(name, v))),
+                );
+            }
+            Condition::ValuePrefix(prefix) => {
+                preds.push(prefixed("val", n));
+                params.push((format!("f{n}"), prefix.clone()));
+            }
+        }
+    }
+    match preds.is_empty() {
+        true => ("TRUE".into(), params),
+        false => (preds.join(" AND "), params),
+    }
+}
+
+// the named params(params!) with the filter params
+pub fn with_filter(params: Params, fparams: &[Param]) -> Params {
+    let mut named: HashMap<Vec<u8>, Value> = match params {
+        Params::Named(named) => named,
+        _ => HashMap::new(),
+    };
+    named.extend(
+        fparams
+            .iter()
+            .map(|(name, v)| (name.as_bytes().to_vec(), Value::from(v.as_slice()))),
+    );
+    Params::Named(named)
+}
@@ -0,0 +1,1374 @@
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use tokio::sync::futures::Notified;
+use tokio::sync::{mpsc, Notify};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{params, Conn, Params, Pool, Transaction};
+
+use db2q::queue::cmd::ack::AckReq;
+use db2q::queue::cmd::count::CountReq;
+use db2q::queue::cmd::extend_lease::ExtendLeaseReq;
+use db2q::queue::cmd::filter::{matches, Filter};
+use db2q::queue::cmd::headers;
+use db2q::queue::cmd::keys::KeysReq;
+use db2q::queue::cmd::lease::LeaseReq;
+use db2q::queue::cmd::nack::NackReq;
+use db2q::queue::cmd::next::NextReq;
+use db2q::queue::cmd::pop::PopFrontReq;
+use db2q::queue::cmd::push::{PushBackReq, DEDUP_WINDOW};
+use db2q::queue::cmd::push_batch::PushBatchReq;
+use db2q::queue::cmd::redrive::RedriveReq;
+use db2q::queue::cmd::report_failure::ReportFailureReq;
+use db2q::queue::cmd::subscribe::SubscribeReq;
+use db2q::queue::cmd::wait_next::WaitNextReq;
+use db2q::uuid::Uuid;
+
+use db2q::db2q::proto::queue::v1::q_svc::{AckRequest, AckResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{CountRequest, CountResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{ExtendLeaseRequest, ExtendLeaseResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{KeysRequest, KeysResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{LeaseRequest, LeaseResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{NackRequest, NackResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{NextRequest, NextResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{PopFrontRequest, PopFrontResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{PushBackRequest, PushBackResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{PushBatchRequest, PushBatchResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{RedriveRequest, RedriveResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{ReportFailureRequest, ReportFailureResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{SubscribeRequest, SubscribeResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{WaitNextRequest, WaitNextResponse};
+use db2q::db2q::proto::queue::v1::queue_service_server::QueueService;
+
+use super::filter::{filter2sql, with_filter};
+use super::topic2table::Topic2Table;
+
+use crate::common::minimal::catalog::{self, PUSH_DEDUP, TOPIC_CONFIG};
+use crate::common::minimal::client::{begin, commit, execute, get_conn, insert, my2status};
+use crate::common::minimal::client::{is_server_error, push2status, ER_NO_REFERENCED_ROW};
+use crate::common::minimal::notifier::Notifier;
+use crate::common::minimal::time::{dur2micros, micros2time, now_micros, time2micros};
+
+const ITEM_COLUMNS: &str = "`key`, priority, val, headers, attempts, last_error";
+
+// rows narrowed by the filters are scanned page by page(the filters are evaluated here)
+const SCAN_PAGE: i64 = 256;
+
+// key, priority, val, headers, attempts, last_error
+type ItemRow = (i64, i32, Vec<u8>, Vec<u8>, i64, Option<String>);
+
+// a visible message(ITEM_COLUMNS)
+struct Item {
+    key: i64,
+    priority: i32,
+    val: Vec<u8>,
+    headers: HashMap<String, Vec<u8>>,
+    attempts: i64,
+    last_error: Option<String>,
+}
+
+impl Item {
+    #[allow(clippy::result_large_err)]
+    async fn page<Q>(conn: &mut Q, query: &str, params: Params) -> Result<Vec<Self>, Status>
+    where
+        Q: Queryable,
+    {
+        let rows: Vec<ItemRow> = conn
+            .exec(query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to select"))?;
+        rows.into_iter()
+            .map(|(key, priority, val, encoded, attempts, last_error)| {
+                Ok(Self {
+                    key,
+                    priority,
+                    val,
+                    headers: headers::decode(&encoded)?,
+                    attempts,
+                    last_error,
+                })
+            })
+            .collect()
+    }
+
+    // the first item which matches the filter(the pushed down one narrows the rows only)
+    fn first_match(items: Vec<Self>, filter: Option<&Filter>) -> Option<Self> {
+        items
+            .into_iter()
+            .find(|i: &Self| matches(filter, &i.val, &i.headers))
+    }
+}
+
+impl From<Item> for NextResponse {
+    fn from(i: Item) -> Self {
+        Self {
+            next: i.key,
+            value: i.val,
+            priority: i.priority,
+            headers: i.headers,
+            attempts: i.attempts as u64,
+            last_error: i.last_error.unwrap_or_default(),
+        }
+    }
+}
+
+// cleared when full
+pub const SAFE_TAIL_CACHE_MAX: usize = 1024;
+
+fn groups_unsupported() -> Status {
+    Status::unimplemented("consumer groups not supported")
+}
+
+pub struct Svc<T> {
+    pool: Pool,
+    topic2table: T,
+    notifier: Arc<Notifier>,
+    safe_tail: Mutex<HashMap<String, bool>>,
+}
+
+impl<T> Svc<T>
+where
+    T: Send + Sync + 'static,
+{
+    // fixed on create; cached per topic
+    async fn is_safe_tail<Q>(&self, checked_name: &str, conn: &mut Q) -> Result<bool, Status>
+    where
+        Q: Queryable,
+    {
+        let cached: Option<bool> = match self.safe_tail.lock() {
+            Ok(guard) => guard.get(checked_name).copied(),
+            Err(e) => {
+                log::warn!("Unable to lock: {e}");
+                None
+            }
+        };
+        if let Some(safe_tail) = cached {
+            return Ok(safe_tail);
+        }
+        let safe_tail: bool = catalog::safe_tail(checked_name, conn).await?;
+        match self.safe_tail.lock() {
+            Ok(mut guard) => {
+                if SAFE_TAIL_CACHE_MAX <= guard.len() {
+                    guard.clear();
+                }
+                guard.insert(checked_name.into(), safe_tail);
+            }
+            Err(e) => log::warn!("Unable to lock: {e}"),
+        }
+        Ok(safe_tail)
+    }
+
+    // no lock unless the topic is safe tail
+    async fn tail_lock<Q>(&self, checked_name: &str, conn: &mut Q) -> Result<(), Status>
+    where
+        Q: Queryable,
+    {
+        match self.is_safe_tail(checked_name, conn).await? {
+            false => Ok(()),
+            true => catalog::tail_lock(checked_name, conn).await,
+        }
+    }
+
+    async fn pushed_or_elapsed(notified: Pin<&mut Notified<'_>>, interval: Duration) {
+        match tokio::time::timeout(interval, notified).await {
+            Ok(_) => {}
+            Err(_) => log::debug!("no notification. polling..."),
+        }
+    }
+
+    async fn push<Q>(
+        checked_name: &str,
+        conn: &mut Q,
+        checked: &PushBackReq,
+    ) -> Result<(i64, i64), Status>
+    where
+        Q: Queryable,
+    {
+        let request_id: String = checked.as_request_id().to_string();
+        let now: i64 = now_micros();
+        // the request id is claimed first(locked until commit); reused if older than the window
+        let query = format!(
+            r#"
+                INSERT INTO {PUSH_DEDUP} (
+                    name,
+                    req_id,
+                    `key`,
+                    pushed
+                )
+                VALUES (
+                    :name,
+                    :req_id,
+                    0,
+                    :now
+                )
+                ON DUPLICATE KEY UPDATE
+                    `key` = IF(pushed < :expired, 0, `key`),
+                    pushed = IF(pushed < :expired, :now, pushed)
+            "#
+        );
+        let params = params! {
+            "name" => checked_name,
+            "req_id" => request_id.as_str(),
+            "now" => now,
+            "expired" => now.saturating_sub(dur2micros(DEDUP_WINDOW)),
+        };
+        execute(conn, &query, params).await.map_err(|e| {
+            match is_server_error(&e, ER_NO_REFERENCED_ROW) {
+                true => Status::not_found(format!("No such topic: {checked_name}")),
+                false => my2status(e, "Unable to claim a request id"),
+            }
+        })?;
+        let query = format!(
+            r#"
+                SELECT
+                    `key`,
+                    pushed
+                FROM {PUSH_DEDUP}
+                WHERE
+                    name = :name
+                    AND req_id = :req_id
+                FOR UPDATE
+            "#
+        );
+        let params = params! { "name" => checked_name, "req_id" => request_id.as_str() };
+        let (claimed, pushed): (i64, i64) = conn
+            .exec_first(query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to select"))?
+            .ok_or_else(|| Status::not_found(format!("No such topic: {checked_name}")))?;
+        // the original item if duplicated
+        if 0 < claimed {
+            return Ok((claimed, pushed));
+        }
+        let query = format!(
+            r#"
+                INSERT INTO {checked_name} (
+                    val,
+                    pushed,
+                    visible_at,
+                    expires_at,
+                    priority,
+                    headers
+                )
+                VALUES (
+                    :val,
+                    :now,
+                    COALESCE(:not_before, :now + COALESCE(:delay, 0)),
+                    :now + COALESCE(
+                        :ttl,
+                        (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = :name)
+                    ),
+                    :priority,
+                    :headers
+                )
+            "#
+        );
+        let params = params! {
+            "val" => checked.as_value(),
+            "now" => now,
+            "not_before" => checked.as_not_before().map(time2micros),
+            "delay" => checked.as_delay().map(dur2micros),
+            "ttl" => checked.as_ttl().map(dur2micros),
+            "name" => checked_name,
+            "priority" => checked.as_priority(),
+            "headers" => headers::encode(checked.as_headers()),
+        };
+        let key: i64 = insert(conn, &query, params)
+            .await
+            .map_err(|e| push2status(e, "Unable to insert"))?;
+        let query = format!(
+            r#"
+                UPDATE {PUSH_DEDUP}
+                SET `key` = :key
+                WHERE
+                    name = :name
+                    AND req_id = :req_id
+            "#
+        );
+        let params = params! {
+            "key" => key,
+            "name" => checked_name,
+            "req_id" => request_id.as_str(),
+        };
+        execute(conn, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to record a request id"))?;
+        Ok((key, now))
+    }
+
+    async fn push_batch<Q>(
+        checked_name: &str,
+        conn: &mut Q,
+        vals: &[Vec<u8>],
+    ) -> Result<(Vec<i64>, Option<i64>), Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                INSERT INTO {checked_name} (
+                    val,
+                    pushed,
+                    visible_at,
+                    expires_at,
+                    headers
+                )
+                VALUES (
+                    :val,
+                    :now,
+                    :now,
+                    :now + (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = :name),
+                    ''
+                )
+            "#
+        );
+        let now: i64 = now_micros();
+        // one row per statement: the generated keys of a multi row insert may interleave
+        let mut keys: Vec<i64> = Vec::with_capacity(vals.len());
+        for v in vals {
+            let params = params! { "val" => v.as_slice(), "now" => now, "name" => checked_name };
+            let key: i64 = insert(conn, &query, params)
+                .await
+                .map_err(|e| push2status(e, "Unable to insert"))?;
+            keys.push(key);
+        }
+        let pushed: Option<i64> = (!keys.is_empty()).then_some(now);
+        Ok((keys, pushed))
+    }
+
+    async fn count<Q>(checked_name: &str, conn: &mut Q) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    COUNT(*) AS cnt
+                FROM {checked_name}
+                WHERE expires_at IS NULL OR expires_at > :now
+            "#
+        );
+        let cnt: Option<i64> = conn
+            .exec_first(query, params! { "now" => now_micros() })
+            .await
+            .map_err(|e| my2status(e, "Unable to count"))?;
+        Ok(cnt.unwrap_or_default() as u64)
+    }
+
+    async fn next<Q>(
+        checked_name: &str,
+        prev: Option<i64>,
+        filter: Option<&Filter>,
+        conn: &mut Q,
+    ) -> Result<Item, Status>
+    where
+        Q: Queryable,
+    {
+        let (pred, fparams) = filter2sql(filter);
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    `key` > :after
+                    AND visible_at <= :now
+                    AND (expires_at IS NULL OR expires_at > :now)
+                    AND {pred}
+                ORDER BY `key`
+                LIMIT :page
+            "#
+        );
+        let now: i64 = now_micros();
+        let mut after: i64 = prev.unwrap_or(-1);
+        loop {
+            let params = params! { "after" => after, "now" => now, "page" => SCAN_PAGE };
+            let params: Params = with_filter(params, &fparams);
+            let items: Vec<Item> = Item::page(conn, &query, params).await?;
+            let full: bool = items.len() as i64 == SCAN_PAGE;
+            if let Some(last) = items.last() {
+                after = last.key;
+            }
+            if let Some(found) = Item::first_match(items, filter) {
+                return Ok(found);
+            }
+            if !full {
+                return Err(match prev {
+                    None => Status::not_found("Empty queue"),
+                    Some(prev) => {
+                        Status::not_found(format!("No more queue items. previous key: {prev}"))
+                    }
+                });
+            }
+        }
+    }
+
+    // (priority desc, key) order
+    async fn next_prioritized<Q>(
+        checked_name: &str,
+        prev: Option<(i32, i64)>,
+        filter: Option<&Filter>,
+        conn: &mut Q,
+    ) -> Result<Item, Status>
+    where
+        Q: Queryable,
+    {
+        let (pred, fparams) = filter2sql(filter);
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    (
+                        :after_key IS NULL
+                        OR priority < :after_priority
+                        OR (priority = :after_priority AND `key` > :after_key)
+                    )
+                    AND visible_at <= :now
+                    AND (expires_at IS NULL OR expires_at > :now)
+                    AND {pred}
+                ORDER BY priority DESC, `key`
+                LIMIT :page
+            "#
+        );
+        let now: i64 = now_micros();
+        let mut after: Option<(i32, i64)> = prev;
+        loop {
+            let params = params! {
+                "after_priority" => after.map(|a| a.0),
+                "after_key" => after.map(|a| a.1),
+                "now" => now,
+                "page" => SCAN_PAGE,
+            };
+            let params: Params = with_filter(params, &fparams);
+            let items: Vec<Item> = Item::page(conn, &query, params).await?;
+            let full: bool = items.len() as i64 == SCAN_PAGE;
+            if let Some(last) = items.last() {
+                after = Some((last.priority, last.key));
+            }
+            if let Some(found) = Item::first_match(items, filter) {
+                return Ok(found);
+            }
+            if !full {
+                return Err(Status::not_found(format!(
+                    "No more queue items. previous: {prev:?}"
+                )));
+            }
+        }
+    }
+
+    pub async fn wait_next(
+        &self,
+        checked_name: &str,
+        req: WaitNextReq,
+    ) -> Result<ReceiverStream<Result<WaitNextResponse, Status>>, Status> {
+        if req.as_group().is_some() {
+            return Err(groups_unsupported());
+        }
+        let prev: Option<i64> = req.as_previous_key().map(|u| u as i64);
+        let start: Instant = Instant::now();
+        let notify: Arc<Notify> = self.notifier.watch(checked_name);
+        let interval: Duration = req.as_interval();
+        let (tx, rx) = mpsc::channel(1);
+        let name: String = checked_name.into();
+        let pool: Pool = self.pool.clone();
+        let timeout: Duration = req.as_timeout();
+        tokio::spawn(async move {
+            let mut retry_cnt: u64 = 0;
+            loop {
+                // registered before checking to avoid missing a notification
+                let notified = notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let rslt: Result<Item, Status> = match get_conn(&pool).await {
+                    Ok(mut conn) => {
+                        Self::next(name.as_str(), prev, req.as_filter(), &mut conn).await
+                    }
+                    Err(e) => Err(e),
+                };
+                match rslt {
+                    Ok(item) => {
+                        let elapsed: Duration = start.elapsed();
+                        let reply = WaitNextResponse {
+                            next: Some(item.into()),
+                            elapsed: elapsed.try_into().ok(),
+                            retried: retry_cnt,
+                        };
+                        match tx.send(Ok(reply)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        };
+                        return;
+                    }
+                    Err(e) => match e.code() {
+                        Code::NotFound => {}
+                        _ => {
+                            match tx.send(Err(e)).await {
+                                Ok(_) => {}
+                                Err(e) => log::warn!("Unable to send: {e}"),
+                            }
+                            return;
+                        }
+                    },
+                }
+                let remaining: Duration = timeout.saturating_sub(start.elapsed());
+                if remaining.is_zero() {
+                    let e = Status::deadline_exceeded(format!(
+                        "timeout. table={name}, retried={retry_cnt}"
+                    ));
+                    match tx.send(Err(e)).await {
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Unable to send: {e}"),
+                    }
+                    return;
+                }
+                Self::pushed_or_elapsed(notified.as_mut(), interval.min(remaining)).await;
+                retry_cnt += 1;
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+
+    // locked rows are skipped: concurrent consumers get different items
+    async fn pop(checked_name: &str, pool: &Pool, priority_order: bool) -> Result<Item, Status> {
+        let order: &str = match priority_order {
+            true => "priority DESC, `key`",
+            false => "`key`",
+        };
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    visible_at <= :now
+                    AND (expires_at IS NULL OR expires_at > :now)
+                    AND (leased_until IS NULL OR leased_until <= :now)
+                ORDER BY {order}
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            "#
+        );
+        let mut tx: Transaction = begin(pool).await?;
+        let item: Item = Item::page(&mut tx, &query, params! { "now" => now_micros() })
+            .await?
+            .pop()
+            .ok_or_else(|| Status::not_found("Empty queue"))?;
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE `key` = :key
+            "#
+        );
+        execute(&mut tx, &query, params! { "key" => item.key })
+            .await
+            .map_err(|e| my2status(e, "Unable to delete"))?;
+        commit(tx).await?;
+        Ok(item)
+    }
+
+    async fn lease(
+        checked_name: &str,
+        pool: &Pool,
+        lease_id: Uuid,
+        timeout: Duration,
+    ) -> Result<(Item, SystemTime), Status> {
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    visible_at <= :now
+                    AND (expires_at IS NULL OR expires_at > :now)
+                    AND (leased_until IS NULL OR leased_until <= :now)
+                ORDER BY `key`
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            "#
+        );
+        let micros: i64 = timeout
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("visibility timeout too large: {e}")))?;
+        let now: i64 = now_micros();
+        let deadline: i64 = now.saturating_add(micros);
+        let mut tx: Transaction = begin(pool).await?;
+        let leased: Item = Item::page(&mut tx, &query, params! { "now" => now })
+            .await?
+            .pop()
+            .ok_or_else(|| Status::not_found("No visible queue items"))?;
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = :deadline,
+                    lease_id = :lease_id
+                WHERE `key` = :key
+            "#
+        );
+        let params = params! {
+            "deadline" => deadline,
+            "lease_id" => lease_id.to_string(),
+            "key" => leased.key,
+        };
+        execute(&mut tx, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to lease"))?;
+        commit(tx).await?;
+        Ok((leased, micros2time(deadline)))
+    }
+
+    async fn ack<Q>(
+        checked_name: &str,
+        conn: &mut Q,
+        key: i64,
+        lease_id: Uuid,
+    ) -> Result<(), Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE
+                    `key` = :key
+                    AND lease_id = :lease_id
+                    AND leased_until > :now
+            "#
+        );
+        let params = params! {
+            "key" => key,
+            "lease_id" => lease_id.to_string(),
+            "now" => now_micros(),
+        };
+        let cnt: u64 = execute(conn, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to ack"))?;
+        match cnt {
+            0 => Err(Status::not_found(format!(
+                "No such lease(expired?). key: {key}, lease id: {lease_id}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn nack<Q>(
+        checked_name: &str,
+        conn: &mut Q,
+        key: i64,
+        lease_id: Uuid,
+        delay: Option<Duration>,
+    ) -> Result<(), Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = :now + :delay,
+                    lease_id = NULL
+                WHERE
+                    `key` = :key
+                    AND lease_id = :lease_id
+                    AND leased_until > :now
+            "#
+        );
+        let micros: i64 = delay
+            .unwrap_or_default()
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("delay too large: {e}")))?;
+        let params = params! {
+            "key" => key,
+            "lease_id" => lease_id.to_string(),
+            "now" => now_micros(),
+            "delay" => micros,
+        };
+        let cnt: u64 = execute(conn, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to nack"))?;
+        match cnt {
+            0 => Err(Status::not_found(format!(
+                "No such lease(expired?). key: {key}, lease id: {lease_id}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn extend_lease<Q>(
+        checked_name: &str,
+        conn: &mut Q,
+        key: i64,
+        lease_id: Uuid,
+        extension: Duration,
+    ) -> Result<SystemTime, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = :deadline
+                WHERE
+                    `key` = :key
+                    AND lease_id = :lease_id
+                    AND leased_until > :now
+            "#
+        );
+        let micros: i64 = extension
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("extension too large: {e}")))?;
+        let now: i64 = now_micros();
+        let deadline: i64 = now.saturating_add(micros);
+        let params = params! {
+            "key" => key,
+            "lease_id" => lease_id.to_string(),
+            "now" => now,
+            "deadline" => deadline,
+        };
+        let cnt: u64 = execute(conn, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to extend a lease"))?;
+        match cnt {
+            0 => Err(Status::not_found(format!(
+                "No such lease(expired?). key: {key}, lease id: {lease_id}"
+            ))),
+            _ => Ok(micros2time(deadline)),
+        }
+    }
+
+    async fn fail<Q>(checked_name: &str, conn: &mut Q, key: i64, error: &str) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    attempts = attempts + 1,
+                    last_error = :error
+                WHERE `key` = :key
+            "#
+        );
+        execute(conn, &query, params! { "key" => key, "error" => error })
+            .await
+            .map_err(|e| my2status(e, "Unable to record a failure"))?;
+        let query = format!(
+            r#"
+                SELECT attempts
+                FROM {checked_name}
+                WHERE `key` = :key
+            "#
+        );
+        let attempts: i64 = conn
+            .exec_first(query, params! { "key" => key })
+            .await
+            .map_err(|e| my2status(e, "Unable to record a failure"))?
+            .ok_or_else(|| Status::not_found(format!("No such queue item. key: {key}")))?;
+        Ok(attempts as u64)
+    }
+
+    async fn dead_letter<Q>(
+        checked_name: &str,
+        dlq_name: &str,
+        conn: &mut Q,
+        key: i64,
+    ) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                INSERT INTO {dlq_name} (
+                    val,
+                    attempts,
+                    last_error,
+                    priority,
+                    headers,
+                    pushed,
+                    visible_at,
+                    expires_at
+                )
+                SELECT
+                    val,
+                    attempts,
+                    last_error,
+                    priority,
+                    headers,
+                    :now,
+                    :now,
+                    :now + (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = :dlq)
+                FROM {checked_name}
+                WHERE `key` = :key
+            "#
+        );
+        let params = params! { "key" => key, "now" => now_micros(), "dlq" => dlq_name };
+        execute(conn, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to move to a dead letter topic"))?;
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE `key` = :key
+            "#
+        );
+        execute(conn, &query, params! { "key" => key })
+            .await
+            .map_err(|e| my2status(e, "Unable to move to a dead letter topic"))
+    }
+
+    async fn redrive<Q>(
+        checked_name: &str,
+        dlq_name: &str,
+        conn: &mut Q,
+        limit: i64,
+    ) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        // the oldest dead letters are locked first
+        let query = format!(
+            r#"
+                SELECT `key`
+                FROM {dlq_name}
+                ORDER BY `key`
+                LIMIT :limit
+                FOR UPDATE
+            "#
+        );
+        let keys: Vec<i64> = conn
+            .exec(query, params! { "limit" => limit })
+            .await
+            .map_err(|e| my2status(e, "Unable to redrive"))?;
+        let upper: i64 = match keys.last() {
+            None => return Ok(0),
+            Some(upper) => *upper,
+        };
+        let query = format!(
+            r#"
+                INSERT INTO {checked_name} (
+                    val,
+                    priority,
+                    headers,
+                    pushed,
+                    visible_at,
+                    expires_at
+                )
+                SELECT
+                    val,
+                    priority,
+                    headers,
+                    :now,
+                    :now,
+                    :now + (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = :name)
+                FROM {dlq_name}
+                WHERE `key` <= :upper
+                ORDER BY `key`
+            "#
+        );
+        let params = params! { "upper" => upper, "now" => now_micros(), "name" => checked_name };
+        let redriven: u64 = execute(conn, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to redrive"))?;
+        let query = format!(
+            r#"
+                DELETE FROM {dlq_name}
+                WHERE `key` <= :upper
+            "#
+        );
+        execute(conn, &query, params! { "upper" => upper })
+            .await
+            .map_err(|e| my2status(e, "Unable to redrive"))?;
+        Ok(redriven)
+    }
+
+    async fn next_batch<Q>(
+        checked_name: &str,
+        prev: i64,
+        limit: i64,
+        conn: &mut Q,
+    ) -> Result<Vec<Item>, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    `key` > :prev
+                    AND visible_at <= :now
+                    AND (expires_at IS NULL OR expires_at > :now)
+                ORDER BY `key`
+                LIMIT :limit
+            "#
+        );
+        let params = params! { "prev" => prev, "limit" => limit, "now" => now_micros() };
+        Item::page(conn, &query, params).await
+    }
+
+    pub async fn subscribe(
+        &self,
+        checked_name: &str,
+        req: SubscribeReq,
+    ) -> Result<ReceiverStream<Result<SubscribeResponse, Status>>, Status> {
+        let mut prev: i64 = req.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let batch_size: u64 = req.as_batch_size();
+        let limit: i64 = batch_size as i64;
+        let notify: Arc<Notify> = self.notifier.watch(checked_name);
+        let interval: Duration = req.as_interval();
+        // bounded: a slow client stops the polling instead of buffering
+        let (tx, rx) = mpsc::channel(batch_size as usize);
+        let name: String = checked_name.into();
+        let pool: Pool = self.pool.clone();
+        tokio::spawn(async move {
+            loop {
+                let notified = notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let rslt: Result<Vec<Item>, Status> = match get_conn(&pool).await {
+                    Ok(mut conn) => Self::next_batch(name.as_str(), prev, limit, &mut conn).await,
+                    Err(e) => Err(e),
+                };
+                let items: Vec<Item> = match rslt {
+                    Ok(items) => items,
+                    Err(e) => {
+                        match tx.send(Err(e)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        }
+                        return;
+                    }
+                };
+                if items.is_empty() {
+                    tokio::select! {
+                        _ = Self::pushed_or_elapsed(notified.as_mut(), interval) => {},
+                        _ = tx.closed() => { return },
+                    };
+                    continue;
+                }
+                for item in items {
+                    prev = item.key;
+                    let reply = SubscribeResponse {
+                        next: Some(item.into()),
+                    };
+                    match tx.send(Ok(reply)).await {
+                        Ok(_) => {}
+                        Err(_) => return, // cancelled
+                    }
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+
+    // one more key is found to know whether the page is full
+    async fn keys<Q>(
+        checked_name: &str,
+        conn: &mut Q,
+        checked: &KeysReq,
+    ) -> Result<Vec<KeysResponse>, Status>
+    where
+        Q: Queryable,
+    {
+        let (cursor, order) = match checked.as_descending() {
+            false => ("`key` > :after", "ASC"),
+            true => ("`key` < :after", "DESC"),
+        };
+        let (pred, fparams) = filter2sql(checked.as_filter());
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    {cursor}
+                    AND `key` > COALESCE(:lower, 0)
+                    AND `key` < COALESCE(:upper, 9223372036854775807)
+                    AND visible_at <= :now
+                    AND (expires_at IS NULL OR expires_at > :now)
+                    AND {pred}
+                ORDER BY `key` {order}
+                LIMIT :page
+            "#
+        );
+        let limit: u64 = checked.as_max_keys().min(i64::MAX as u64 - 1);
+        let start_after: Option<i64> = checked.as_start_after().map(|u| u as i64);
+        let end_before: Option<i64> = checked.as_end_before().map(|u| u as i64);
+        let (lower, upper) = match checked.as_descending() {
+            false => (start_after, end_before),
+            true => (end_before, start_after),
+        };
+        let now: i64 = now_micros();
+        let mut after: i64 = match checked.as_descending() {
+            false => -1,
+            true => i64::MAX,
+        };
+        let mut keys: Vec<KeysResponse> = Vec::new();
+        loop {
+            let params = params! {
+                "after" => after,
+                "lower" => lower,
+                "upper" => upper,
+                "now" => now,
+                "page" => SCAN_PAGE,
+            };
+            let params: Params = with_filter(params, &fparams);
+            let items: Vec<Item> = Item::page(conn, &query, params).await?;
+            let full: bool = items.len() as i64 == SCAN_PAGE;
+            if let Some(last) = items.last() {
+                after = last.key;
+            }
+            let found = items
+                .into_iter()
+                .filter(|i: &Item| matches(checked.as_filter(), &i.val, &i.headers));
+            for item in found {
+                if limit <= keys.len() as u64 {
+                    if let Some(last) = keys.last_mut() {
+                        last.continuation = last.key as i64;
+                    }
+                    return Ok(keys);
+                }
+                keys.push(KeysResponse {
+                    key: item.key as u64,
+                    continuation: -1,
+                });
+            }
+            if !full {
+                return Ok(keys);
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl<T> QueueService for Svc<T>
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    async fn push_back(
+        &self,
+        req: Request<PushBackRequest>,
+    ) -> Result<Response<PushBackResponse>, Status> {
+        let pbr: PushBackRequest = req.into_inner();
+        let checked: PushBackReq = pbr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut tx: Transaction = begin(&self.pool).await?;
+        self.tail_lock(&name, &mut tx).await?;
+        let (key, pushed) = Self::push(&name, &mut tx, &checked).await?;
+        commit(tx).await?;
+        self.notifier.wake(&name);
+        let reply = PushBackResponse {
+            pushed: Some(micros2time(pushed).into()),
+            key,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn push_batch(
+        &self,
+        req: Request<PushBatchRequest>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let pbr: PushBatchRequest = req.into_inner();
+        let checked: PushBatchReq = pbr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut tx: Transaction = begin(&self.pool).await?;
+        self.tail_lock(&name, &mut tx).await?;
+        let (keys, pushed) = Self::push_batch(&name, &mut tx, checked.as_values()).await?;
+        commit(tx).await?;
+        self.notifier.wake(&name);
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: i64| micros2time(t).into()),
+            keys,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn push_batch_stream(
+        &self,
+        req: Request<Streaming<PushBatchRequest>>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let mut chunks: Streaming<PushBatchRequest> = req.into_inner();
+        let first: PushBatchRequest = chunks
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("empty batch stream"))?;
+        let mut checked: PushBatchReq = first.try_into()?;
+        while let Some(chunk) = chunks.message().await? {
+            let next: PushBatchReq = chunk.try_into()?;
+            checked.append(next)?;
+        }
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut tx: Transaction = begin(&self.pool).await?;
+        self.tail_lock(&name, &mut tx).await?;
+        let (keys, pushed) = Self::push_batch(&name, &mut tx, checked.as_values()).await?;
+        commit(tx).await?;
+        self.notifier.wake(&name);
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: i64| micros2time(t).into()),
+            keys,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn pop_front(
+        &self,
+        req: Request<PopFrontRequest>,
+    ) -> Result<Response<PopFrontResponse>, Status> {
+        let pfr: PopFrontRequest = req.into_inner();
+        let checked: PopFrontReq = pfr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let item: Item = Self::pop(&name, &self.pool, checked.as_priority_order()).await?;
+        let popped: SystemTime = SystemTime::now();
+        let reply = PopFrontResponse {
+            popped: Some(popped.into()),
+            value: item.val,
+            headers: item.headers,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn count(&self, req: Request<CountRequest>) -> Result<Response<CountResponse>, Status> {
+        let cr: CountRequest = req.into_inner();
+        let checked: CountReq = cr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let cnt: u64 = Self::count(&name, &mut conn).await?;
+        let reply = CountResponse { count: cnt };
+        Ok(Response::new(reply))
+    }
+
+    async fn next(&self, req: Request<NextRequest>) -> Result<Response<NextResponse>, Status> {
+        let nr: NextRequest = req.into_inner();
+        let checked: NextReq = (&nr).try_into()?;
+        if checked.as_group().is_some() {
+            return Err(groups_unsupported());
+        }
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let prev_key: Option<i64> = checked.as_previous_key().map(|u| u as i64);
+        let filter: Option<&Filter> = checked.as_filter();
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let item: Item = match checked.as_priority_order() {
+            false => Self::next(&name, prev_key, filter, &mut conn).await?,
+            true => {
+                let prev: Option<(i32, i64)> =
+                    prev_key.map(|k| (checked.as_previous_priority(), k));
+                Self::next_prioritized(&name, prev, filter, &mut conn).await?
+            }
+        };
+        let reply: NextResponse = item.into();
+        Ok(Response::new(reply))
+    }
+
+    type WaitNextStream = ReceiverStream<Result<WaitNextResponse, Status>>;
+
+    async fn wait_next(
+        &self,
+        req: Request<WaitNextRequest>,
+    ) -> Result<Response<Self::WaitNextStream>, Status> {
+        let wnr: WaitNextRequest = req.into_inner();
+        let checked: WaitNextReq = (&wnr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let reply: Self::WaitNextStream = self.wait_next(name.as_str(), checked).await?;
+        Ok(Response::new(reply))
+    }
+
+    type SubscribeStream = ReceiverStream<Result<SubscribeResponse, Status>>;
+
+    async fn subscribe(
+        &self,
+        req: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let sr: SubscribeRequest = req.into_inner();
+        let checked: SubscribeReq = (&sr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let reply: Self::SubscribeStream = self.subscribe(name.as_str(), checked).await?;
+        Ok(Response::new(reply))
+    }
+
+    type KeysStream = ReceiverStream<Result<KeysResponse, Status>>;
+
+    async fn keys(&self, req: Request<KeysRequest>) -> Result<Response<Self::KeysStream>, Status> {
+        let kr: KeysRequest = req.into_inner();
+        let checked: KeysReq = (&kr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let keys: Vec<KeysResponse> = Self::keys(&name, &mut conn, &checked).await?;
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            for key in keys {
+                match tx.send(Ok(key)).await {
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("Error while sending keys: {e}");
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn lease(&self, req: Request<LeaseRequest>) -> Result<Response<LeaseResponse>, Status> {
+        let lr: LeaseRequest = req.into_inner();
+        let checked: LeaseReq = (&lr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let lease_id: Uuid = Uuid::new_v4();
+        let timeout: Duration = checked.as_visibility_timeout();
+        let (item, deadline) = Self::lease(&name, &self.pool, lease_id, timeout).await?;
+        let reply = LeaseResponse {
+            key: item.key,
+            value: item.val,
+            lease_id: Some(lease_id.into()),
+            deadline: Some(deadline.into()),
+            headers: item.headers,
+            attempts: item.attempts as u64,
+            last_error: item.last_error.unwrap_or_default(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn ack(&self, req: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        let ar: AckRequest = req.into_inner();
+        let checked: AckReq = (&ar).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        Self::ack(&name, &mut conn, key, checked.as_lease_id()).await?;
+        let acked: SystemTime = SystemTime::now();
+        let reply = AckResponse {
+            acked: Some(acked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn nack(&self, req: Request<NackRequest>) -> Result<Response<NackResponse>, Status> {
+        let nr: NackRequest = req.into_inner();
+        let checked: NackReq = (&nr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        Self::nack(
+            &name,
+            &mut conn,
+            key,
+            checked.as_lease_id(),
+            checked.as_delay(),
+        )
+        .await?;
+        let nacked: SystemTime = SystemTime::now();
+        let reply = NackResponse {
+            nacked: Some(nacked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn extend_lease(
+        &self,
+        req: Request<ExtendLeaseRequest>,
+    ) -> Result<Response<ExtendLeaseResponse>, Status> {
+        let er: ExtendLeaseRequest = req.into_inner();
+        let checked: ExtendLeaseReq = (&er).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let deadline: SystemTime = Self::extend_lease(
+            &name,
+            &mut conn,
+            key,
+            checked.as_lease_id(),
+            checked.as_extension(),
+        )
+        .await?;
+        let reply = ExtendLeaseResponse {
+            deadline: Some(deadline.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn report_failure(
+        &self,
+        req: Request<ReportFailureRequest>,
+    ) -> Result<Response<ReportFailureResponse>, Status> {
+        let rfr: ReportFailureRequest = req.into_inner();
+        let checked: ReportFailureReq = rfr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let mut tx: Transaction = begin(&self.pool).await?;
+        let attempts: u64 = Self::fail(&name, &mut tx, key, checked.as_error()).await?;
+        let dead_lettered: Option<String> = match catalog::dead_letter(&name, &mut tx).await? {
+            Some((dlq, max_attempts)) if max_attempts <= attempts => {
+                self.tail_lock(&dlq, &mut tx).await?;
+                Self::dead_letter(&name, &dlq, &mut tx, key).await?;
+                Some(dlq)
+            }
+            _ => None,
+        };
+        commit(tx).await?;
+        if let Some(dlq) = &dead_lettered {
+            self.notifier.wake(dlq);
+        }
+        let reply = ReportFailureResponse {
+            attempts,
+            dead_lettered: dead_lettered.is_some(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn redrive(
+        &self,
+        req: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        let rr: RedriveRequest = req.into_inner();
+        let checked: RedriveReq = (&rr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let limit: i64 = checked
+            .as_max_messages()
+            .map(|u| u.min(i64::MAX as u64) as i64)
+            .unwrap_or(i64::MAX);
+        let mut tx: Transaction = begin(&self.pool).await?;
+        let (dlq, _) = catalog::dead_letter(&name, &mut tx).await?.ok_or_else(|| {
+            Status::failed_precondition(format!("No dead letter topic configured: {topic_id}"))
+        })?;
+        self.tail_lock(&name, &mut tx).await?;
+        let redriven: u64 = Self::redrive(&name, &dlq, &mut tx, limit).await?;
+        commit(tx).await?;
+        self.notifier.wake(&name);
+        let reply = RedriveResponse { redriven };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn queue_svc_new<T>(pool: &Pool, topic2table: T) -> impl QueueService
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    Svc {
+        pool: pool.clone(),
+        topic2table,
+        notifier: Arc::new(Notifier::default()),
+        safe_tail: Mutex::new(HashMap::new()),
+    }
+}
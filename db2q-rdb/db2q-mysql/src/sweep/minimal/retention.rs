@@ -0,0 +1,185 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use tokio::task::JoinHandle;
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{params, Conn, Pool};
+
+use crate::common::minimal::catalog::TOPIC_CONFIG;
+use crate::common::minimal::client::{execute, get_conn, my2status};
+use crate::common::minimal::time::now_micros;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+pub const BATCH_SIZE_DEFAULT: u64 = 1024;
+
+struct Limits {
+    name: String,
+    max_messages: Option<i64>,
+    max_age_us: Option<i64>,
+    max_bytes: Option<i64>,
+}
+
+async fn limits<Q>(conn: &mut Q) -> Result<Vec<Limits>, Status>
+where
+    Q: Queryable,
+{
+    let query = format!(
+        r#"
+            SELECT
+                name,
+                retain_messages,
+                retain_age,
+                retain_bytes
+            FROM {TOPIC_CONFIG}
+            WHERE
+                retain_messages IS NOT NULL
+                OR retain_age IS NOT NULL
+                OR retain_bytes IS NOT NULL
+            ORDER BY name
+        "#
+    );
+    conn.query_map(query, |(name, max_messages, max_age_us, max_bytes)| {
+        Limits {
+            name,
+            max_messages,
+            max_age_us,
+            max_bytes,
+        }
+    })
+    .await
+    .map_err(|e| my2status(e, "Unable to get retentions"))
+}
+
+// LIMIT/OFFSET accept no NULL: each limit is a separate query
+async fn boundary<Q>(
+    checked_name: &str,
+    conn: &mut Q,
+    limits: &Limits,
+) -> Result<Option<i64>, Status>
+where
+    Q: Queryable,
+{
+    let col = |e| my2status(e, "Unable to get a retention boundary");
+    let mut bounds: Vec<i64> = Vec::new();
+    if let Some(max_messages) = limits.max_messages {
+        let query = format!(
+            r#"
+                SELECT `key`
+                FROM {checked_name}
+                ORDER BY `key` DESC
+                LIMIT 1
+                OFFSET :max_messages
+            "#
+        );
+        let bound: Option<i64> = conn
+            .exec_first(query, params! { "max_messages" => max_messages })
+            .await
+            .map_err(col)?;
+        bounds.extend(bound);
+    }
+    if let Some(max_age_us) = limits.max_age_us {
+        let query = format!(
+            r#"
+                SELECT MAX(`key`)
+                FROM {checked_name}
+                WHERE pushed < :now - :max_age
+            "#
+        );
+        let params = params! { "now" => now_micros(), "max_age" => max_age_us };
+        let bound: Option<Option<i64>> = conn.exec_first(query, params).await.map_err(col)?;
+        bounds.extend(bound.flatten());
+    }
+    if let Some(max_bytes) = limits.max_bytes {
+        let query = format!(
+            r#"
+                SELECT MAX(`key`)
+                FROM (
+                    SELECT
+                        `key`,
+                        SUM(LENGTH(val)) OVER (ORDER BY `key` DESC) AS total
+                    FROM {checked_name}
+                ) AS newer
+                WHERE total > :max_bytes
+            "#
+        );
+        let bound: Option<Option<i64>> = conn
+            .exec_first(query, params! { "max_bytes" => max_bytes })
+            .await
+            .map_err(col)?;
+        bounds.extend(bound.flatten());
+    }
+    Ok(bounds.into_iter().max())
+}
+
+async fn trim_batch<Q>(
+    checked_name: &str,
+    conn: &mut Q,
+    upper: i64,
+    limit: i64,
+) -> Result<u64, Status>
+where
+    Q: Queryable,
+{
+    let query = format!(
+        r#"
+            DELETE FROM {checked_name}
+            WHERE `key` <= :upper
+            ORDER BY `key`
+            LIMIT :limit
+        "#
+    );
+    execute(conn, &query, params! { "upper" => upper, "limit" => limit })
+        .await
+        .map_err(|e| my2status(e, "Unable to trim a topic"))
+}
+
+// small batches; pushes(higher keys) and reads are never blocked
+async fn trim_topic<Q>(conn: &mut Q, limits: &Limits, batch_size: u64) -> Result<u64, Status>
+where
+    Q: Queryable,
+{
+    let checked_name: &str = limits.name.as_str();
+    let upper: i64 = match boundary(checked_name, conn, limits).await? {
+        None => return Ok(0),
+        Some(upper) => upper,
+    };
+    let limit: i64 = batch_size.clamp(1, i64::MAX as u64) as i64;
+    let mut trimmed: u64 = 0;
+    loop {
+        let deleted: u64 = trim_batch(checked_name, conn, upper, limit).await?;
+        trimmed += deleted;
+        if deleted < limit as u64 {
+            return Ok(trimmed);
+        }
+    }
+}
+
+pub async fn trim(pool: &Pool, batch_size: u64) -> Result<u64, Status> {
+    let mut conn: Conn = get_conn(pool).await?;
+    let all: Vec<Limits> = limits(&mut conn).await?;
+    let mut trimmed: u64 = 0;
+    for l in all {
+        // a topic may be dropped while trimming
+        match trim_topic(&mut conn, &l, batch_size).await {
+            Ok(cnt) => trimmed += cnt,
+            Err(e) => log::warn!("Unable to trim {}: {e}", l.name),
+        }
+    }
+    Ok(trimmed)
+}
+
+pub fn retention_worker_new(pool: &Pool, interval: Duration, batch_size: u64) -> JoinHandle<()> {
+    let pool: Pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            match trim(&pool, batch_size).await {
+                Ok(0) => {}
+                Ok(cnt) => log::debug!("Messages trimmed: {cnt}"),
+                Err(e) => log::warn!("Unable to trim: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -0,0 +1,91 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use tokio::task::JoinHandle;
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{params, Conn, Pool};
+
+use db2q::queue::cmd::push::DEDUP_WINDOW;
+
+use crate::common::minimal::catalog;
+use crate::common::minimal::client::{execute, get_conn, my2status};
+use crate::common::minimal::time::now_micros;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+pub const BATCH_SIZE_DEFAULT: u64 = 1024;
+
+async fn sweep_batch<Q>(checked_name: &str, conn: &mut Q, batch_size: i64) -> Result<u64, Status>
+where
+    Q: Queryable,
+{
+    let query = format!(
+        r#"
+            DELETE FROM {checked_name}
+            WHERE expires_at <= :now
+            ORDER BY expires_at
+            LIMIT :limit
+        "#
+    );
+    execute(
+        conn,
+        &query,
+        params! { "now" => now_micros(), "limit" => batch_size },
+    )
+    .await
+    .map_err(|e| my2status(e, "Unable to delete expired messages"))
+}
+
+// each batch is a short transaction(no long running locks)
+pub async fn sweep_topic<Q>(
+    checked_name: &str,
+    conn: &mut Q,
+    batch_size: u64,
+) -> Result<u64, Status>
+where
+    Q: Queryable,
+{
+    let limit: i64 = batch_size.clamp(1, i64::MAX as u64) as i64;
+    let mut swept: u64 = 0;
+    loop {
+        let deleted: u64 = sweep_batch(checked_name, conn, limit).await?;
+        swept += deleted;
+        if deleted < limit as u64 {
+            return Ok(swept);
+        }
+    }
+}
+
+pub async fn sweep(pool: &Pool, batch_size: u64) -> Result<u64, Status> {
+    let mut conn: Conn = get_conn(pool).await?;
+    let names: Vec<String> = catalog::names(&mut conn).await?;
+    let mut swept: u64 = 0;
+    match catalog::sweep_dedup(&mut conn, DEDUP_WINDOW).await {
+        Ok(0) => {}
+        Ok(cnt) => log::debug!("Old request ids deleted: {cnt}"),
+        Err(e) => log::warn!("Unable to sweep request ids: {e}"),
+    }
+    for name in names {
+        // a topic may be dropped while sweeping
+        match sweep_topic(name.as_str(), &mut conn, batch_size).await {
+            Ok(cnt) => swept += cnt,
+            Err(e) => log::warn!("Unable to sweep {name}: {e}"),
+        }
+    }
+    Ok(swept)
+}
+
+pub fn sweeper_new(pool: &Pool, interval: Duration, batch_size: u64) -> JoinHandle<()> {
+    let pool: Pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            match sweep(&pool, batch_size).await {
+                Ok(0) => {}
+                Ok(cnt) => log::debug!("Expired messages deleted: {cnt}"),
+                Err(e) => log::warn!("Unable to sweep: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -0,0 +1,523 @@
+use core::time::Duration;
+use std::time::SystemTime;
+
+use tonic::{Request, Response, Status};
+
+use mysql_async::prelude::Queryable;
+use mysql_async::{params, Conn, Pool, Transaction};
+
+use db2q::uuid::Uuid;
+
+use db2q::topic::cmd::create::CreateReq;
+use db2q::topic::cmd::describe::DescribeReq;
+use db2q::topic::cmd::drop::DropReq;
+use db2q::topic::cmd::list::ListReq;
+use db2q::topic::cmd::retention::Retention;
+use db2q::topic::cmd::update_retention::UpdateRetentionReq;
+
+use db2q::db2q::proto::queue::v1::Uuid as Guid;
+
+use db2q::db2q::proto::queue::v1::topic_service_server::TopicService;
+use db2q::db2q::proto::queue::v1::topic_svc::{CreateRequest, CreateResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{
+    DeadLetter as DeadLetterPb, Retention as RetentionPb,
+};
+use db2q::db2q::proto::queue::v1::topic_svc::{DescribeRequest, DescribeResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{DropRequest, DropResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{ListRequest, ListResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{UpdateRetentionRequest, UpdateRetentionResponse};
+
+use crate::common::minimal::catalog::TOPIC_CONFIG;
+use crate::common::minimal::client::{
+    begin, commit, execute, get_conn, is_server_error, my2status,
+};
+use crate::common::minimal::client::{
+    ER_NO_REFERENCED_ROW, ER_NO_SUCH_TABLE, ER_ROW_IS_REFERENCED,
+};
+use crate::common::minimal::time::{dur2micros, micros2dur, micros2time, now_micros};
+use crate::topic::minimal::topic2table::TopicConv;
+
+// limits in the catalog(microseconds for the age)
+struct Limits {
+    max_messages: Option<i64>,
+    max_age_us: Option<i64>,
+    max_bytes: Option<i64>,
+}
+
+impl From<Option<&Retention>> for Limits {
+    fn from(r: Option<&Retention>) -> Self {
+        Self {
+            max_messages: r
+                .and_then(|r| r.as_max_messages())
+                .map(|u| u.min(i64::MAX as u64) as i64),
+            max_age_us: r.and_then(|r| r.as_max_age()).map(dur2micros),
+            max_bytes: r
+                .and_then(|r| r.as_max_bytes())
+                .map(|u| u.min(i64::MAX as u64) as i64),
+        }
+    }
+}
+
+pub struct Svc<T> {
+    pool: Pool,
+    topic_conv: T,
+}
+
+impl<T> Svc<T>
+where
+    T: TopicConv,
+{
+    async fn create<Q>(
+        &self,
+        checked_name: &str,
+        priority: bool,
+        conn: &mut Q,
+    ) -> Result<(), Status>
+    where
+        Q: Queryable,
+    {
+        let priority_index: String = match priority {
+            true => format!(
+                r#"
+                    ,INDEX {checked_name}_priority (priority DESC, `key`)
+                "#
+            ),
+            false => String::new(),
+        };
+        let query = format!(
+            r#"
+                CREATE TABLE {checked_name} (
+                    `key` BIGINT AUTO_INCREMENT PRIMARY KEY,
+                    val LONGBLOB NOT NULL,
+                    pushed BIGINT NOT NULL,
+                    visible_at BIGINT NOT NULL,
+                    expires_at BIGINT,
+                    priority INTEGER NOT NULL DEFAULT 0,
+                    headers LONGBLOB NOT NULL,
+                    leased_until BIGINT,
+                    lease_id CHAR(36),
+                    attempts BIGINT NOT NULL DEFAULT 0,
+                    last_error TEXT,
+                    INDEX {checked_name}_expires (expires_at),
+                    INDEX {checked_name}_pushed (pushed)
+                    {priority_index}
+                ) ENGINE = InnoDB
+            "#
+        );
+        conn.query_drop(query)
+            .await
+            .map_err(|e| my2status(e, "Unexpected error"))
+    }
+
+    async fn configure<Q>(
+        &self,
+        checked_name: &str,
+        dead_letter: Option<(&str, u64)>,
+        safe_tail: bool,
+        default_ttl: Option<Duration>,
+        conn: &mut Q,
+    ) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                INSERT INTO {TOPIC_CONFIG} (
+                    name,
+                    dead_letter,
+                    max_attempts,
+                    safe_tail,
+                    default_ttl,
+                    created
+                )
+                VALUES (
+                    :name,
+                    :dead_letter,
+                    :max_attempts,
+                    :safe_tail,
+                    :default_ttl,
+                    :created
+                )
+            "#
+        );
+        let dlq: Option<&str> = dead_letter.map(|d| d.0);
+        let max_attempts: i64 = dead_letter
+            .map(|d| d.1.min(i64::MAX as u64) as i64)
+            .unwrap_or_default();
+        let ttl_us: Option<i64> = default_ttl.map(dur2micros);
+        let params = params! {
+            "name" => checked_name,
+            "dead_letter" => dlq,
+            "max_attempts" => max_attempts,
+            "safe_tail" => safe_tail,
+            "default_ttl" => ttl_us,
+            "created" => now_micros(),
+        };
+        execute(conn, &query, params).await.map_err(|e| {
+            match is_server_error(&e, ER_NO_REFERENCED_ROW) {
+                true => Status::failed_precondition(format!("dead letter topic missing: {e}")),
+                false => my2status(e, "Unexpected error"),
+            }
+        })
+    }
+
+    async fn retain<Q>(
+        &self,
+        checked_name: &str,
+        limits: &Limits,
+        conn: &mut Q,
+    ) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                UPDATE {TOPIC_CONFIG}
+                SET
+                    retain_messages = :max_messages,
+                    retain_age = :max_age,
+                    retain_bytes = :max_bytes
+                WHERE name = :name
+            "#
+        );
+        let params = params! {
+            "name" => checked_name,
+            "max_messages" => limits.max_messages,
+            "max_age" => limits.max_age_us,
+            "max_bytes" => limits.max_bytes,
+        };
+        execute(conn, &query, params)
+            .await
+            .map_err(|e| my2status(e, "Unable to update a retention"))
+    }
+
+    async fn unconfigure<Q>(&self, checked_name: &str, conn: &mut Q) -> Result<u64, Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                DELETE FROM {TOPIC_CONFIG}
+                WHERE name = :name
+            "#
+        );
+        execute(conn, &query, params! { "name" => checked_name })
+            .await
+            .map_err(|e| match is_server_error(&e, ER_ROW_IS_REFERENCED) {
+                true => {
+                    Status::failed_precondition(format!("still used as a dead letter topic: {e}"))
+                }
+                false => my2status(e, "Unexpected error"),
+            })
+    }
+
+    async fn drop<Q>(&self, checked_name: &str, conn: &mut Q) -> Result<(), Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                DROP TABLE IF EXISTS {checked_name}
+            "#
+        );
+        conn.query_drop(query)
+            .await
+            .map_err(|e| my2status(e, "Unexpected error"))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn list<Q>(&self, conn: &mut Q) -> Result<Vec<Guid>, Status>
+    where
+        Q: Queryable,
+    {
+        let query: &str = r#"
+            SELECT
+                table_name
+            FROM information_schema.tables
+            WHERE
+                table_schema = DATABASE()
+                AND table_name <> :catalog
+            ORDER BY table_name
+        "#;
+        let names: Vec<String> = conn
+            .exec(query, params! { "catalog" => TOPIC_CONFIG })
+            .await
+            .map_err(|e| my2status(e, "Unable to get table names"))?;
+        names
+            .iter()
+            .map(|name: &String| self.topic_conv.name2id(name).map(Guid::from))
+            .collect()
+    }
+
+    // (described without the dead letter, the dead letter name)
+    async fn config<Q>(
+        &self,
+        checked_name: &str,
+        conn: &mut Q,
+    ) -> Result<(DescribeResponse, Option<String>), Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    created,
+                    dead_letter,
+                    max_attempts,
+                    safe_tail,
+                    default_ttl,
+                    retain_messages,
+                    retain_age,
+                    retain_bytes,
+                    EXISTS(
+                        SELECT 1
+                        FROM information_schema.statistics
+                        WHERE
+                            table_schema = DATABASE()
+                            AND table_name = :name
+                            AND index_name = CONCAT(:name, '_priority')
+                    )
+                FROM {TOPIC_CONFIG}
+                WHERE name = :name
+            "#
+        );
+        type Config = (
+            i64,
+            Option<String>,
+            i64,
+            bool,
+            Option<i64>,
+            Option<i64>,
+            Option<i64>,
+            Option<i64>,
+            bool,
+        );
+        let row: Config = conn
+            .exec_first(query, params! { "name" => checked_name })
+            .await
+            .map_err(|e| my2status(e, "Unable to get a config"))?
+            .ok_or_else(|| Status::not_found(format!("No such topic: {checked_name}")))?;
+        let (
+            created,
+            dead_letter,
+            max_attempts,
+            safe_tail,
+            ttl_us,
+            max_messages,
+            max_age_us,
+            max_bytes,
+            priority,
+        ) = row;
+        let us2dur = |us: i64| micros2dur(us).try_into().ok();
+        let retention: Option<RetentionPb> = match (max_messages, max_age_us, max_bytes) {
+            (None, None, None) => None,
+            _ => Some(RetentionPb {
+                max_messages: max_messages.unwrap_or_default().max(0) as u64,
+                max_age: max_age_us.and_then(us2dur),
+                max_bytes: max_bytes.unwrap_or_default().max(0) as u64,
+            }),
+        };
+        let described = DescribeResponse {
+            created: Some(micros2time(created).into()),
+            dead_letter: dead_letter.as_ref().map(|_| DeadLetterPb {
+                topic_id: None,
+                max_attempts: max_attempts.max(0) as u64,
+            }),
+            safe_tail,
+            default_ttl: ttl_us.and_then(us2dur),
+            retention,
+            priority,
+            ..Default::default()
+        };
+        Ok((described, dead_letter))
+    }
+
+    // the row count and the sizes are the estimates of the storage engine
+    async fn stats<Q>(
+        &self,
+        checked_name: &str,
+        conn: &mut Q,
+        described: &mut DescribeResponse,
+    ) -> Result<(), Status>
+    where
+        Q: Queryable,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    COALESCE(MIN(`key`), -1),
+                    COALESCE(MAX(`key`), -1),
+                    CAST(COALESCE(SUM(LENGTH(val)), 0) AS SIGNED)
+                FROM {checked_name}
+            "#
+        );
+        let keys: Option<(i64, i64, i64)> = conn.query_first(query).await.map_err(|e| {
+            match is_server_error(&e, ER_NO_SUCH_TABLE) {
+                true => Status::not_found(format!("No such topic: {checked_name}")),
+                false => my2status(e, "Unable to get stats"),
+            }
+        })?;
+        let (min_key, max_key, payload_bytes) = keys.unwrap_or((-1, -1, 0));
+        let query: &str = r#"
+            SELECT
+                CAST(COALESCE(table_rows, 0) AS UNSIGNED),
+                CAST(COALESCE(data_length, 0) + COALESCE(index_length, 0) AS UNSIGNED)
+            FROM information_schema.tables
+            WHERE
+                table_schema = DATABASE()
+                AND table_name = :name
+        "#;
+        let sizes: Option<(u64, u64)> = conn
+            .exec_first(query, params! { "name" => checked_name })
+            .await
+            .map_err(|e| my2status(e, "Unable to get stats"))?;
+        let (count_estimate, relation_size) = sizes.unwrap_or_default();
+        described.min_key = min_key;
+        described.max_key = max_key;
+        described.count_estimate = count_estimate;
+        described.payload_bytes = payload_bytes.max(0) as u64;
+        described.relation_size = relation_size;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl<T> TopicService for Svc<T>
+where
+    T: Send + Sync + 'static + TopicConv,
+{
+    async fn create(
+        &self,
+        req: Request<CreateRequest>,
+    ) -> Result<Response<CreateResponse>, Status> {
+        let cr: CreateRequest = req.into_inner();
+        let checked: CreateReq = (&cr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let dead_letter: Option<(String, u64)> = checked.as_dead_letter().map(|d| {
+            (
+                self.topic_conv.id2name(d.as_topic_id()),
+                d.as_max_attempts(),
+            )
+        });
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        // a DDL commits implicitly: the table is dropped if the config is rejected
+        self.create(name.as_str(), checked.as_priority(), &mut conn)
+            .await?;
+        let mut tx: Transaction = conn
+            .start_transaction(Default::default())
+            .await
+            .map_err(|e| Status::unavailable(format!("Unable to start a transaction: {e}")))?;
+        let configured: Result<(), Status> = async {
+            self.configure(
+                name.as_str(),
+                dead_letter.as_ref().map(|(n, m)| (n.as_str(), *m)),
+                checked.as_safe_tail(),
+                checked.as_default_ttl(),
+                &mut tx,
+            )
+            .await?;
+            self.retain(name.as_str(), &checked.as_retention().into(), &mut tx)
+                .await?;
+            Ok(())
+        }
+        .await;
+        match configured {
+            Ok(_) => commit(tx).await?,
+            Err(e) => {
+                drop(tx);
+                self.drop(name.as_str(), &mut conn).await?;
+                return Err(e);
+            }
+        }
+        let created: SystemTime = SystemTime::now();
+        let reply = CreateResponse {
+            created: Some(created.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn drop(&self, req: Request<DropRequest>) -> Result<Response<DropResponse>, Status> {
+        let cr: DropRequest = req.into_inner();
+        let checked: DropReq = (&cr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        // the config first: rejected while used as a dead letter topic
+        self.unconfigure(name.as_str(), &mut conn).await?;
+        self.drop(name.as_str(), &mut conn).await?;
+        let dropped: SystemTime = SystemTime::now();
+        let reply = DropResponse {
+            dropped: Some(dropped.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn list(&self, req: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        let lr: ListRequest = req.into_inner();
+        let _checked: ListReq = (&lr).try_into()?;
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        let topics: Vec<Guid> = self.list(&mut conn).await?;
+        let reply = ListResponse { topics };
+        Ok(Response::new(reply))
+    }
+
+    async fn update_retention(
+        &self,
+        req: Request<UpdateRetentionRequest>,
+    ) -> Result<Response<UpdateRetentionResponse>, Status> {
+        let ur: UpdateRetentionRequest = req.into_inner();
+        let checked: UpdateRetentionReq = (&ur).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let limits: Limits = checked.as_retention().into();
+        let mut conn: Conn = get_conn(&self.pool).await?;
+        // the found rows: an unchanged retention is not an error
+        let found: Option<String> = conn
+            .exec_first(
+                format!("SELECT name FROM {TOPIC_CONFIG} WHERE name = :name"),
+                params! { "name" => name.as_str() },
+            )
+            .await
+            .map_err(|e| my2status(e, "Unable to get a config"))?;
+        match found {
+            None => Err(Status::not_found(format!("No such topic: {topic_id}"))),
+            Some(_) => Ok(()),
+        }?;
+        self.retain(name.as_str(), &limits, &mut conn).await?;
+        let updated: SystemTime = SystemTime::now();
+        let reply = UpdateRetentionResponse {
+            updated: Some(updated.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn describe(
+        &self,
+        req: Request<DescribeRequest>,
+    ) -> Result<Response<DescribeResponse>, Status> {
+        let dr: DescribeRequest = req.into_inner();
+        let checked: DescribeReq = (&dr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        // a snapshot of the config and the stats
+        let mut tx: Transaction = begin(&self.pool).await?;
+        let (mut reply, dead_letter) = self.config(name.as_str(), &mut tx).await?;
+        self.stats(name.as_str(), &mut tx, &mut reply).await?;
+        commit(tx).await?;
+        if let (Some(d), Some(dlq)) = (reply.dead_letter.as_mut(), dead_letter) {
+            d.topic_id = Some(self.topic_conv.name2id(dlq.as_str())?.into());
+        }
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn topic_svc_new<T>(pool: &Pool, topic_conv: T) -> impl TopicService
+where
+    T: Send + Sync + 'static + TopicConv,
+{
+    Svc {
+        pool: pool.clone(),
+        topic_conv,
+    }
+}
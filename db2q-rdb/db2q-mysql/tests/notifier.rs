@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+use db2q_mysql::common::minimal::notifier::Notifier;
+
+#[test]
+fn unwatched_topics_pruned() {
+    let notifier: Notifier = Notifier::default();
+
+    let t1: Arc<Notify> = notifier.watch("t1");
+    let again: Arc<Notify> = notifier.watch("t1");
+    assert!(Arc::ptr_eq(&t1, &again));
+    let t2: Arc<Notify> = notifier.watch("t2");
+    assert_eq!(notifier.as_watched(), 2);
+
+    drop(t1);
+    drop(again);
+    let _t3: Arc<Notify> = notifier.watch("t3");
+    assert_eq!(notifier.as_watched(), 2);
+
+    drop(t2);
+    let _t1: Arc<Notify> = notifier.watch("t1");
+    assert_eq!(notifier.as_watched(), 2);
+}
@@ -2,7 +2,7 @@ use std::env;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-use db2q_postgresql::db2q::queue::st::svc::locked_q_topic_svc_new;
+use db2q_postgresql::db2q::queue::st::svc::locked_q_topic_group_svc_new;
 
 use db2q_postgresql::deadpool_postgres;
 use db2q_postgresql::tonic;
@@ -18,6 +18,7 @@ use tokio_postgres::{Config, NoTls};
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
 
 use db2q_postgresql::count_service_server::CountServiceServer;
+use db2q_postgresql::group_service_server::GroupServiceServer;
 use db2q_postgresql::queue_service_server::QueueServiceServer;
 use db2q_postgresql::topic_service_server::TopicServiceServer;
 
@@ -48,6 +49,8 @@ async fn main() -> Result<(), String> {
     let mgcfg: ManagerConfig = ManagerConfig {
         recycling_method: RecyclingMethod::Fast,
     };
+    let listener = db2q_postgresql::common::minimal::listener::listener_new(pgcfg.clone(), NoTls);
+
     let mg: Manager = Manager::from_config(pgcfg, NoTls, mgcfg);
 
     let pool: Pool = Pool::builder(mg)
@@ -55,6 +58,21 @@ async fn main() -> Result<(), String> {
         .build()
         .map_err(|e| format!("Unable to build pool: {e}"))?;
 
+    db2q_postgresql::common::minimal::catalog::init_pool(&pool)
+        .await
+        .map_err(|e| format!("Unable to create a catalog: {e}"))?;
+
+    db2q_postgresql::sweep::minimal::ttl::sweeper_new(
+        &pool,
+        db2q_postgresql::sweep::minimal::ttl::INTERVAL_DEFAULT,
+        db2q_postgresql::sweep::minimal::ttl::BATCH_SIZE_DEFAULT,
+    );
+    db2q_postgresql::sweep::minimal::retention::retention_worker_new(
+        &pool,
+        db2q_postgresql::sweep::minimal::retention::INTERVAL_DEFAULT,
+        db2q_postgresql::sweep::minimal::retention::BATCH_SIZE_DEFAULT,
+    );
+
     let t2t = db2q_postgresql::topic::minimal::topic2table::topic2table_prefix_default();
     let topic_svc = db2q_postgresql::topic::minimal::svc::topic_svc_new(&pool, t2t);
     let topic_svc_shared: Arc<_> = Arc::new(topic_svc);
@@ -64,16 +82,23 @@ async fn main() -> Result<(), String> {
     let count_svr: CountServiceServer<_> = CountServiceServer::new(count_svc);
 
     let t2t = db2q_postgresql::topic::minimal::topic2table::topic2table_prefix_default();
-    let queue_svc = db2q_postgresql::queue::minimal::svc::queue_svc_new(&pool, t2t);
+    let group_svc = db2q_postgresql::group::minimal::svc::group_svc_new(&pool, t2t);
+    let group_svc_shared: Arc<_> = Arc::new(group_svc);
+
+    let t2t = db2q_postgresql::topic::minimal::topic2table::topic2table_prefix_default();
+    let queue_svc =
+        db2q_postgresql::queue::minimal::svc::queue_svc_new_with_listener(&pool, t2t, &listener);
     let queue_svc_shared: Arc<_> = Arc::new(queue_svc);
 
-    let locked_q_topic_svc = locked_q_topic_svc_new(&queue_svc_shared, &topic_svc_shared);
+    let locked_q_topic_svc =
+        locked_q_topic_group_svc_new(&queue_svc_shared, &topic_svc_shared, &group_svc_shared);
     let lqts_shared: Arc<_> = Arc::new(locked_q_topic_svc);
 
     let topic_svr: TopicServiceServer<_> = TopicServiceServer::new(lqts_shared.clone());
 
     let rw_q_svc: RwQueueSvc<_> = rw_q_svc_new(&lqts_shared);
     let queue_svr: QueueServiceServer<_> = QueueServiceServer::new(rw_q_svc.clone());
+    let group_svr: GroupServiceServer<_> = GroupServiceServer::new(rw_q_svc.clone());
 
     rw_q_svc
         .make_writable()
@@ -84,7 +109,8 @@ async fn main() -> Result<(), String> {
     let router: Router<_> = sv
         .add_service(topic_svr)
         .add_service(queue_svr)
-        .add_service(count_svr);
+        .add_service(count_svr)
+        .add_service(group_svr);
 
     router
         .serve(listen)
@@ -66,10 +66,10 @@ tdrop(){
 }
 
 tpush(){
-	jq -n -c '{
+	jq -n -c --argjson lo "${1}" '{
 		request_id: {
-			hi: 20230911,
-			lo: 092555,
+			hi: 20231003,
+			lo: $lo,
 		},
 		topic_id: {
 			hi: 3776,
@@ -227,6 +227,26 @@ wnext(){
 		db2q.proto.queue.v1.QueueService/WaitNext
 }
 
+qpop(){
+	jq -n -c '{
+		request_id: {
+			hi: 20231002,
+			lo: 101530,
+		},
+		topic_id: {
+			hi: 3776,
+			lo:  599,
+		},
+	}' |
+	grpcurl \
+		-plaintext \
+		-d @ \
+		-import-path "${protodir}" \
+		-proto db2q/proto/queue/v1/q.proto \
+		"${listen_addr}" \
+		db2q.proto.queue.v1.QueueService/PopFront
+}
+
 
 tdrop
 tcreate
@@ -235,20 +255,24 @@ tdrop
 
 tcreate
 tcreate2
-tpush
-tpush
-tpush
+tpush 1
+tpush 2
+tpush 3
+tpush 3
 tcount
 tlist
 qnext
 cexact
 echo 'ANALYZE' | psql
 cfast
-tpush
-tpush
+tpush 4
+tpush 5
 qkeys
 wnext &
 sleep 0.5
 echo "INSERT INTO t0000000000000ec00000000000000257(val) VALUES('')" | psql
 
 wait
+
+qpop
+tcount
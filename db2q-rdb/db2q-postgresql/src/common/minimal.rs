@@ -1 +1,3 @@
+pub mod catalog;
+pub mod listener;
 pub mod topic2table;
@@ -0,0 +1,400 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use deadpool::managed::PoolError;
+use deadpool_postgres::{Client, GenericClient, Pool};
+
+use super::listener::CHANNEL;
+
+pub const TOPIC_CONFIG: &str = "db2q.topic_config";
+pub const NOTIFY_PUSHED: &str = "db2q.notify_pushed";
+pub const CONSUMER_OFFSET: &str = "db2q.consumer_offset";
+pub const PUSH_DEDUP: &str = "db2q.push_dedup";
+
+// applied in order to every topic table on init; each must be idempotent
+const TOPIC_MIGRATIONS: &[&str] = &[
+    // leases
+    r#"
+        ALTER TABLE {table}
+        ADD COLUMN IF NOT EXISTS leased_until TIMESTAMPTZ,
+        ADD COLUMN IF NOT EXISTS lease_id UUID
+    "#,
+    // dead letters
+    r#"
+        ALTER TABLE {table}
+        ADD COLUMN IF NOT EXISTS attempts BIGINT NOT NULL DEFAULT 0,
+        ADD COLUMN IF NOT EXISTS last_error TEXT
+    "#,
+    // insert timestamps
+    r#"
+        ALTER TABLE {table}
+        ADD COLUMN IF NOT EXISTS pushed TIMESTAMPTZ NOT NULL DEFAULT CLOCK_TIMESTAMP()
+    "#,
+    // idempotent pushes(request ids moved to the dedup table)
+    r#"
+        DO $$
+        BEGIN
+            IF EXISTS (
+                SELECT 1
+                FROM information_schema.columns
+                WHERE
+                    table_schema = 'public'
+                    AND table_name = '{table}'
+                    AND column_name = 'req_id'
+            ) THEN
+                INSERT INTO {push_dedup} (name, req_id, key, pushed)
+                SELECT '{table}', req_id, key, pushed
+                FROM {table}
+                WHERE req_id IS NOT NULL
+                ON CONFLICT (name, req_id) DO NOTHING;
+
+                ALTER TABLE {table} DROP COLUMN req_id;
+            END IF;
+        END
+        $$
+    "#,
+    // push notifications
+    r#"
+        DROP TRIGGER IF EXISTS {table}_pushed ON {table};
+
+        CREATE TRIGGER {table}_pushed
+        AFTER INSERT ON {table}
+        FOR EACH STATEMENT
+        EXECUTE FUNCTION {notify_pushed}()
+    "#,
+    // delayed messages
+    r#"
+        ALTER TABLE {table}
+        ADD COLUMN IF NOT EXISTS visible_at TIMESTAMPTZ NOT NULL DEFAULT CLOCK_TIMESTAMP()
+    "#,
+    // expiry
+    r#"
+        ALTER TABLE {table}
+        ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+        CREATE INDEX IF NOT EXISTS {table}_expires
+        ON {table} (expires_at)
+        WHERE expires_at IS NOT NULL
+    "#,
+    // priorities
+    r#"
+        ALTER TABLE {table}
+        ADD COLUMN IF NOT EXISTS priority INTEGER NOT NULL DEFAULT 0
+    "#,
+    // headers
+    r#"
+        ALTER TABLE {table}
+        ADD COLUMN IF NOT EXISTS hdr_keys TEXT[] NOT NULL DEFAULT '{}',
+        ADD COLUMN IF NOT EXISTS hdr_vals BYTEA[] NOT NULL DEFAULT '{}'
+    "#,
+    // time bounded counts
+    r#"
+        CREATE INDEX IF NOT EXISTS {table}_pushed
+        ON {table} USING BRIN (pushed)
+    "#,
+];
+
+pub async fn init<C>(client: &C) -> Result<(), Status>
+where
+    C: GenericClient,
+{
+    let query: String = format!(
+        r#"
+            CREATE SCHEMA IF NOT EXISTS db2q;
+
+            CREATE TABLE IF NOT EXISTS {TOPIC_CONFIG} (
+                name TEXT PRIMARY KEY,
+                dead_letter TEXT REFERENCES {TOPIC_CONFIG} (name),
+                max_attempts BIGINT NOT NULL DEFAULT 0,
+                safe_tail BOOLEAN NOT NULL DEFAULT FALSE,
+                default_ttl INTERVAL,
+                retain_messages BIGINT,
+                retain_age INTERVAL,
+                retain_bytes BIGINT,
+                created TIMESTAMPTZ NOT NULL DEFAULT CLOCK_TIMESTAMP()
+            );
+
+            ALTER TABLE {TOPIC_CONFIG}
+            ADD COLUMN IF NOT EXISTS safe_tail BOOLEAN NOT NULL DEFAULT FALSE,
+            ADD COLUMN IF NOT EXISTS default_ttl INTERVAL,
+            ADD COLUMN IF NOT EXISTS retain_messages BIGINT,
+            ADD COLUMN IF NOT EXISTS retain_age INTERVAL,
+            ADD COLUMN IF NOT EXISTS retain_bytes BIGINT;
+
+            CREATE TABLE IF NOT EXISTS {CONSUMER_OFFSET} (
+                name TEXT NOT NULL REFERENCES {TOPIC_CONFIG} (name) ON DELETE CASCADE,
+                grp TEXT NOT NULL,
+                key BIGINT NOT NULL,
+                updated TIMESTAMPTZ NOT NULL DEFAULT CLOCK_TIMESTAMP(),
+                PRIMARY KEY (name, grp)
+            );
+
+            CREATE TABLE IF NOT EXISTS {PUSH_DEDUP} (
+                name TEXT NOT NULL REFERENCES {TOPIC_CONFIG} (name) ON DELETE CASCADE,
+                req_id UUID NOT NULL,
+                key BIGINT NOT NULL,
+                pushed TIMESTAMPTZ NOT NULL,
+                PRIMARY KEY (name, req_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS push_dedup_pushed
+            ON {PUSH_DEDUP} (pushed);
+
+            CREATE OR REPLACE FUNCTION {NOTIFY_PUSHED}() RETURNS TRIGGER AS $$
+            BEGIN
+                PERFORM pg_notify('{CHANNEL}', TG_TABLE_NAME);
+                RETURN NULL;
+            END;
+            $$ LANGUAGE plpgsql;
+        "#
+    );
+    client
+        .batch_execute(&query)
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to create a catalog: {e}")),
+        })?;
+    migrate(client).await
+}
+
+// topic tables(key, val) in the public schema; including those created by older versions
+#[allow(clippy::result_large_err)]
+pub async fn topic_tables<C>(client: &C) -> Result<Vec<String>, Status>
+where
+    C: GenericClient,
+{
+    let query: &str = r#"
+        SELECT table_name::TEXT
+        FROM information_schema.columns
+        WHERE
+            table_schema = 'public'
+            AND column_name IN ('key', 'val')
+        GROUP BY table_name
+        HAVING COUNT(*) = 2
+        ORDER BY table_name
+    "#;
+    let rows = client
+        .query(query, &[])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get topic tables: {e}")),
+        })?;
+    rows.iter()
+        .map(|row| {
+            row.try_get(0)
+                .map_err(|e| Status::internal(format!("Unable to get a table name: {e}")))
+        })
+        .collect()
+}
+
+pub async fn migrate<C>(client: &C) -> Result<(), Status>
+where
+    C: GenericClient,
+{
+    let tables: Vec<String> = topic_tables(client).await?;
+
+    // topics created before the catalog get the default config
+    let query: String = format!(
+        r#"
+            INSERT INTO {TOPIC_CONFIG} (name)
+            SELECT UNNEST($1::TEXT[])
+            ON CONFLICT (name) DO NOTHING
+        "#
+    );
+    client
+        .execute(&query, &[&tables])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to configure old topics: {e}")),
+        })?;
+
+    let migrations: String = TOPIC_MIGRATIONS.join(";");
+    for table in &tables {
+        let query: String = migrations
+            .replace("{table}", table)
+            .replace("{notify_pushed}", NOTIFY_PUSHED)
+            .replace("{push_dedup}", PUSH_DEDUP);
+        client
+            .batch_execute(&query)
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to migrate a topic {table}: {e}")),
+            })?;
+    }
+    Ok(())
+}
+
+// twice the window: a request id is never swept while a push is checking it
+pub async fn sweep_dedup<C>(client: &C, window: Duration) -> Result<u64, Status>
+where
+    C: GenericClient,
+{
+    let query: String = format!(
+        r#"
+            DELETE FROM {PUSH_DEDUP}
+            WHERE pushed < CLOCK_TIMESTAMP() - 2 * $1::BIGINT * INTERVAL '1 microsecond'
+        "#
+    );
+    let window_us: i64 = window.as_micros().min(i64::MAX as u128) as i64;
+    client
+        .execute(&query, &[&window_us])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to delete old request ids: {e}")),
+        })
+}
+
+pub async fn init_pool(pool: &Pool) -> Result<(), Status> {
+    let client: Client = match pool.get().await {
+        Ok(client) => Ok(client),
+        Err(PoolError::Timeout(t)) => Err(Status::unavailable(format!("timeout: {t:#?}"))),
+        Err(PoolError::Closed) => Err(Status::failed_precondition("All connection closed")),
+        Err(e) => Err(Status::internal(format!("Unexpected error: {e}"))),
+    }?;
+    init(&client).await
+}
+
+pub async fn dead_letter<C>(checked_name: &str, client: &C) -> Result<Option<(String, u64)>, Status>
+where
+    C: GenericClient,
+{
+    let query: String = format!(
+        r#"
+            SELECT
+                dead_letter::TEXT,
+                max_attempts::BIGINT
+            FROM {TOPIC_CONFIG}
+            WHERE
+                name = $1::TEXT
+                AND dead_letter IS NOT NULL
+        "#
+    );
+    let orow = client
+        .query_opt(&query, &[&checked_name])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get a topic config: {e}")),
+        })?;
+    match orow {
+        None => Ok(None),
+        Some(row) => {
+            let dlq: String = row
+                .try_get(0)
+                .map_err(|e| Status::internal(format!("Unable to get a dead letter topic: {e}")))?;
+            let max_attempts: i64 = row
+                .try_get(1)
+                .map_err(|e| Status::internal(format!("Unable to get max attempts: {e}")))?;
+            Ok(Some((dlq, max_attempts as u64)))
+        }
+    }
+}
+
+// false if not configured
+pub async fn safe_tail<C>(checked_name: &str, client: &C) -> Result<bool, Status>
+where
+    C: GenericClient,
+{
+    let query: String = format!(
+        r#"
+            SELECT safe_tail::BOOLEAN
+            FROM {TOPIC_CONFIG}
+            WHERE name = $1::TEXT
+        "#
+    );
+    let orow = client
+        .query_opt(&query, &[&checked_name])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get a topic config: {e}")),
+        })?;
+    orow.map(|row| row.try_get(0))
+        .transpose()
+        .map(|o: Option<bool>| o.unwrap_or(false))
+        .map_err(|e| Status::internal(format!("Unable to get a safe tail flag: {e}")))
+}
+
+// keys are committed in key order while the lock is held until the end of the transaction
+pub async fn tail_lock<C>(checked_name: &str, client: &C) -> Result<(), Status>
+where
+    C: GenericClient,
+{
+    let query: String = format!(
+        r#"
+            SELECT
+                pg_advisory_xact_lock('{TOPIC_CONFIG}'::REGCLASS::OID::INTEGER, HASHTEXT(name))
+            FROM {TOPIC_CONFIG}
+            WHERE
+                name = $1::TEXT
+                AND safe_tail
+        "#
+    );
+    client
+        .query(&query, &[&checked_name])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to lock a topic: {e}")),
+        })?;
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+pub async fn names<C>(client: &C) -> Result<Vec<String>, Status>
+where
+    C: GenericClient,
+{
+    let query: String = format!(
+        r#"
+            SELECT name::TEXT
+            FROM {TOPIC_CONFIG}
+            ORDER BY name
+        "#
+    );
+    let rows = client
+        .query(&query, &[])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get topic names: {e}")),
+        })?;
+    rows.iter()
+        .map(|row| {
+            row.try_get(0)
+                .map_err(|e| Status::internal(format!("Unable to get a topic name: {e}")))
+        })
+        .collect()
+}
+
+// the last consumed key of the group(if committed)
+pub async fn offset<C>(checked_name: &str, group: &str, client: &C) -> Result<Option<i64>, Status>
+where
+    C: GenericClient,
+{
+    let query: String = format!(
+        r#"
+            SELECT key::BIGINT
+            FROM {CONSUMER_OFFSET}
+            WHERE
+                name = $1::TEXT
+                AND grp = $2::TEXT
+        "#
+    );
+    let orow = client
+        .query_opt(&query, &[&checked_name, &group])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get an offset: {e}")),
+        })?;
+    orow.map(|row| row.try_get(0))
+        .transpose()
+        .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))
+}
@@ -0,0 +1,133 @@
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+use tokio::sync::Notify;
+
+use futures_util::StreamExt;
+
+use deadpool_postgres::tokio_postgres;
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_postgres::{AsyncMessage, Config, Socket};
+
+pub const CHANNEL: &str = "db2q_pushed";
+
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+// polling is a safety net while notifications are available
+pub const FALLBACK_INTERVAL_MINIMUM: Duration = Duration::from_secs(1);
+
+// entries are removed once all of their watchers are dropped
+pub struct Listener {
+    waiters: Mutex<HashMap<String, Weak<Notify>>>,
+}
+
+impl Listener {
+    pub fn watch(&self, checked_name: &str) -> Arc<Notify> {
+        match self.waiters.lock() {
+            Ok(mut guard) => match guard.get(checked_name).and_then(Weak::upgrade) {
+                Some(n) => n,
+                None => {
+                    guard.retain(|_, w| 0 < w.strong_count());
+                    let n: Arc<Notify> = Arc::new(Notify::new());
+                    guard.insert(checked_name.into(), Arc::downgrade(&n));
+                    n
+                }
+            },
+            Err(e) => {
+                log::warn!("Unable to lock: {e}");
+                Arc::new(Notify::new())
+            }
+        }
+    }
+
+    fn wake(&self, checked_name: &str) {
+        match self.waiters.lock() {
+            Ok(guard) => match guard.get(checked_name).and_then(Weak::upgrade) {
+                None => {}
+                Some(n) => n.notify_waiters(),
+            },
+            Err(e) => log::warn!("Unable to lock: {e}"),
+        }
+    }
+
+    fn wake_all(&self) {
+        match self.waiters.lock() {
+            Ok(mut guard) => guard.retain(|_, w| match w.upgrade() {
+                None => false,
+                Some(n) => {
+                    n.notify_waiters();
+                    true
+                }
+            }),
+            Err(e) => log::warn!("Unable to lock: {e}"),
+        }
+    }
+
+    pub fn as_watched(&self) -> usize {
+        match self.waiters.lock() {
+            Ok(guard) => guard.len(),
+            Err(e) => {
+                log::warn!("Unable to lock: {e}");
+                0
+            }
+        }
+    }
+
+    async fn listen<T>(&self, config: &Config, tls: T) -> Result<(), tokio_postgres::Error>
+    where
+        T: MakeTlsConnect<Socket>,
+        T::Stream: Send + 'static,
+    {
+        let (client, mut connection) = config.connect(tls).await?;
+        let mut messages = futures_util::stream::poll_fn(move |cx| connection.poll_message(cx));
+        let query: String = format!("LISTEN {CHANNEL}");
+        let listen = client.batch_execute(&query);
+        tokio::pin!(listen);
+        // the connection must be polled to complete the LISTEN
+        loop {
+            tokio::select! {
+                rslt = &mut listen => { rslt?; break },
+                om = messages.next() => match om {
+                    None => return Ok(()),
+                    Some(m) => { m?; },
+                },
+            }
+        }
+        // notifications sent while (re)connecting may be lost
+        self.wake_all();
+        while let Some(m) = messages.next().await {
+            match m? {
+                AsyncMessage::Notification(n) => self.wake(n.payload()),
+                AsyncMessage::Notice(n) => log::debug!("notice: {n}"),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn listener_new<T>(config: Config, tls: T) -> Arc<Listener>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    T::Stream: Send + 'static,
+    T::TlsConnect: Send,
+    <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let listener = Arc::new(Listener {
+        waiters: Mutex::new(HashMap::new()),
+    });
+    let l: Arc<Listener> = listener.clone();
+    // a dedicated connection(not pooled); waiters keep polling while reconnecting
+    tokio::spawn(async move {
+        loop {
+            match l.listen(&config, tls.clone()).await {
+                Ok(_) => log::warn!("Listener connection closed"),
+                Err(e) => log::warn!("Listener connection error: {e}"),
+            }
+            l.wake_all();
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+        }
+    });
+    listener
+}
@@ -1,3 +1,5 @@
+use std::time::{Duration, SystemTime};
+
 use tonic::{Request, Response, Status};
 
 use deadpool::managed::PoolError;
@@ -7,13 +9,19 @@ use db2q::uuid::Uuid;
 
 use db2q::count::cmd::exact::ExactReq;
 use db2q::count::cmd::fast::FastReq;
+use db2q::count::cmd::lag::LagReq;
 
 use db2q::db2q::proto::queue::v1::cnt_svc::{ExactRequest, ExactResponse};
 use db2q::db2q::proto::queue::v1::cnt_svc::{FastRequest, FastResponse};
+use db2q::db2q::proto::queue::v1::cnt_svc::{LagRequest, LagResponse};
 use db2q::db2q::proto::queue::v1::count_service_server::CountService;
 
+use crate::common::minimal::catalog;
 use crate::common::minimal::topic2table::Topic2Table;
 
+// remaining items are counted up to this; estimated from the keys beyond it
+pub const LAG_EXACT_MAX: i64 = 10000;
+
 pub struct Svc<T> {
     pool: Pool,
     topic2table: T,
@@ -29,7 +37,13 @@ impl<T> Svc<T> {
         }
     }
 
-    pub async fn count<C>(&self, checked_name: &str, client: &C) -> Result<u64, Status>
+    // unset bounds are replaced with the widest ones to keep the key range index scan
+    pub async fn count<C>(
+        &self,
+        checked_name: &str,
+        checked: &ExactReq,
+        client: &C,
+    ) -> Result<u64, Status>
     where
         C: GenericClient,
     {
@@ -38,10 +52,20 @@ impl<T> Svc<T> {
                 SELECT
                     COUNT(*) AS cnt
                 FROM {checked_name}
+                WHERE
+                    key >= COALESCE($1::BIGINT, 0)
+                    AND key < COALESCE($2::BIGINT, 9223372036854775807)
+                    AND pushed >= COALESCE($3::TIMESTAMPTZ, '-infinity')
+                    AND pushed < COALESCE($4::TIMESTAMPTZ, 'infinity')
+                    AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
             "#
         );
+        let lower: Option<i64> = checked.as_lower().map(|u| u as i64);
+        let upper: Option<i64> = checked.as_upper().map(|u| u as i64);
+        let since: Option<SystemTime> = checked.as_since();
+        let until: Option<SystemTime> = checked.as_until();
         let row = client
-            .query_one(&query, &[])
+            .query_one(&query, &[&lower, &upper, &since, &until])
             .await
             .map_err(|e| match e.is_closed() {
                 true => Status::unavailable(format!("connection closed: {e}")),
@@ -83,6 +107,86 @@ impl<T> Svc<T> {
             ))),
         }
     }
+
+    // (remaining, age of the oldest remaining, newest key)
+    // all of the subqueries are bounded range scans on the primary key
+    pub async fn lag<C>(
+        &self,
+        checked_name: &str,
+        prev: i64,
+        client: &C,
+    ) -> Result<(u64, Option<Duration>, i64), Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                WITH oldest AS (
+                    SELECT
+                        key,
+                        EXTRACT(
+                            EPOCH FROM (CLOCK_TIMESTAMP() - pushed)
+                        )::DOUBLE PRECISION AS age
+                    FROM {checked_name}
+                    WHERE
+                        key > $1::BIGINT
+                        AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                    ORDER BY key
+                    LIMIT 1
+                )
+                SELECT
+                    (
+                        SELECT COUNT(*)
+                        FROM (
+                            SELECT key
+                            FROM {checked_name}
+                            WHERE
+                                key > $1::BIGINT
+                                AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                            ORDER BY key
+                            LIMIT $2::BIGINT
+                        ) AS bounded
+                    ) AS remaining,
+                    (SELECT age FROM oldest) AS oldest_age,
+                    (SELECT key FROM oldest) AS oldest_key,
+                    COALESCE(
+                        (
+                            SELECT key
+                            FROM {checked_name}
+                            ORDER BY key DESC
+                            LIMIT 1
+                        ),
+                        -1
+                    )::BIGINT AS newest
+            "#
+        );
+        let row = client
+            .query_one(&query, &[&prev, &LAG_EXACT_MAX])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to get a lag: {e}")),
+            })?;
+        let remaining: i64 = row
+            .try_get(0)
+            .map_err(|e| Status::internal(format!("No column got: {e}")))?;
+        let oldest_age: Option<f64> = row
+            .try_get(1)
+            .map_err(|e| Status::internal(format!("No column got: {e}")))?;
+        let oldest_key: Option<i64> = row
+            .try_get(2)
+            .map_err(|e| Status::internal(format!("No column got: {e}")))?;
+        let newest: i64 = row
+            .try_get(3)
+            .map_err(|e| Status::internal(format!("No column got: {e}")))?;
+        let age: Option<Duration> = oldest_age.map(|f| Duration::from_secs_f64(f.max(0.0)));
+        // the key range is an upper bound(keys may have gaps)
+        let remaining: i64 = match (remaining < LAG_EXACT_MAX, oldest_key) {
+            (false, Some(oldest)) => (newest - oldest + 1).max(remaining),
+            _ => remaining,
+        };
+        Ok((remaining as u64, age, newest))
+    }
 }
 
 #[tonic::async_trait]
@@ -96,7 +200,7 @@ where
         let topic_id: Uuid = checked.as_topic();
         let name: String = self.topic2table.id2name(topic_id);
         let client: Client = self.get_client().await?;
-        let cnt: u64 = self.count(name.as_str(), &client).await?;
+        let cnt: u64 = self.count(name.as_str(), &checked, &client).await?;
         let reply = ExactResponse { count: cnt };
         Ok(Response::new(reply))
     }
@@ -113,6 +217,26 @@ where
         };
         Ok(Response::new(reply))
     }
+
+    async fn lag(&self, req: Request<LagRequest>) -> Result<Response<LagResponse>, Status> {
+        let lr: LagRequest = req.into_inner();
+        let checked: LagReq = (&lr).try_into()?;
+        let topic_id: Uuid = checked.as_topic();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        // a committed group offset overrides the previous key
+        let prev: Option<i64> = match checked.as_group() {
+            None => checked.as_previous_key().map(|u| u as i64),
+            Some(group) => catalog::offset(name.as_str(), group, &client).await?,
+        };
+        let (remaining, age, newest) = self.lag(name.as_str(), prev.unwrap_or(-1), &client).await?;
+        let reply = LagResponse {
+            remaining,
+            oldest_age: age.and_then(|d| d.try_into().ok()),
+            newest,
+        };
+        Ok(Response::new(reply))
+    }
 }
 
 pub fn count_svc_new<T>(pool: &Pool, topic2table: T) -> impl CountService
@@ -0,0 +1,263 @@
+use std::time::SystemTime;
+
+use tonic::{Request, Response, Status};
+
+use deadpool::managed::PoolError;
+use deadpool_postgres::tokio_postgres;
+use deadpool_postgres::{Client, GenericClient, Pool};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::Row;
+
+use db2q::uuid::Uuid;
+
+use db2q::group::cmd::commit::CommitOffsetReq;
+use db2q::group::cmd::delete::DeleteGroupReq;
+use db2q::group::cmd::get::GetOffsetReq;
+use db2q::group::cmd::list::ListGroupsReq;
+
+use db2q::db2q::proto::queue::v1::group_service_server::GroupService;
+use db2q::db2q::proto::queue::v1::grp_svc::{CommitOffsetRequest, CommitOffsetResponse};
+use db2q::db2q::proto::queue::v1::grp_svc::{DeleteGroupRequest, DeleteGroupResponse};
+use db2q::db2q::proto::queue::v1::grp_svc::{GetOffsetRequest, GetOffsetResponse};
+use db2q::db2q::proto::queue::v1::grp_svc::{ListGroupsRequest, ListGroupsResponse};
+
+use crate::common::minimal::catalog::CONSUMER_OFFSET;
+use crate::common::minimal::topic2table::Topic2Table;
+
+pub struct Svc<T> {
+    pool: Pool,
+    topic2table: T,
+}
+
+impl<T> Svc<T> {
+    async fn get_client(&self) -> Result<Client, Status> {
+        match self.pool.get().await {
+            Ok(client) => Ok(client),
+            Err(PoolError::Timeout(t)) => Err(Status::unavailable(format!("timeout: {t:#?}"))),
+            Err(PoolError::Closed) => Err(Status::failed_precondition("All connection closed")),
+            Err(e) => Err(Status::internal(format!("Unexpected error: {e}"))),
+        }
+    }
+
+    async fn commit<C>(
+        &self,
+        checked_name: &str,
+        group: &str,
+        offset: i64,
+        client: &C,
+    ) -> Result<SystemTime, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                INSERT INTO {CONSUMER_OFFSET} (
+                    name,
+                    grp,
+                    key
+                )
+                VALUES (
+                    $1::TEXT,
+                    $2::TEXT,
+                    $3::BIGINT
+                )
+                ON CONFLICT (name, grp) DO UPDATE
+                SET
+                    key = EXCLUDED.key,
+                    updated = CLOCK_TIMESTAMP()
+                RETURNING updated
+            "#
+        );
+        let row: Row = client
+            .query_one(&query, &[&checked_name, &group, &offset])
+            .await
+            .map_err(|e| match (e.is_closed(), e.code()) {
+                (true, _) => Status::unavailable(format!("connection closed: {e}")),
+                (_, Some(&SqlState::FOREIGN_KEY_VIOLATION)) => {
+                    Status::not_found(format!("No such topic: {e}"))
+                }
+                _ => Status::internal(format!("Unable to commit an offset: {e}")),
+            })?;
+        row.try_get(0)
+            .map_err(|e| Status::internal(format!("Unable to get a timestamp: {e}")))
+    }
+
+    async fn get<C>(
+        &self,
+        checked_name: &str,
+        group: &str,
+        client: &C,
+    ) -> Result<(i64, SystemTime), Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    key::BIGINT,
+                    updated
+                FROM {CONSUMER_OFFSET}
+                WHERE
+                    name = $1::TEXT
+                    AND grp = $2::TEXT
+            "#
+        );
+        let row: Row = client
+            .query_opt(&query, &[&checked_name, &group])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to get an offset: {e}")),
+            })?
+            .ok_or_else(|| Status::not_found(format!("No offset committed: {group}")))?;
+        let key: i64 = row
+            .try_get(0)
+            .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))?;
+        let updated: SystemTime = row
+            .try_get(1)
+            .map_err(|e| Status::internal(format!("Unable to get a timestamp: {e}")))?;
+        Ok((key, updated))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn list<C>(&self, checked_name: &str, client: &C) -> Result<Vec<String>, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                SELECT grp::TEXT
+                FROM {CONSUMER_OFFSET}
+                WHERE name = $1::TEXT
+                ORDER BY grp
+            "#
+        );
+        let rows: Vec<Row> =
+            client
+                .query(&query, &[&checked_name])
+                .await
+                .map_err(|e| match e.is_closed() {
+                    true => Status::unavailable(format!("connection closed: {e}")),
+                    _ => Status::internal(format!("Unable to get groups: {e}")),
+                })?;
+        rows.iter()
+            .map(|row: &Row| {
+                row.try_get(0)
+                    .map_err(|e| Status::internal(format!("Unable to get a group: {e}")))
+            })
+            .collect()
+    }
+
+    async fn delete<C>(&self, checked_name: &str, group: &str, client: &C) -> Result<u64, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                DELETE FROM {CONSUMER_OFFSET}
+                WHERE
+                    name = $1::TEXT
+                    AND grp = $2::TEXT
+            "#
+        );
+        client
+            .execute(&query, &[&checked_name, &group])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to delete a group: {e}")),
+            })
+    }
+}
+
+#[tonic::async_trait]
+impl<T> GroupService for Svc<T>
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    async fn commit_offset(
+        &self,
+        req: Request<CommitOffsetRequest>,
+    ) -> Result<Response<CommitOffsetResponse>, Status> {
+        let cr: CommitOffsetRequest = req.into_inner();
+        let checked: CommitOffsetReq = (&cr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let offset: i64 = checked.as_offset() as i64;
+        let client: Client = self.get_client().await?;
+        let committed: SystemTime = self
+            .commit(name.as_str(), checked.as_group(), offset, &client)
+            .await?;
+        let reply = CommitOffsetResponse {
+            committed: Some(committed.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn get_offset(
+        &self,
+        req: Request<GetOffsetRequest>,
+    ) -> Result<Response<GetOffsetResponse>, Status> {
+        let gr: GetOffsetRequest = req.into_inner();
+        let checked: GetOffsetReq = (&gr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let (offset, committed) = self.get(name.as_str(), checked.as_group(), &client).await?;
+        let reply = GetOffsetResponse {
+            offset,
+            committed: Some(committed.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn list_groups(
+        &self,
+        req: Request<ListGroupsRequest>,
+    ) -> Result<Response<ListGroupsResponse>, Status> {
+        let lr: ListGroupsRequest = req.into_inner();
+        let checked: ListGroupsReq = (&lr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let groups: Vec<String> = self.list(name.as_str(), &client).await?;
+        let reply = ListGroupsResponse { groups };
+        Ok(Response::new(reply))
+    }
+
+    async fn delete_group(
+        &self,
+        req: Request<DeleteGroupRequest>,
+    ) -> Result<Response<DeleteGroupResponse>, Status> {
+        let dr: DeleteGroupRequest = req.into_inner();
+        let checked: DeleteGroupReq = (&dr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let deleted: u64 = self
+            .delete(name.as_str(), checked.as_group(), &client)
+            .await?;
+        match deleted {
+            0 => Err(Status::not_found(format!(
+                "No such group: {}",
+                checked.as_group()
+            ))),
+            _ => Ok(()),
+        }?;
+        let deleted: SystemTime = SystemTime::now();
+        let reply = DeleteGroupResponse {
+            deleted: Some(deleted.into()),
+        };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn group_svc_new<T>(pool: &Pool, topic2table: T) -> impl GroupService
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    Svc {
+        pool: pool.clone(),
+        topic2table,
+    }
+}
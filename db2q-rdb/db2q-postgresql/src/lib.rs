@@ -2,7 +2,9 @@ pub mod common;
 pub mod topic;
 
 pub mod count;
+pub mod group;
 pub mod queue;
+pub mod sweep;
 
 pub use deadpool_postgres;
 pub use tonic;
@@ -10,5 +12,6 @@ pub use tonic;
 pub use db2q;
 
 pub use db2q::db2q::proto::queue::v1::count_service_server;
+pub use db2q::db2q::proto::queue::v1::group_service_server;
 pub use db2q::db2q::proto::queue::v1::queue_service_server;
 pub use db2q::db2q::proto::queue::v1::topic_service_server;
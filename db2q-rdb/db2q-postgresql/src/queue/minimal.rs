@@ -1,2 +1,3 @@
+pub mod filter;
 pub mod svc;
 pub use crate::common::minimal::topic2table;
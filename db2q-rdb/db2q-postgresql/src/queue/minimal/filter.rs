@@ -0,0 +1,50 @@
+use deadpool_postgres::tokio_postgres;
+use tokio_postgres::types::ToSql;
+
+use db2q::queue::cmd::filter::{Condition, Filter};
+
+pub type Param = Box<dyn ToSql + Sync + Send>;
+
+// header value or NULL(never matches) if missing
+fn header(n: usize) -> String {
+    format!("hdr_vals[ARRAY_POSITION(hdr_keys, ${n}::TEXT)]")
+}
+
+fn prefixed(column: &str, n: usize) -> String {
+    format!("SUBSTRING({column} FROM 1 FOR OCTET_LENGTH(${n}::BYTEA)) = ${n}::BYTEA")
+}
+
+// AND-ed predicate using placeholders from $first
+pub fn filter2sql(filter: Option<&Filter>, first: usize) -> (String, Vec<Param>) {
+    let mut params: Vec<Param> = Vec::new();
+    let mut preds: Vec<String> = Vec::new();
+    let conditions: &[Condition] = filter.map(|f| f.as_conditions()).unwrap_or_default();
+    for c in conditions {
+        let n: usize = first + params.len();
+        match c {
+            Condition::HeaderEq { name, value } => {
+                preds.push(format!("{} = ${}::BYTEA", header(n), n + 1));
+                params.push(Box::new(name.clone()));
+                params.push(Box::new(value.clone()));
+            }
+            Condition::HeaderPrefix { name, prefix } => {
+                preds.push(prefixed(&header(n), n + 1));
+                params.push(Box::new(name.clone()));
+                params.push(Box::new(prefix.clone()));
+            }
+            Condition::HeaderIn { name, values } => {
+                preds.push(format!("{} = ANY(${}::BYTEA[])", header(n), n + 1));
+                params.push(Box::new(name.clone()));
+                params.push(Box::new(values.clone()));
+            }
+            Condition::ValuePrefix(prefix) => {
+                preds.push(prefixed("val", n));
+                params.push(Box::new(prefix.clone()));
+            }
+        }
+    }
+    match preds.is_empty() {
+        true => ("TRUE".into(), params),
+        false => (preds.join(" AND "), params),
+    }
+}
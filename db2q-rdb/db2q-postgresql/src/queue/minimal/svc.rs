@@ -1,39 +1,109 @@
+use core::pin::Pin;
 use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::{Instant, SystemTime};
-use tokio::sync::mpsc;
-use tokio::time::Interval;
+use tokio::sync::futures::Notified;
+use tokio::sync::{mpsc, Notify};
 
-use futures_util::{StreamExt, TryStreamExt};
+use futures_util::StreamExt;
 
 use tokio_stream::wrappers::ReceiverStream;
 
-use tonic::{Code, Request, Response, Status};
+use tonic::{Code, Request, Response, Status, Streaming};
 
 use deadpool::managed::PoolError;
 use deadpool_postgres::tokio_postgres;
-use deadpool_postgres::{Client, GenericClient, Pool};
+use deadpool_postgres::{Client, GenericClient, Pool, Transaction};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::ToSql;
 use tokio_postgres::{Row, RowStream};
 
+use db2q::queue::cmd::ack::AckReq;
 use db2q::queue::cmd::count::CountReq;
+use db2q::queue::cmd::extend_lease::ExtendLeaseReq;
+use db2q::queue::cmd::filter::Filter;
 use db2q::queue::cmd::keys::KeysReq;
+use db2q::queue::cmd::lease::LeaseReq;
+use db2q::queue::cmd::nack::NackReq;
 use db2q::queue::cmd::next::NextReq;
-use db2q::queue::cmd::push::PushBackReq;
+use db2q::queue::cmd::pop::PopFrontReq;
+use db2q::queue::cmd::push::{PushBackReq, DEDUP_WINDOW};
+use db2q::queue::cmd::push_batch::PushBatchReq;
+use db2q::queue::cmd::redrive::RedriveReq;
+use db2q::queue::cmd::report_failure::ReportFailureReq;
+use db2q::queue::cmd::subscribe::SubscribeReq;
 use db2q::queue::cmd::wait_next::WaitNextReq;
 use db2q::uuid::Uuid;
 
+use db2q::db2q::proto::queue::v1::q_svc::{AckRequest, AckResponse};
 use db2q::db2q::proto::queue::v1::q_svc::{CountRequest, CountResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{ExtendLeaseRequest, ExtendLeaseResponse};
 use db2q::db2q::proto::queue::v1::q_svc::{KeysRequest, KeysResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{LeaseRequest, LeaseResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{NackRequest, NackResponse};
 use db2q::db2q::proto::queue::v1::q_svc::{NextRequest, NextResponse};
 use db2q::db2q::proto::queue::v1::q_svc::{PopFrontRequest, PopFrontResponse};
 use db2q::db2q::proto::queue::v1::q_svc::{PushBackRequest, PushBackResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{PushBatchRequest, PushBatchResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{RedriveRequest, RedriveResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{ReportFailureRequest, ReportFailureResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{SubscribeRequest, SubscribeResponse};
 use db2q::db2q::proto::queue::v1::q_svc::{WaitNextRequest, WaitNextResponse};
 use db2q::db2q::proto::queue::v1::queue_service_server::QueueService;
 
+use super::filter::filter2sql;
 use super::topic2table::Topic2Table;
 
+use crate::common::minimal::catalog::{self, PUSH_DEDUP, TOPIC_CONFIG};
+use crate::common::minimal::listener::{Listener, FALLBACK_INTERVAL_MINIMUM};
+
+// a visible message(key, priority, val, hdr_keys, hdr_vals, attempts, last_error columns)
+struct Item {
+    key: i64,
+    priority: i32,
+    val: Vec<u8>,
+    headers: HashMap<String, Vec<u8>>,
+    attempts: i64,
+    last_error: Option<String>,
+}
+
+impl Item {
+    fn from_row(row: &Row) -> Result<Self, tokio_postgres::Error> {
+        let hdr_keys: Vec<String> = row.try_get(3)?;
+        let hdr_vals: Vec<Vec<u8>> = row.try_get(4)?;
+        Ok(Self {
+            key: row.try_get(0)?,
+            priority: row.try_get(1)?,
+            val: row.try_get(2)?,
+            headers: hdr_keys.into_iter().zip(hdr_vals).collect(),
+            attempts: row.try_get(5)?,
+            last_error: row.try_get(6)?,
+        })
+    }
+}
+
+impl From<Item> for NextResponse {
+    fn from(i: Item) -> Self {
+        Self {
+            next: i.key,
+            value: i.val,
+            priority: i.priority,
+            headers: i.headers,
+            attempts: i.attempts as u64,
+            last_error: i.last_error.unwrap_or_default(),
+        }
+    }
+}
+
+// cleared when full
+pub const SAFE_TAIL_CACHE_MAX: usize = 1024;
+
 pub struct Svc<T> {
     pool: Pool,
     topic2table: T,
+    listener: Option<Arc<Listener>>,
+    safe_tail: Mutex<HashMap<String, bool>>,
 }
 
 impl<T> Svc<T>
@@ -53,25 +123,297 @@ where
         }
     }
 
-    async fn push<C>(&self, checked_name: &str, client: &C, val: &[u8]) -> Result<u64, Status>
+    fn watch(&self, checked_name: &str, interval: Duration) -> (Option<Arc<Notify>>, Duration) {
+        match &self.listener {
+            None => (None, interval),
+            Some(l) => (
+                Some(l.watch(checked_name)),
+                interval.max(FALLBACK_INTERVAL_MINIMUM),
+            ),
+        }
+    }
+
+    // fixed on create; cached per topic
+    async fn is_safe_tail<C>(&self, checked_name: &str, client: &C) -> Result<bool, Status>
     where
         C: GenericClient,
     {
+        let cached: Option<bool> = match self.safe_tail.lock() {
+            Ok(guard) => guard.get(checked_name).copied(),
+            Err(e) => {
+                log::warn!("Unable to lock: {e}");
+                None
+            }
+        };
+        if let Some(safe_tail) = cached {
+            return Ok(safe_tail);
+        }
+        let safe_tail: bool = catalog::safe_tail(checked_name, client).await?;
+        match self.safe_tail.lock() {
+            Ok(mut guard) => {
+                if SAFE_TAIL_CACHE_MAX <= guard.len() {
+                    guard.clear();
+                }
+                guard.insert(checked_name.into(), safe_tail);
+            }
+            Err(e) => log::warn!("Unable to lock: {e}"),
+        }
+        Ok(safe_tail)
+    }
+
+    // a plain insert unless the topic is safe tail
+    async fn push_tail(
+        &self,
+        checked_name: &str,
+        client: &mut Client,
+        checked: &PushBackReq,
+    ) -> Result<(i64, SystemTime), Status> {
+        match self.is_safe_tail(checked_name, client).await? {
+            false => self.push(checked_name, client, checked).await,
+            true => {
+                let tx: Transaction = client.transaction().await.map_err(|e| {
+                    Status::unavailable(format!("Unable to start a transaction: {e}"))
+                })?;
+                catalog::tail_lock(checked_name, &tx).await?;
+                let pushed = self.push(checked_name, &tx, checked).await?;
+                tx.commit()
+                    .await
+                    .map_err(|e| Status::internal(format!("Unable to commit: {e}")))?;
+                Ok(pushed)
+            }
+        }
+    }
+
+    async fn push_batch_tail(
+        &self,
+        checked_name: &str,
+        client: &mut Client,
+        vals: &[Vec<u8>],
+    ) -> Result<(Vec<i64>, Option<SystemTime>), Status> {
+        match self.is_safe_tail(checked_name, client).await? {
+            false => self.push_batch(checked_name, client, vals).await,
+            true => {
+                let tx: Transaction = client.transaction().await.map_err(|e| {
+                    Status::unavailable(format!("Unable to start a transaction: {e}"))
+                })?;
+                catalog::tail_lock(checked_name, &tx).await?;
+                let pushed = self.push_batch(checked_name, &tx, vals).await?;
+                tx.commit()
+                    .await
+                    .map_err(|e| Status::internal(format!("Unable to commit: {e}")))?;
+                Ok(pushed)
+            }
+        }
+    }
+
+    async fn pushed_or_elapsed(notified: Option<Pin<&mut Notified<'_>>>, interval: Duration) {
+        match notified {
+            None => tokio::time::sleep(interval).await,
+            Some(n) => match tokio::time::timeout(interval, n).await {
+                Ok(_) => {}
+                Err(_) => log::debug!("no notification. polling..."),
+            },
+        }
+    }
+
+    async fn push<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        checked: &PushBackReq,
+    ) -> Result<(i64, SystemTime), Status>
+    where
+        C: GenericClient,
+    {
+        let val: &[u8] = checked.as_value();
+        let request_id: Uuid = checked.as_request_id();
+        let not_before: Option<SystemTime> = checked.as_not_before();
+        // the request id is claimed first; reused if older than the window
         let query = format!(
             r#"
+                WITH claimed AS (
+                    INSERT INTO {PUSH_DEDUP} AS d (
+                        name,
+                        req_id,
+                        key,
+                        pushed
+                    )
+                    VALUES (
+                        $6::TEXT,
+                        $2::TEXT::UUID,
+                        NEXTVAL(PG_GET_SERIAL_SEQUENCE($6::TEXT, 'key')),
+                        CLOCK_TIMESTAMP()
+                    )
+                    ON CONFLICT (name, req_id) DO UPDATE
+                    SET
+                        key = EXCLUDED.key,
+                        pushed = EXCLUDED.pushed
+                    WHERE d.pushed < CLOCK_TIMESTAMP() - $10::BIGINT * INTERVAL '1 microsecond'
+                    RETURNING
+                        key,
+                        pushed
+                )
                 INSERT INTO {checked_name} (
-                    val
+                    key,
+                    pushed,
+                    val,
+                    visible_at,
+                    expires_at,
+                    priority,
+                    hdr_keys,
+                    hdr_vals
                 )
-                VALUES ($1::BYTEA)
+                SELECT
+                    claimed.key,
+                    claimed.pushed,
+                    $1::BYTEA,
+                    COALESCE(
+                        $3::TIMESTAMPTZ,
+                        CLOCK_TIMESTAMP() + COALESCE($4::BIGINT, 0) * INTERVAL '1 microsecond'
+                    ),
+                    CLOCK_TIMESTAMP() + COALESCE(
+                        $5::BIGINT * INTERVAL '1 microsecond',
+                        (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = $6::TEXT)
+                    ),
+                    $7::INTEGER,
+                    $8::TEXT[],
+                    $9::BYTEA[]
+                FROM claimed
+                RETURNING
+                    key::BIGINT,
+                    pushed
+            "#
+        );
+        let window_us: i64 = DEDUP_WINDOW.as_micros().min(i64::MAX as u128) as i64;
+        let delay_us: Option<i64> = checked
+            .as_delay()
+            .map(|d| d.as_micros().min(i64::MAX as u128) as i64);
+        let (hdr_keys, hdr_vals): (Vec<&str>, Vec<&[u8]>) = checked
+            .as_headers()
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_slice()))
+            .unzip();
+        let ttl_us: Option<i64> = checked
+            .as_ttl()
+            .map(|d| d.as_micros().min(i64::MAX as u128) as i64);
+        let oinserted = client
+            .query_opt(
+                &query,
+                &[
+                    &val,
+                    &request_id.to_string(),
+                    &not_before,
+                    &delay_us,
+                    &ttl_us,
+                    &checked_name,
+                    &checked.as_priority(),
+                    &hdr_keys,
+                    &hdr_vals,
+                    &window_us,
+                ],
+            )
+            .await
+            .map_err(|e| match (e.is_closed(), e.code()) {
+                (true, _) => Status::unavailable(format!("connection closed: {e}")),
+                (_, Some(&SqlState::UNDEFINED_TABLE)) => {
+                    Status::not_found(format!("No such topic: {e}"))
+                }
+                _ => Status::internal(format!("Unable to insert: {e}")),
+            })?;
+        let row: Row = match oinserted {
+            Some(row) => row,
+            None => self.pushed(checked_name, client, request_id).await?,
+        };
+        let key: i64 = row
+            .try_get(0)
+            .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))?;
+        let pushed: SystemTime = row
+            .try_get(1)
+            .map_err(|e| Status::internal(format!("Unable to get a timestamp: {e}")))?;
+        Ok((key, pushed))
+    }
+
+    // the original push of a duplicated request id
+    async fn pushed<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        request_id: Uuid,
+    ) -> Result<Row, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    key::BIGINT,
+                    pushed
+                FROM {PUSH_DEDUP}
+                WHERE
+                    name = $1::TEXT
+                    AND req_id = $2::TEXT::UUID
             "#
         );
         client
-            .execute(&query, &[&val])
+            .query_opt(&query, &[&checked_name, &request_id.to_string()])
             .await
             .map_err(|e| match e.is_closed() {
                 true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to select: {e}")),
+            })?
+            .ok_or_else(|| Status::not_found(format!("No such topic: {checked_name}")))
+    }
+
+    async fn push_batch<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        vals: &[Vec<u8>],
+    ) -> Result<(Vec<i64>, Option<SystemTime>), Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                INSERT INTO {checked_name} (
+                    val,
+                    expires_at
+                )
+                SELECT
+                    v,
+                    CLOCK_TIMESTAMP() + (
+                        SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = $2::TEXT
+                    )
+                FROM UNNEST($1::BYTEA[]) WITH ORDINALITY AS u(v, i)
+                ORDER BY i
+                RETURNING
+                    key::BIGINT,
+                    pushed
+            "#
+        );
+        let rows: Vec<Row> = client
+            .query(&query, &[&vals, &checked_name])
+            .await
+            .map_err(|e| match (e.is_closed(), e.code()) {
+                (true, _) => Status::unavailable(format!("connection closed: {e}")),
+                (_, Some(&SqlState::UNDEFINED_TABLE)) => {
+                    Status::not_found(format!("No such topic: {e}"))
+                }
                 _ => Status::internal(format!("Unable to insert: {e}")),
-            })
+            })?;
+        let mut keys: Vec<i64> = rows
+            .iter()
+            .map(|row: &Row| row.try_get(0))
+            .collect::<Result<_, _>>()
+            .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))?;
+        // keys are assigned in the order of the values
+        keys.sort_unstable();
+        let pushed: Vec<SystemTime> = rows
+            .iter()
+            .map(|row: &Row| row.try_get(1))
+            .collect::<Result<_, _>>()
+            .map_err(|e| Status::internal(format!("Unable to get a timestamp: {e}")))?;
+        Ok((keys, pushed.into_iter().max()))
     }
 
     async fn count<C>(&self, checked_name: &str, client: &C) -> Result<u64, Status>
@@ -83,6 +425,7 @@ where
                 SELECT
                     COUNT(*) AS cnt
                 FROM {checked_name}
+                WHERE expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP()
             "#
         );
         let row = client
@@ -98,23 +441,40 @@ where
         Ok(cnt as u64)
     }
 
-    async fn next<C>(checked_name: &str, prev: i64, client: &C) -> Result<(i64, Vec<u8>), Status>
+    async fn next<C>(
+        checked_name: &str,
+        prev: i64,
+        filter: Option<&Filter>,
+        client: &C,
+    ) -> Result<Item, Status>
     where
         C: GenericClient,
     {
+        let (cond, fparams) = filter2sql(filter, 2);
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&prev];
+        params.extend(fparams.iter().map(|p| p.as_ref() as &(dyn ToSql + Sync)));
         let query = format!(
             r#"
                 SELECT
                     key::BIGINT,
-                    val::BYTEA
+                    priority::INTEGER,
+                    val::BYTEA,
+                    hdr_keys::TEXT[],
+                    hdr_vals::BYTEA[],
+                    attempts::BIGINT,
+                    last_error::TEXT
                 FROM {checked_name}
-                WHERE key > $1::BIGINT
+                WHERE
+                    key > $1::BIGINT
+                    AND visible_at <= CLOCK_TIMESTAMP()
+                    AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                    AND {cond}
                 ORDER BY key
                 LIMIT 1
             "#
         );
         let row = client
-            .query_opt(&query, &[&prev])
+            .query_opt(&query, &params)
             .await
             .map_err(|e| match e.is_closed() {
                 true => Status::unavailable(format!("connection closed: {e}")),
@@ -123,13 +483,57 @@ where
             .ok_or_else(|| {
                 Status::not_found(format!("No more queue items. previous key: {prev}"))
             })?;
-        let next_key: i64 = row
-            .try_get(0)
-            .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))?;
-        let next_val: Vec<u8> = row
-            .try_get(1)
-            .map_err(|e| Status::internal(format!("Unable to get a value: {e}")))?;
-        Ok((next_key, next_val))
+        Item::from_row(&row).map_err(|e| Status::internal(format!("Unable to get an item: {e}")))
+    }
+
+    // (priority desc, key) order
+    async fn next_prioritized<C>(
+        checked_name: &str,
+        prev: Option<(i32, i64)>,
+        filter: Option<&Filter>,
+        client: &C,
+    ) -> Result<Item, Status>
+    where
+        C: GenericClient,
+    {
+        let (cond, fparams) = filter2sql(filter, 3);
+        let query = format!(
+            r#"
+                SELECT
+                    key::BIGINT,
+                    priority::INTEGER,
+                    val::BYTEA,
+                    hdr_keys::TEXT[],
+                    hdr_vals::BYTEA[],
+                    attempts::BIGINT,
+                    last_error::TEXT
+                FROM {checked_name}
+                WHERE
+                    (
+                        $1::INTEGER IS NULL
+                        OR priority < $1::INTEGER
+                        OR (priority = $1::INTEGER AND key > $2::BIGINT)
+                    )
+                    AND visible_at <= CLOCK_TIMESTAMP()
+                    AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                    AND {cond}
+                ORDER BY priority DESC, key
+                LIMIT 1
+            "#
+        );
+        let prev_priority: Option<i32> = prev.map(|p| p.0);
+        let prev_key: Option<i64> = prev.map(|p| p.1);
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&prev_priority, &prev_key];
+        params.extend(fparams.iter().map(|p| p.as_ref() as &(dyn ToSql + Sync)));
+        let row = client
+            .query_opt(&query, &params)
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to select: {e}")),
+            })?
+            .ok_or_else(|| Status::not_found(format!("No more queue items. previous: {prev:?}")))?;
+        Item::from_row(&row).map_err(|e| Status::internal(format!("Unable to get an item: {e}")))
     }
 
     pub async fn wait_next(
@@ -137,154 +541,685 @@ where
         checked_name: &str,
         req: WaitNextReq,
     ) -> Result<ReceiverStream<Result<WaitNextResponse, Status>>, Status> {
-        let prev: i64 = req.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let prev: i64 = match req.as_group() {
+            None => req.as_previous_key().map(|u| u as i64),
+            Some(group) => {
+                let client: Client = self.get_client().await?;
+                catalog::offset(checked_name, group, &client).await?
+            }
+        }
+        .unwrap_or(-1);
         let start: Instant = Instant::now();
-        let mut i: Interval = tokio::time::interval(req.as_interval());
+        let (notify, interval) = self.watch(checked_name, req.as_interval());
         let (tx, rx) = mpsc::channel(1);
         let name: String = checked_name.into();
         let pool: Pool = self.pool.clone();
         let timeout: Duration = req.as_timeout();
+        let filter: Option<Filter> = req.as_filter().cloned();
         tokio::spawn(async move {
             let mut retry_cnt: u64 = 0;
-            match Self::pool2client(&pool).await {
-                Ok(client) => loop {
-                    let check: Duration = start.elapsed();
-                    match check < timeout {
-                        true => {}
-                        false => {
-                            let e = Status::deadline_exceeded(format!(
-                                "timeout. table={name}, retried={retry_cnt}"
-                            ));
+            loop {
+                // registered before checking to avoid missing a notification
+                let notified = notify.as_deref().map(Notify::notified);
+                tokio::pin!(notified);
+                if let Some(n) = notified.as_mut().as_pin_mut() {
+                    n.enable();
+                }
+                // the client is released while waiting
+                let rslt: Result<Item, Status> = match Self::pool2client(&pool).await {
+                    Ok(client) => Self::next(name.as_str(), prev, filter.as_ref(), &client).await,
+                    Err(e) => Err(e),
+                };
+                match rslt {
+                    Ok(item) => {
+                        let elapsed: Duration = start.elapsed();
+                        let reply = WaitNextResponse {
+                            next: Some(item.into()),
+                            elapsed: elapsed.try_into().ok(),
+                            retried: retry_cnt,
+                        };
+                        match tx.send(Ok(reply)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        };
+                        return;
+                    }
+                    Err(e) => match e.code() {
+                        Code::NotFound => {}
+                        _ => {
                             match tx.send(Err(e)).await {
                                 Ok(_) => {}
                                 Err(e) => log::warn!("Unable to send: {e}"),
                             }
                             return;
                         }
+                    },
+                }
+                let remaining: Duration = timeout.saturating_sub(start.elapsed());
+                if remaining.is_zero() {
+                    let e = Status::deadline_exceeded(format!(
+                        "timeout. table={name}, retried={retry_cnt}"
+                    ));
+                    match tx.send(Err(e)).await {
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Unable to send: {e}"),
                     }
-                    i.tick().await; // 1st tick has 0 latency
-                    match Self::next(name.as_str(), prev, &client).await {
-                        Ok(t) => {
-                            let elapsed: Duration = start.elapsed();
-                            let (i, v) = t;
-                            let reply = WaitNextResponse {
-                                next: Some(NextResponse { next: i, value: v }),
-                                elapsed: elapsed.try_into().ok(),
-                                retried: retry_cnt,
-                            };
-                            match tx.send(Ok(reply)).await {
-                                Ok(_) => {}
-                                Err(e) => log::warn!("Unable to send: {e}"),
-                            };
-                            return;
+                    return;
+                }
+                Self::pushed_or_elapsed(notified.as_mut().as_pin_mut(), interval.min(remaining))
+                    .await;
+                retry_cnt += 1;
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+
+    async fn pop<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        priority_order: bool,
+    ) -> Result<Item, Status>
+    where
+        C: GenericClient,
+    {
+        let order: &str = match priority_order {
+            true => "priority DESC, key",
+            false => "key",
+        };
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE key = (
+                    SELECT key
+                    FROM {checked_name}
+                    WHERE
+                        visible_at <= CLOCK_TIMESTAMP()
+                        AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                        AND (leased_until IS NULL OR leased_until <= CLOCK_TIMESTAMP())
+                    ORDER BY {order}
+                    FOR UPDATE SKIP LOCKED
+                    LIMIT 1
+                )
+                RETURNING
+                    key::BIGINT,
+                    priority::INTEGER,
+                    val::BYTEA,
+                    hdr_keys::TEXT[],
+                    hdr_vals::BYTEA[],
+                    attempts::BIGINT,
+                    last_error::TEXT
+            "#
+        );
+        let row = client
+            .query_opt(&query, &[])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to delete: {e}")),
+            })?
+            .ok_or_else(|| Status::not_found("Empty queue"))?;
+        Item::from_row(&row).map_err(|e| Status::internal(format!("Unable to get an item: {e}")))
+    }
+
+    async fn lease<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        lease_id: Uuid,
+        timeout: Duration,
+    ) -> Result<(Item, SystemTime), Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = CLOCK_TIMESTAMP() + $1::BIGINT * INTERVAL '1 microsecond',
+                    lease_id = $2::TEXT::UUID
+                WHERE key = (
+                    SELECT key
+                    FROM {checked_name}
+                    WHERE
+                        visible_at <= CLOCK_TIMESTAMP()
+                        AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                        AND (leased_until IS NULL OR leased_until <= CLOCK_TIMESTAMP())
+                    ORDER BY key
+                    FOR UPDATE SKIP LOCKED
+                    LIMIT 1
+                )
+                RETURNING
+                    key::BIGINT,
+                    priority::INTEGER,
+                    val::BYTEA,
+                    hdr_keys::TEXT[],
+                    hdr_vals::BYTEA[],
+                    attempts::BIGINT,
+                    last_error::TEXT,
+                    leased_until
+            "#
+        );
+        let micros: i64 = timeout
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("visibility timeout too large: {e}")))?;
+        let row = client
+            .query_opt(&query, &[&micros, &lease_id.to_string()])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to lease: {e}")),
+            })?
+            .ok_or_else(|| Status::not_found("No visible queue items"))?;
+        let leased: Item = Item::from_row(&row)
+            .map_err(|e| Status::internal(format!("Unable to get an item: {e}")))?;
+        let deadline: SystemTime = row
+            .try_get(7)
+            .map_err(|e| Status::internal(format!("Unable to get a deadline: {e}")))?;
+        Ok((leased, deadline))
+    }
+
+    async fn ack<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        key: i64,
+        lease_id: Uuid,
+    ) -> Result<(), Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE
+                    key = $1::BIGINT
+                    AND lease_id = $2::TEXT::UUID
+                    AND leased_until > CLOCK_TIMESTAMP()
+            "#
+        );
+        let cnt: u64 = client
+            .execute(&query, &[&key, &lease_id.to_string()])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to ack: {e}")),
+            })?;
+        match cnt {
+            0 => Err(Status::not_found(format!(
+                "No such lease(expired?). key: {key}, lease id: {lease_id}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn nack<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        key: i64,
+        lease_id: Uuid,
+        delay: Option<Duration>,
+    ) -> Result<(), Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = CLOCK_TIMESTAMP() + $3::BIGINT * INTERVAL '1 microsecond',
+                    lease_id = NULL
+                WHERE
+                    key = $1::BIGINT
+                    AND lease_id = $2::TEXT::UUID
+                    AND leased_until > CLOCK_TIMESTAMP()
+            "#
+        );
+        let micros: i64 = delay
+            .unwrap_or_default()
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("delay too large: {e}")))?;
+        let cnt: u64 = client
+            .execute(&query, &[&key, &lease_id.to_string(), &micros])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to nack: {e}")),
+            })?;
+        match cnt {
+            0 => Err(Status::not_found(format!(
+                "No such lease(expired?). key: {key}, lease id: {lease_id}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn extend_lease<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        key: i64,
+        lease_id: Uuid,
+        extension: Duration,
+    ) -> Result<SystemTime, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = CLOCK_TIMESTAMP() + $3::BIGINT * INTERVAL '1 microsecond'
+                WHERE
+                    key = $1::BIGINT
+                    AND lease_id = $2::TEXT::UUID
+                    AND leased_until > CLOCK_TIMESTAMP()
+                RETURNING leased_until
+            "#
+        );
+        let micros: i64 = extension
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("extension too large: {e}")))?;
+        let row = client
+            .query_opt(&query, &[&key, &lease_id.to_string(), &micros])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to extend a lease: {e}")),
+            })?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No such lease(expired?). key: {key}, lease id: {lease_id}"
+                ))
+            })?;
+        let deadline: SystemTime = row
+            .try_get(0)
+            .map_err(|e| Status::internal(format!("Unable to get a deadline: {e}")))?;
+        Ok(deadline)
+    }
+
+    async fn fail<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        key: i64,
+        error: &str,
+    ) -> Result<u64, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    attempts = attempts + 1,
+                    last_error = $2::TEXT
+                WHERE key = $1::BIGINT
+                RETURNING attempts::BIGINT
+            "#
+        );
+        let row = client
+            .query_opt(&query, &[&key, &error])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to record a failure: {e}")),
+            })?
+            .ok_or_else(|| Status::not_found(format!("No such queue item. key: {key}")))?;
+        let attempts: i64 = row
+            .try_get(0)
+            .map_err(|e| Status::internal(format!("Unable to get attempts: {e}")))?;
+        Ok(attempts as u64)
+    }
+
+    async fn dead_letter<C>(
+        &self,
+        checked_name: &str,
+        dlq_name: &str,
+        client: &C,
+        key: i64,
+    ) -> Result<u64, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                WITH moved AS (
+                    DELETE FROM {checked_name}
+                    WHERE key = $1::BIGINT
+                    RETURNING
+                        val,
+                        attempts,
+                        last_error,
+                        priority,
+                        hdr_keys,
+                        hdr_vals
+                )
+                INSERT INTO {dlq_name} (
+                    val,
+                    attempts,
+                    last_error,
+                    priority,
+                    hdr_keys,
+                    hdr_vals,
+                    expires_at
+                )
+                SELECT
+                    val,
+                    attempts,
+                    last_error,
+                    priority,
+                    hdr_keys,
+                    hdr_vals,
+                    CLOCK_TIMESTAMP() + (
+                        SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = $2::TEXT
+                    )
+                FROM moved
+            "#
+        );
+        client
+            .execute(&query, &[&key, &dlq_name])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to move to a dead letter topic: {e}")),
+            })
+    }
+
+    async fn redrive<C>(
+        &self,
+        checked_name: &str,
+        dlq_name: &str,
+        client: &C,
+        limit: Option<i64>,
+    ) -> Result<u64, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                WITH moved AS (
+                    DELETE FROM {dlq_name}
+                    WHERE key IN (
+                        SELECT key
+                        FROM {dlq_name}
+                        ORDER BY key
+                        FOR UPDATE SKIP LOCKED
+                        LIMIT $1::BIGINT
+                    )
+                    RETURNING
+                        key,
+                        val,
+                        priority,
+                        hdr_keys,
+                        hdr_vals
+                )
+                INSERT INTO {checked_name} (
+                    val,
+                    priority,
+                    hdr_keys,
+                    hdr_vals,
+                    expires_at
+                )
+                SELECT
+                    val,
+                    priority,
+                    hdr_keys,
+                    hdr_vals,
+                    CLOCK_TIMESTAMP() + (
+                        SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = $2::TEXT
+                    )
+                FROM moved
+                ORDER BY key
+            "#
+        );
+        client
+            .execute(&query, &[&limit, &checked_name])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to redrive: {e}")),
+            })
+    }
+
+    async fn next_batch<C>(
+        checked_name: &str,
+        prev: i64,
+        limit: i64,
+        client: &C,
+    ) -> Result<Vec<Item>, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    key::BIGINT,
+                    priority::INTEGER,
+                    val::BYTEA,
+                    hdr_keys::TEXT[],
+                    hdr_vals::BYTEA[],
+                    attempts::BIGINT,
+                    last_error::TEXT
+                FROM {checked_name}
+                WHERE
+                    key > $1::BIGINT
+                    AND visible_at <= CLOCK_TIMESTAMP()
+                    AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                ORDER BY key
+                LIMIT $2::BIGINT
+            "#
+        );
+        let rows: Vec<Row> =
+            client
+                .query(&query, &[&prev, &limit])
+                .await
+                .map_err(|e| match e.is_closed() {
+                    true => Status::unavailable(format!("connection closed: {e}")),
+                    _ => Status::internal(format!("Unable to select: {e}")),
+                })?;
+        rows.iter()
+            .map(Item::from_row)
+            .collect::<Result<_, tokio_postgres::Error>>()
+            .map_err(|e| Status::internal(format!("Unable to get an item: {e}")))
+    }
+
+    pub async fn subscribe(
+        &self,
+        checked_name: &str,
+        req: SubscribeReq,
+    ) -> Result<ReceiverStream<Result<SubscribeResponse, Status>>, Status> {
+        let mut prev: i64 = req.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let batch_size: u64 = req.as_batch_size();
+        let limit: i64 = batch_size as i64;
+        let (notify, interval) = self.watch(checked_name, req.as_interval());
+        // bounded: a slow client stops the polling instead of buffering
+        let (tx, rx) = mpsc::channel(batch_size as usize);
+        let name: String = checked_name.into();
+        let pool: Pool = self.pool.clone();
+        tokio::spawn(async move {
+            loop {
+                let notified = notify.as_deref().map(Notify::notified);
+                tokio::pin!(notified);
+                if let Some(n) = notified.as_mut().as_pin_mut() {
+                    n.enable();
+                }
+                // the client is released before sending to avoid holding it while blocked
+                let rslt: Result<Vec<Item>, Status> = match Self::pool2client(&pool).await {
+                    Ok(client) => Self::next_batch(name.as_str(), prev, limit, &client).await,
+                    Err(e) => Err(e),
+                };
+                let items: Vec<Item> = match rslt {
+                    Ok(items) => items,
+                    Err(e) => {
+                        match tx.send(Err(e)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
                         }
-                        Err(e) => match e.code() {
-                            Code::NotFound => {
-                                retry_cnt += 1;
-                                continue;
-                            }
-                            _ => {
-                                match tx.send(Err(e)).await {
-                                    Ok(_) => {}
-                                    Err(e) => log::warn!("Unable to send: {e}"),
-                                }
-                                return;
-                            }
-                        },
+                        return;
                     }
-                },
-                Err(e) => match tx.send(Err(e)).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::warn!("Unable to send: {e}");
+                };
+                if items.is_empty() {
+                    tokio::select! {
+                        _ = Self::pushed_or_elapsed(notified.as_mut().as_pin_mut(), interval) => {},
+                        _ = tx.closed() => { return },
+                    };
+                    continue;
+                }
+                for item in items {
+                    prev = item.key;
+                    let reply = SubscribeResponse {
+                        next: Some(item.into()),
+                    };
+                    match tx.send(Ok(reply)).await {
+                        Ok(_) => {}
+                        Err(_) => return, // cancelled
                     }
-                },
+                }
             }
         });
         Ok(ReceiverStream::new(rx))
     }
 
-    async fn first<C>(&self, checked_name: &str, client: &C) -> Result<(i64, Vec<u8>), Status>
+    async fn first<C>(
+        &self,
+        checked_name: &str,
+        filter: Option<&Filter>,
+        client: &C,
+    ) -> Result<Item, Status>
     where
         C: GenericClient,
     {
+        let (cond, fparams) = filter2sql(filter, 1);
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::new();
+        params.extend(fparams.iter().map(|p| p.as_ref() as &(dyn ToSql + Sync)));
         let query = format!(
             r#"
                 SELECT
                     key::BIGINT,
-                    val::BYTEA
+                    priority::INTEGER,
+                    val::BYTEA,
+                    hdr_keys::TEXT[],
+                    hdr_vals::BYTEA[],
+                    attempts::BIGINT,
+                    last_error::TEXT
                 FROM {checked_name}
+                WHERE
+                    visible_at <= CLOCK_TIMESTAMP()
+                    AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                    AND {cond}
                 ORDER BY key
                 LIMIT 1
             "#
         );
         let row = client
-            .query_opt(&query, &[])
+            .query_opt(&query, &params)
             .await
             .map_err(|e| match e.is_closed() {
                 true => Status::unavailable(format!("connection closed: {e}")),
                 _ => Status::internal(format!("Unable to select: {e}")),
             })?
             .ok_or_else(|| Status::not_found("Empty queue"))?;
-        let next_key: i64 = row
-            .try_get(0)
-            .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))?;
-        let next_val: Vec<u8> = row
-            .try_get(1)
-            .map_err(|e| Status::internal(format!("Unable to get a value: {e}")))?;
-        Ok((next_key, next_val))
+        Item::from_row(&row).map_err(|e| Status::internal(format!("Unable to get an item: {e}")))
     }
 
+    // one more key is fetched to find out whether the page is full
+    #[allow(clippy::result_large_err)]
     pub async fn keys<C>(
         &self,
         checked_name: &str,
         client: &C,
-        limit: u64,
+        checked: &KeysReq,
     ) -> Result<ReceiverStream<Result<KeysResponse, Status>>, Status>
     where
         C: GenericClient,
     {
+        let (lower, upper, order) = match checked.as_descending() {
+            false => ("$1", "$2", "ASC"),
+            true => ("$2", "$1", "DESC"),
+        };
+        let (cond, fparams) = filter2sql(checked.as_filter(), 4);
         let query = format!(
             r#"
                 SELECT
                     key::BIGINT
                 FROM {checked_name}
-                ORDER BY key
-                LIMIT {limit}
+                WHERE
+                    key > COALESCE({lower}::BIGINT, 0)
+                    AND key < COALESCE({upper}::BIGINT, 9223372036854775807)
+                    AND visible_at <= CLOCK_TIMESTAMP()
+                    AND (expires_at IS NULL OR expires_at > CLOCK_TIMESTAMP())
+                    AND {cond}
+                ORDER BY key {order}
+                LIMIT $3::BIGINT
             "#
         );
-        let empty_params: Vec<i8> = vec![];
-        let row_stream: RowStream =
-            client
-                .query_raw(&query, &empty_params)
-                .await
-                .map_err(|e| match e.is_closed() {
-                    true => Status::unavailable(format!("connection closed: {e}")),
-                    _ => Status::internal(format!("Unable to get keys: {e}")),
-                })?;
+        let limit: u64 = checked.as_max_keys().min(i64::MAX as u64 - 1);
+        let start_after: Option<i64> = checked.as_start_after().map(|u| u as i64);
+        let end_before: Option<i64> = checked.as_end_before().map(|u| u as i64);
+        let fetch: i64 = limit as i64 + 1;
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&start_after, &end_before, &fetch];
+        params.extend(fparams.iter().map(|p| p.as_ref() as &(dyn ToSql + Sync)));
+        let row_stream: RowStream = client.query_raw(&query, params).await.map_err(|e| match e
+            .is_closed()
+        {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get keys: {e}")),
+        })?;
         let keys_stream = row_stream.map(|r: Result<_, _>| {
             r.and_then(|row: Row| {
                 let key: i64 = row.try_get(0)?;
                 Ok(key)
             })
             .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))
-            .map(|key: i64| KeysResponse { key: key as u64 })
         });
 
         let (tx, rx) = mpsc::channel(1);
 
         tokio::spawn(async move {
-            let t = &tx;
-            let rslt: Result<_, _> = keys_stream
-                .try_for_each(|key: KeysResponse| async move {
-                    t.send(Ok(key))
+            let send = |reply: Result<KeysResponse, Status>| {
+                let t = tx.clone();
+                async move {
+                    t.send(reply)
                         .await
-                        .map_err(|e| Status::cancelled(format!("Unable to send: {e}")))
-                })
-                .await;
-            match rslt {
-                Ok(_) => {}
-                Err(e) => log::warn!("Error while sending keys: {e}"),
+                        .map_err(|e| log::warn!("Unable to send: {e}"))
+                        .is_ok()
+                }
+            };
+            let reply = |key: i64, continuation: i64| KeysResponse {
+                key: key as u64,
+                continuation,
+            };
+            tokio::pin!(keys_stream);
+            let mut pending: Option<i64> = None;
+            let mut fetched: u64 = 0;
+            while let Some(r) = keys_stream.next().await {
+                let key: i64 = match r {
+                    Ok(key) => key,
+                    Err(e) => {
+                        send(Err(e)).await;
+                        return;
+                    }
+                };
+                fetched += 1;
+                if limit < fetched {
+                    if let Some(p) = pending.take() {
+                        send(Ok(reply(p, p))).await;
+                    }
+                    return;
+                }
+                if let Some(p) = pending.replace(key) {
+                    if !send(Ok(reply(p, -1))).await {
+                        return;
+                    }
+                }
+            }
+            if let Some(p) = pending {
+                send(Ok(reply(p, -1))).await;
             }
         });
         Ok(ReceiverStream::new(rx))
@@ -304,23 +1239,80 @@ where
         let checked: PushBackReq = pbr.try_into()?;
         let topic_id: Uuid = checked.as_topic_id();
         let name: String = self.topic2table.id2name(topic_id);
-        let value: &[u8] = checked.as_value();
-        let client: Client = self.get_client().await?;
-        self.push(&name, &client, value).await?;
-        let pushed: SystemTime = SystemTime::now();
+        let mut client: Client = self.get_client().await?;
+        let (key, pushed) = self.push_tail(&name, &mut client, &checked).await?;
         let reply = PushBackResponse {
             pushed: Some(pushed.into()),
+            key,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn push_batch(
+        &self,
+        req: Request<PushBatchRequest>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let pbr: PushBatchRequest = req.into_inner();
+        let checked: PushBatchReq = pbr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut client: Client = self.get_client().await?;
+        let (keys, pushed) = self
+            .push_batch_tail(&name, &mut client, checked.as_values())
+            .await?;
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: SystemTime| t.into()),
+            keys,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn push_batch_stream(
+        &self,
+        req: Request<Streaming<PushBatchRequest>>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let mut chunks: Streaming<PushBatchRequest> = req.into_inner();
+        let first: PushBatchRequest = chunks
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("empty batch stream"))?;
+        let mut checked: PushBatchReq = first.try_into()?;
+        while let Some(chunk) = chunks.message().await? {
+            let next: PushBatchReq = chunk.try_into()?;
+            checked.append(next)?;
+        }
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let mut client: Client = self.get_client().await?;
+        let (keys, pushed) = self
+            .push_batch_tail(&name, &mut client, checked.as_values())
+            .await?;
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: SystemTime| t.into()),
+            keys,
         };
         Ok(Response::new(reply))
     }
 
     async fn pop_front(
         &self,
-        _req: Request<PopFrontRequest>,
+        req: Request<PopFrontRequest>,
     ) -> Result<Response<PopFrontResponse>, Status> {
-        Err(Status::unimplemented(
-            "No plan to pop(delete) a queue(row) from a table for now",
-        ))
+        let pfr: PopFrontRequest = req.into_inner();
+        let checked: PopFrontReq = pfr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let item: Item = self
+            .pop(&name, &client, checked.as_priority_order())
+            .await?;
+        let popped: SystemTime = SystemTime::now();
+        let reply = PopFrontResponse {
+            popped: Some(popped.into()),
+            value: item.val,
+            headers: item.headers,
+        };
+        Ok(Response::new(reply))
     }
 
     async fn count(&self, req: Request<CountRequest>) -> Result<Response<CountResponse>, Status> {
@@ -340,15 +1332,24 @@ where
         let topic_id: Uuid = checked.as_topic_id();
         let name: String = self.topic2table.id2name(topic_id);
         let client: Client = self.get_client().await?;
-        let prev_key: Option<u64> = checked.as_previous_key();
-        let (next_key, next_val) = match prev_key {
-            None => self.first(name.as_str(), &client).await,
-            Some(prev) => Self::next(name.as_str(), prev as i64, &client).await,
-        }?;
-        let reply = NextResponse {
-            next: next_key,
-            value: next_val,
+        // a committed group offset overrides the previous key
+        let prev_key: Option<u64> = match checked.as_group() {
+            None => checked.as_previous_key(),
+            Some(group) => catalog::offset(name.as_str(), group, &client)
+                .await?
+                .map(|k| k as u64),
         };
+        let filter: Option<&Filter> = checked.as_filter();
+        let item: Item = match (checked.as_priority_order(), prev_key) {
+            (false, None) => self.first(name.as_str(), filter, &client).await,
+            (false, Some(prev)) => Self::next(name.as_str(), prev as i64, filter, &client).await,
+            (true, _) => {
+                let prev: Option<(i32, i64)> =
+                    prev_key.map(|k| (checked.as_previous_priority(), k as i64));
+                Self::next_prioritized(name.as_str(), prev, filter, &client).await
+            }
+        }?;
+        let reply: NextResponse = item.into();
         Ok(Response::new(reply))
     }
 
@@ -366,6 +1367,20 @@ where
         Ok(Response::new(reply))
     }
 
+    type SubscribeStream = ReceiverStream<Result<SubscribeResponse, Status>>;
+
+    async fn subscribe(
+        &self,
+        req: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let sr: SubscribeRequest = req.into_inner();
+        let checked: SubscribeReq = (&sr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let reply: Self::SubscribeStream = self.subscribe(name.as_str(), checked).await?;
+        Ok(Response::new(reply))
+    }
+
     type KeysStream = ReceiverStream<Result<KeysResponse, Status>>;
 
     async fn keys(&self, req: Request<KeysRequest>) -> Result<Response<Self::KeysStream>, Status> {
@@ -374,8 +1389,151 @@ where
         let topic_id: Uuid = checked.as_topic_id();
         let name: String = self.topic2table.id2name(topic_id);
         let client: Client = self.get_client().await?;
-        let keys_max: u64 = checked.as_max_keys();
-        let reply: Self::KeysStream = self.keys(name.as_str(), &client, keys_max).await?;
+        let reply: Self::KeysStream = self.keys(name.as_str(), &client, &checked).await?;
+        Ok(Response::new(reply))
+    }
+
+    async fn lease(&self, req: Request<LeaseRequest>) -> Result<Response<LeaseResponse>, Status> {
+        let lr: LeaseRequest = req.into_inner();
+        let checked: LeaseReq = (&lr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let lease_id: Uuid = Uuid::new_v4();
+        let timeout: Duration = checked.as_visibility_timeout();
+        let (item, deadline) = self.lease(&name, &client, lease_id, timeout).await?;
+        let reply = LeaseResponse {
+            key: item.key,
+            value: item.val,
+            lease_id: Some(lease_id.into()),
+            deadline: Some(deadline.into()),
+            headers: item.headers,
+            attempts: item.attempts as u64,
+            last_error: item.last_error.unwrap_or_default(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn ack(&self, req: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        let ar: AckRequest = req.into_inner();
+        let checked: AckReq = (&ar).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let key: i64 = checked.as_key() as i64;
+        self.ack(&name, &client, key, checked.as_lease_id()).await?;
+        let acked: SystemTime = SystemTime::now();
+        let reply = AckResponse {
+            acked: Some(acked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn nack(&self, req: Request<NackRequest>) -> Result<Response<NackResponse>, Status> {
+        let nr: NackRequest = req.into_inner();
+        let checked: NackReq = (&nr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let key: i64 = checked.as_key() as i64;
+        self.nack(
+            &name,
+            &client,
+            key,
+            checked.as_lease_id(),
+            checked.as_delay(),
+        )
+        .await?;
+        let nacked: SystemTime = SystemTime::now();
+        let reply = NackResponse {
+            nacked: Some(nacked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn extend_lease(
+        &self,
+        req: Request<ExtendLeaseRequest>,
+    ) -> Result<Response<ExtendLeaseResponse>, Status> {
+        let er: ExtendLeaseRequest = req.into_inner();
+        let checked: ExtendLeaseReq = (&er).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let key: i64 = checked.as_key() as i64;
+        let deadline: SystemTime = self
+            .extend_lease(
+                &name,
+                &client,
+                key,
+                checked.as_lease_id(),
+                checked.as_extension(),
+            )
+            .await?;
+        let reply = ExtendLeaseResponse {
+            deadline: Some(deadline.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn report_failure(
+        &self,
+        req: Request<ReportFailureRequest>,
+    ) -> Result<Response<ReportFailureResponse>, Status> {
+        let rfr: ReportFailureRequest = req.into_inner();
+        let checked: ReportFailureReq = rfr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let mut client: Client = self.get_client().await?;
+        let tx: Transaction = client
+            .transaction()
+            .await
+            .map_err(|e| Status::unavailable(format!("Unable to start a transaction: {e}")))?;
+        let attempts: u64 = self.fail(&name, &tx, key, checked.as_error()).await?;
+        let dead_lettered: bool = match catalog::dead_letter(&name, &tx).await? {
+            Some((dlq, max_attempts)) if max_attempts <= attempts => {
+                catalog::tail_lock(&dlq, &tx).await?;
+                self.dead_letter(&name, &dlq, &tx, key).await?;
+                true
+            }
+            _ => false,
+        };
+        tx.commit()
+            .await
+            .map_err(|e| Status::internal(format!("Unable to commit: {e}")))?;
+        let reply = ReportFailureResponse {
+            attempts,
+            dead_lettered,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn redrive(
+        &self,
+        req: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        let rr: RedriveRequest = req.into_inner();
+        let checked: RedriveReq = (&rr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let limit: Option<i64> = checked
+            .as_max_messages()
+            .map(|u| u.min(i64::MAX as u64) as i64);
+        let mut client: Client = self.get_client().await?;
+        let tx: Transaction = client
+            .transaction()
+            .await
+            .map_err(|e| Status::unavailable(format!("Unable to start a transaction: {e}")))?;
+        let (dlq, _) = catalog::dead_letter(&name, &tx).await?.ok_or_else(|| {
+            Status::failed_precondition(format!("No dead letter topic configured: {topic_id}"))
+        })?;
+        catalog::tail_lock(&name, &tx).await?;
+        let redriven: u64 = self.redrive(&name, &dlq, &tx, limit).await?;
+        tx.commit()
+            .await
+            .map_err(|e| Status::internal(format!("Unable to commit: {e}")))?;
+        let reply = RedriveResponse { redriven };
         Ok(Response::new(reply))
     }
 }
@@ -387,5 +1545,23 @@ where
     Svc {
         pool: pool.clone(),
         topic2table,
+        listener: None,
+        safe_tail: Mutex::new(HashMap::new()),
+    }
+}
+
+pub fn queue_svc_new_with_listener<T>(
+    pool: &Pool,
+    topic2table: T,
+    listener: &Arc<Listener>,
+) -> impl QueueService
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    Svc {
+        pool: pool.clone(),
+        topic2table,
+        listener: Some(listener.clone()),
+        safe_tail: Mutex::new(HashMap::new()),
     }
 }
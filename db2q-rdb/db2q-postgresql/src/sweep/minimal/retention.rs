@@ -0,0 +1,199 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use tokio::task::JoinHandle;
+
+use deadpool::managed::PoolError;
+use deadpool_postgres::tokio_postgres;
+use deadpool_postgres::{Client, GenericClient, Pool};
+use tokio_postgres::Row;
+
+use crate::common::minimal::catalog::TOPIC_CONFIG;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+pub const BATCH_SIZE_DEFAULT: u64 = 1024;
+
+struct Limits {
+    name: String,
+    max_messages: Option<i64>,
+    max_age_us: Option<i64>,
+    max_bytes: Option<i64>,
+}
+
+async fn pool2client(pool: &Pool) -> Result<Client, Status> {
+    match pool.get().await {
+        Ok(client) => Ok(client),
+        Err(PoolError::Timeout(t)) => Err(Status::unavailable(format!("timeout: {t:#?}"))),
+        Err(PoolError::Closed) => Err(Status::failed_precondition("All connection closed")),
+        Err(e) => Err(Status::internal(format!("Unexpected error: {e}"))),
+    }
+}
+
+async fn limits<C>(client: &C) -> Result<Vec<Limits>, Status>
+where
+    C: GenericClient,
+{
+    let query = format!(
+        r#"
+            SELECT
+                name::TEXT,
+                retain_messages::BIGINT,
+                (EXTRACT(EPOCH FROM retain_age) * 1000000)::BIGINT,
+                retain_bytes::BIGINT
+            FROM {TOPIC_CONFIG}
+            WHERE
+                retain_messages IS NOT NULL
+                OR retain_age IS NOT NULL
+                OR retain_bytes IS NOT NULL
+            ORDER BY name
+        "#
+    );
+    let rows: Vec<Row> = client
+        .query(&query, &[])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get retentions: {e}")),
+        })?;
+    rows.iter()
+        .map(|row: &Row| {
+            Ok(Limits {
+                name: row.try_get(0)?,
+                max_messages: row.try_get(1)?,
+                max_age_us: row.try_get(2)?,
+                max_bytes: row.try_get(3)?,
+            })
+        })
+        .collect::<Result<_, tokio_postgres::Error>>()
+        .map_err(|e| Status::internal(format!("Unable to get a retention: {e}")))
+}
+
+// the highest key to be trimmed(if any)
+async fn boundary<C>(checked_name: &str, client: &C, limits: &Limits) -> Result<Option<i64>, Status>
+where
+    C: GenericClient,
+{
+    let query = format!(
+        r#"
+            SELECT GREATEST(
+                (
+                    SELECT key
+                    FROM {checked_name}
+                    WHERE $1::BIGINT IS NOT NULL
+                    ORDER BY key DESC
+                    OFFSET $1::BIGINT
+                    LIMIT 1
+                ),
+                (
+                    SELECT MAX(key)
+                    FROM {checked_name}
+                    WHERE pushed < CLOCK_TIMESTAMP() - $2::BIGINT * INTERVAL '1 microsecond'
+                ),
+                (
+                    SELECT MAX(key)
+                    FROM (
+                        SELECT
+                            key,
+                            SUM(OCTET_LENGTH(val)) OVER (ORDER BY key DESC) AS total
+                        FROM {checked_name}
+                        WHERE $3::BIGINT IS NOT NULL
+                    ) AS newer
+                    WHERE total > $3::BIGINT
+                )
+            )::BIGINT
+        "#
+    );
+    let row: Row = client
+        .query_one(
+            &query,
+            &[&limits.max_messages, &limits.max_age_us, &limits.max_bytes],
+        )
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to get a retention boundary: {e}")),
+        })?;
+    row.try_get(0)
+        .map_err(|e| Status::internal(format!("Unable to get a key: {e}")))
+}
+
+pub async fn trim_batch<C>(
+    checked_name: &str,
+    client: &C,
+    upper: i64,
+    limit: i64,
+) -> Result<u64, Status>
+where
+    C: GenericClient,
+{
+    let query = format!(
+        r#"
+            DELETE FROM {checked_name}
+            WHERE key IN (
+                SELECT key
+                FROM {checked_name}
+                WHERE key <= $1::BIGINT
+                ORDER BY key
+                FOR UPDATE SKIP LOCKED
+                LIMIT $2::BIGINT
+            )
+        "#
+    );
+    client
+        .execute(&query, &[&upper, &limit])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to trim a topic: {e}")),
+        })
+}
+
+// small batches; pushes(higher keys) and reads are never blocked
+async fn trim_topic<C>(client: &C, limits: &Limits, batch_size: u64) -> Result<u64, Status>
+where
+    C: GenericClient,
+{
+    let checked_name: &str = limits.name.as_str();
+    let upper: i64 = match boundary(checked_name, client, limits).await? {
+        None => return Ok(0),
+        Some(upper) => upper,
+    };
+    let limit: i64 = batch_size.clamp(1, i64::MAX as u64) as i64;
+    let mut trimmed: u64 = 0;
+    loop {
+        let deleted: u64 = trim_batch(checked_name, client, upper, limit).await?;
+        trimmed += deleted;
+        if deleted < limit as u64 {
+            return Ok(trimmed);
+        }
+    }
+}
+
+pub async fn trim(pool: &Pool, batch_size: u64) -> Result<u64, Status> {
+    let client: Client = pool2client(pool).await?;
+    let all: Vec<Limits> = limits(&client).await?;
+    let mut trimmed: u64 = 0;
+    for l in all {
+        // a topic may be dropped while trimming
+        match trim_topic(&client, &l, batch_size).await {
+            Ok(cnt) => trimmed += cnt,
+            Err(e) => log::warn!("Unable to trim {}: {e}", l.name),
+        }
+    }
+    Ok(trimmed)
+}
+
+pub fn retention_worker_new(pool: &Pool, interval: Duration, batch_size: u64) -> JoinHandle<()> {
+    let pool: Pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            match trim(&pool, batch_size).await {
+                Ok(0) => {}
+                Ok(cnt) => log::debug!("Messages trimmed: {cnt}"),
+                Err(e) => log::warn!("Unable to trim: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -0,0 +1,99 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use tokio::task::JoinHandle;
+
+use deadpool::managed::PoolError;
+use deadpool_postgres::{Client, GenericClient, Pool};
+
+use db2q::queue::cmd::push::DEDUP_WINDOW;
+
+use crate::common::minimal::catalog;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+pub const BATCH_SIZE_DEFAULT: u64 = 1024;
+
+async fn pool2client(pool: &Pool) -> Result<Client, Status> {
+    match pool.get().await {
+        Ok(client) => Ok(client),
+        Err(PoolError::Timeout(t)) => Err(Status::unavailable(format!("timeout: {t:#?}"))),
+        Err(PoolError::Closed) => Err(Status::failed_precondition("All connection closed")),
+        Err(e) => Err(Status::internal(format!("Unexpected error: {e}"))),
+    }
+}
+
+pub async fn sweep_batch<C>(checked_name: &str, client: &C, batch_size: i64) -> Result<u64, Status>
+where
+    C: GenericClient,
+{
+    let query = format!(
+        r#"
+            DELETE FROM {checked_name}
+            WHERE key IN (
+                SELECT key
+                FROM {checked_name}
+                WHERE expires_at <= CLOCK_TIMESTAMP()
+                ORDER BY expires_at
+                FOR UPDATE SKIP LOCKED
+                LIMIT $1::BIGINT
+            )
+        "#
+    );
+    client
+        .execute(&query, &[&batch_size])
+        .await
+        .map_err(|e| match e.is_closed() {
+            true => Status::unavailable(format!("connection closed: {e}")),
+            _ => Status::internal(format!("Unable to delete expired messages: {e}")),
+        })
+}
+
+// each batch is a short transaction(no long running locks)
+pub async fn sweep_topic<C>(checked_name: &str, client: &C, batch_size: u64) -> Result<u64, Status>
+where
+    C: GenericClient,
+{
+    let limit: i64 = batch_size.clamp(1, i64::MAX as u64) as i64;
+    let mut swept: u64 = 0;
+    loop {
+        let deleted: u64 = sweep_batch(checked_name, client, limit).await?;
+        swept += deleted;
+        if deleted < limit as u64 {
+            return Ok(swept);
+        }
+    }
+}
+
+pub async fn sweep(pool: &Pool, batch_size: u64) -> Result<u64, Status> {
+    let client: Client = pool2client(pool).await?;
+    let names: Vec<String> = catalog::names(&client).await?;
+    let mut swept: u64 = 0;
+    match catalog::sweep_dedup(&client, DEDUP_WINDOW).await {
+        Ok(0) => {}
+        Ok(cnt) => log::debug!("Old request ids deleted: {cnt}"),
+        Err(e) => log::warn!("Unable to sweep request ids: {e}"),
+    }
+    for name in names {
+        // a topic may be dropped while sweeping
+        match sweep_topic(name.as_str(), &client, batch_size).await {
+            Ok(cnt) => swept += cnt,
+            Err(e) => log::warn!("Unable to sweep {name}: {e}"),
+        }
+    }
+    Ok(swept)
+}
+
+pub fn sweeper_new(pool: &Pool, interval: Duration, batch_size: u64) -> JoinHandle<()> {
+    let pool: Pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            match sweep(&pool, batch_size).await {
+                Ok(0) => {}
+                Ok(cnt) => log::debug!("Expired messages deleted: {cnt}"),
+                Err(e) => log::warn!("Unable to sweep: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -1,3 +1,4 @@
+use core::time::Duration;
 use std::time::SystemTime;
 
 use futures_util::stream::{StreamExt, TryStreamExt};
@@ -7,22 +8,32 @@ use tonic_types::{ErrorDetails, StatusExt};
 
 use deadpool::managed::PoolError;
 use deadpool_postgres::tokio_postgres;
-use deadpool_postgres::{Client, GenericClient, Pool};
+use deadpool_postgres::{Client, GenericClient, Pool, Transaction};
+use tokio_postgres::error::SqlState;
 use tokio_postgres::Row;
 
 use db2q::uuid::Uuid;
 
-use db2q::topic::cmd::create::CreateReq;
+use db2q::topic::cmd::create::{CreateReq, DeadLetter};
+use db2q::topic::cmd::describe::DescribeReq;
 use db2q::topic::cmd::drop::DropReq;
 use db2q::topic::cmd::list::ListReq;
+use db2q::topic::cmd::retention::Retention;
+use db2q::topic::cmd::update_retention::UpdateRetentionReq;
 
 use db2q::db2q::proto::queue::v1::Uuid as Guid;
 
 use db2q::db2q::proto::queue::v1::topic_service_server::TopicService;
 use db2q::db2q::proto::queue::v1::topic_svc::{CreateRequest, CreateResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{
+    DeadLetter as DeadLetterPb, Retention as RetentionPb,
+};
+use db2q::db2q::proto::queue::v1::topic_svc::{DescribeRequest, DescribeResponse};
 use db2q::db2q::proto::queue::v1::topic_svc::{DropRequest, DropResponse};
 use db2q::db2q::proto::queue::v1::topic_svc::{ListRequest, ListResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{UpdateRetentionRequest, UpdateRetentionResponse};
 
+use crate::common::minimal::catalog::{NOTIFY_PUSHED, TOPIC_CONFIG};
 use crate::topic::minimal::topic2table::TopicConv;
 
 pub struct Svc<T> {
@@ -43,20 +54,51 @@ where
         }
     }
 
-    async fn create<C>(&self, checked_name: &str, client: &C) -> Result<u64, Status>
+    async fn create<C>(&self, checked_name: &str, priority: bool, client: &C) -> Result<(), Status>
     where
         C: GenericClient,
     {
+        let priority_index: String = match priority {
+            true => format!(
+                r#"
+                    CREATE INDEX {checked_name}_priority
+                    ON {checked_name} (priority DESC, key);
+                "#
+            ),
+            false => String::new(),
+        };
         let query = format!(
             r#"
                 CREATE TABLE {checked_name} (
                     key BIGSERIAL PRIMARY KEY,
-                    val BYTEA NOT NULL
-                )
+                    val BYTEA NOT NULL,
+                    pushed TIMESTAMPTZ NOT NULL DEFAULT CLOCK_TIMESTAMP(),
+                    visible_at TIMESTAMPTZ NOT NULL DEFAULT CLOCK_TIMESTAMP(),
+                    expires_at TIMESTAMPTZ,
+                    priority INTEGER NOT NULL DEFAULT 0,
+                    hdr_keys TEXT[] NOT NULL DEFAULT '{{}}',
+                    hdr_vals BYTEA[] NOT NULL DEFAULT '{{}}',
+                    leased_until TIMESTAMPTZ,
+                    lease_id UUID,
+                    attempts BIGINT NOT NULL DEFAULT 0,
+                    last_error TEXT
+                );
+
+                CREATE INDEX {checked_name}_expires
+                ON {checked_name} (expires_at)
+                WHERE expires_at IS NOT NULL;
+
+                CREATE INDEX {checked_name}_pushed
+                ON {checked_name} USING BRIN (pushed);
+                {priority_index}
+                CREATE TRIGGER {checked_name}_pushed
+                AFTER INSERT ON {checked_name}
+                FOR EACH STATEMENT
+                EXECUTE FUNCTION {NOTIFY_PUSHED}();
             "#
         );
         client
-            .execute(&query, &[])
+            .batch_execute(&query)
             .await
             .map_err(|e| match e.is_closed() {
                 true => Status::unavailable(format!("connection closed: {e}")),
@@ -64,6 +106,116 @@ where
             })
     }
 
+    async fn configure<C>(
+        &self,
+        checked_name: &str,
+        dead_letter: Option<&DeadLetter>,
+        safe_tail: bool,
+        default_ttl: Option<Duration>,
+        client: &C,
+    ) -> Result<u64, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                INSERT INTO {TOPIC_CONFIG} (
+                    name,
+                    dead_letter,
+                    max_attempts,
+                    safe_tail,
+                    default_ttl
+                )
+                VALUES (
+                    $1::TEXT,
+                    $2::TEXT,
+                    $3::BIGINT,
+                    $4::BOOLEAN,
+                    $5::BIGINT * INTERVAL '1 microsecond'
+                )
+            "#
+        );
+        let dlq: Option<String> = dead_letter.map(|d| self.topic_conv.id2name(d.as_topic_id()));
+        let max_attempts: i64 = dead_letter
+            .map(|d| d.as_max_attempts().min(i64::MAX as u64) as i64)
+            .unwrap_or_default();
+        let ttl_us: Option<i64> = default_ttl.map(|d| d.as_micros().min(i64::MAX as u128) as i64);
+        client
+            .execute(
+                &query,
+                &[&checked_name, &dlq, &max_attempts, &safe_tail, &ttl_us],
+            )
+            .await
+            .map_err(|e| match (e.is_closed(), e.code()) {
+                (true, _) => Status::unavailable(format!("connection closed: {e}")),
+                (_, Some(&SqlState::FOREIGN_KEY_VIOLATION)) => {
+                    Status::failed_precondition(format!("dead letter topic missing: {e}"))
+                }
+                _ => Status::internal(format!("Unexpected error: {e}")),
+            })
+    }
+
+    async fn retain<C>(
+        &self,
+        checked_name: &str,
+        retention: Option<&Retention>,
+        client: &C,
+    ) -> Result<u64, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                UPDATE {TOPIC_CONFIG}
+                SET
+                    retain_messages = $2::BIGINT,
+                    retain_age = $3::BIGINT * INTERVAL '1 microsecond',
+                    retain_bytes = $4::BIGINT
+                WHERE name = $1::TEXT
+            "#
+        );
+        let max_messages: Option<i64> = retention
+            .and_then(|r| r.as_max_messages())
+            .map(|u| u.min(i64::MAX as u64) as i64);
+        let max_age_us: Option<i64> = retention
+            .and_then(|r| r.as_max_age())
+            .map(|d| d.as_micros().min(i64::MAX as u128) as i64);
+        let max_bytes: Option<i64> = retention
+            .and_then(|r| r.as_max_bytes())
+            .map(|u| u.min(i64::MAX as u64) as i64);
+        client
+            .execute(
+                &query,
+                &[&checked_name, &max_messages, &max_age_us, &max_bytes],
+            )
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to update a retention: {e}")),
+            })
+    }
+
+    async fn unconfigure<C>(&self, checked_name: &str, client: &C) -> Result<u64, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                DELETE FROM {TOPIC_CONFIG}
+                WHERE name = $1::TEXT
+            "#
+        );
+        client.execute(&query, &[&checked_name]).await.map_err(|e| {
+            match (e.is_closed(), e.code()) {
+                (true, _) => Status::unavailable(format!("connection closed: {e}")),
+                (_, Some(&SqlState::FOREIGN_KEY_VIOLATION)) => {
+                    Status::failed_precondition(format!("still used as a dead letter topic: {e}"))
+                }
+                _ => Status::internal(format!("Unexpected error: {e}")),
+            }
+        })
+    }
+
     async fn drop<C>(&self, checked_name: &str, client: &C) -> Result<u64, Status>
     where
         C: GenericClient,
@@ -82,6 +234,7 @@ where
             })
     }
 
+    #[allow(clippy::result_large_err)]
     async fn list<C>(&self, client: &C) -> Result<Vec<Guid>, Status>
     where
         C: GenericClient,
@@ -122,6 +275,127 @@ where
         let topics: Vec<Guid> = mapd.try_collect().await?;
         Ok(topics)
     }
+
+    // the options and the creation time from the catalog
+    async fn config<C>(&self, checked_name: &str, client: &C) -> Result<DescribeResponse, Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    created,
+                    dead_letter::TEXT,
+                    max_attempts::BIGINT,
+                    safe_tail::BOOLEAN,
+                    (EXTRACT(EPOCH FROM default_ttl) * 1000000)::BIGINT,
+                    retain_messages::BIGINT,
+                    (EXTRACT(EPOCH FROM retain_age) * 1000000)::BIGINT,
+                    retain_bytes::BIGINT,
+                    TO_REGCLASS('public.{checked_name}_priority') IS NOT NULL
+                FROM {TOPIC_CONFIG}
+                WHERE name = $1::TEXT
+            "#
+        );
+        let row: Row = client
+            .query_opt(&query, &[&checked_name])
+            .await
+            .map_err(|e| match e.is_closed() {
+                true => Status::unavailable(format!("connection closed: {e}")),
+                _ => Status::internal(format!("Unable to get a config: {e}")),
+            })?
+            .ok_or_else(|| Status::not_found(format!("No such topic: {checked_name}")))?;
+        let col = |e| Status::internal(format!("Unable to get a config column: {e}"));
+        let created: SystemTime = row.try_get(0).map_err(col)?;
+        let dead_letter: Option<String> = row.try_get(1).map_err(col)?;
+        let max_attempts: i64 = row.try_get(2).map_err(col)?;
+        let safe_tail: bool = row.try_get(3).map_err(col)?;
+        let ttl_us: Option<i64> = row.try_get(4).map_err(col)?;
+        let max_messages: Option<i64> = row.try_get(5).map_err(col)?;
+        let max_age_us: Option<i64> = row.try_get(6).map_err(col)?;
+        let max_bytes: Option<i64> = row.try_get(7).map_err(col)?;
+        let priority: bool = row.try_get(8).map_err(col)?;
+        let us2dur = |us: i64| Duration::from_micros(us.max(0) as u64).try_into().ok();
+        let dead_letter: Option<DeadLetterPb> = match dead_letter {
+            None => None,
+            Some(dlq) => Some(DeadLetterPb {
+                topic_id: Some(self.topic_conv.name2id(dlq.as_str())?.into()),
+                max_attempts: max_attempts.max(0) as u64,
+            }),
+        };
+        let retention: Option<RetentionPb> = match (max_messages, max_age_us, max_bytes) {
+            (None, None, None) => None,
+            _ => Some(RetentionPb {
+                max_messages: max_messages.unwrap_or_default().max(0) as u64,
+                max_age: max_age_us.and_then(us2dur),
+                max_bytes: max_bytes.unwrap_or_default().max(0) as u64,
+            }),
+        };
+        Ok(DescribeResponse {
+            created: Some(created.into()),
+            dead_letter,
+            safe_tail,
+            default_ttl: ttl_us.and_then(us2dur),
+            retention,
+            priority,
+            ..Default::default()
+        })
+    }
+
+    // payload bytes requires a full scan
+    async fn stats<C>(
+        &self,
+        checked_name: &str,
+        client: &C,
+        described: &mut DescribeResponse,
+    ) -> Result<(), Status>
+    where
+        C: GenericClient,
+    {
+        let query = format!(
+            r#"
+                SELECT
+                    COALESCE((SELECT MIN(key) FROM {checked_name}), -1)::BIGINT,
+                    COALESCE((SELECT MAX(key) FROM {checked_name}), -1)::BIGINT,
+                    GREATEST(
+                        (
+                            SELECT reltuples
+                            FROM pg_class
+                            WHERE oid = 'public.{checked_name}'::REGCLASS
+                        ),
+                        0
+                    )::BIGINT,
+                    COALESCE(
+                        (SELECT SUM(OCTET_LENGTH(val)) FROM {checked_name}),
+                        0
+                    )::BIGINT,
+                    PG_TOTAL_RELATION_SIZE('public.{checked_name}'::REGCLASS)::BIGINT
+            "#
+        );
+        let row: Row =
+            client
+                .query_one(&query, &[])
+                .await
+                .map_err(|e| match (e.is_closed(), e.code()) {
+                    (true, _) => Status::unavailable(format!("connection closed: {e}")),
+                    (_, Some(&SqlState::UNDEFINED_TABLE)) => {
+                        Status::not_found(format!("No such topic: {e}"))
+                    }
+                    _ => Status::internal(format!("Unable to get stats: {e}")),
+                })?;
+        let col = |e| Status::internal(format!("Unable to get a stat column: {e}"));
+        let min_key: i64 = row.try_get(0).map_err(col)?;
+        let max_key: i64 = row.try_get(1).map_err(col)?;
+        let count_estimate: i64 = row.try_get(2).map_err(col)?;
+        let payload_bytes: i64 = row.try_get(3).map_err(col)?;
+        let relation_size: i64 = row.try_get(4).map_err(col)?;
+        described.min_key = min_key;
+        described.max_key = max_key;
+        described.count_estimate = count_estimate as u64;
+        described.payload_bytes = payload_bytes.max(0) as u64;
+        described.relation_size = relation_size.max(0) as u64;
+        Ok(())
+    }
 }
 
 #[tonic::async_trait]
@@ -137,8 +411,26 @@ where
         let checked: CreateReq = (&cr).try_into()?;
         let topic_id: Uuid = checked.as_topic_id();
         let name: String = self.topic_conv.id2name(topic_id);
-        let client: Client = self.get_client().await?;
-        self.create(name.as_str(), &client).await?;
+        let mut client: Client = self.get_client().await?;
+        let tx: Transaction = client
+            .transaction()
+            .await
+            .map_err(|e| Status::unavailable(format!("Unable to start a transaction: {e}")))?;
+        self.create(name.as_str(), checked.as_priority(), &tx)
+            .await?;
+        self.configure(
+            name.as_str(),
+            checked.as_dead_letter(),
+            checked.as_safe_tail(),
+            checked.as_default_ttl(),
+            &tx,
+        )
+        .await?;
+        self.retain(name.as_str(), checked.as_retention(), &tx)
+            .await?;
+        tx.commit()
+            .await
+            .map_err(|e| Status::internal(format!("Unable to commit: {e}")))?;
         let created: SystemTime = SystemTime::now();
         let reply = CreateResponse {
             created: Some(created.into()),
@@ -151,8 +443,16 @@ where
         let checked: DropReq = (&cr).try_into()?;
         let topic_id: Uuid = checked.as_topic_id();
         let name: String = self.topic_conv.id2name(topic_id);
-        let client: Client = self.get_client().await?;
-        self.drop(name.as_str(), &client).await?;
+        let mut client: Client = self.get_client().await?;
+        let tx: Transaction = client
+            .transaction()
+            .await
+            .map_err(|e| Status::unavailable(format!("Unable to start a transaction: {e}")))?;
+        self.unconfigure(name.as_str(), &tx).await?;
+        self.drop(name.as_str(), &tx).await?;
+        tx.commit()
+            .await
+            .map_err(|e| Status::internal(format!("Unable to commit: {e}")))?;
         let dropped: SystemTime = SystemTime::now();
         let reply = DropResponse {
             dropped: Some(dropped.into()),
@@ -175,6 +475,43 @@ where
         let reply = ListResponse { topics };
         Ok(Response::new(reply))
     }
+
+    async fn update_retention(
+        &self,
+        req: Request<UpdateRetentionRequest>,
+    ) -> Result<Response<UpdateRetentionResponse>, Status> {
+        let ur: UpdateRetentionRequest = req.into_inner();
+        let checked: UpdateRetentionReq = (&ur).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let updated: u64 = self
+            .retain(name.as_str(), checked.as_retention(), &client)
+            .await?;
+        match updated {
+            0 => Err(Status::not_found(format!("No such topic: {topic_id}"))),
+            _ => Ok(()),
+        }?;
+        let updated: SystemTime = SystemTime::now();
+        let reply = UpdateRetentionResponse {
+            updated: Some(updated.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn describe(
+        &self,
+        req: Request<DescribeRequest>,
+    ) -> Result<Response<DescribeResponse>, Status> {
+        let dr: DescribeRequest = req.into_inner();
+        let checked: DescribeReq = (&dr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let client: Client = self.get_client().await?;
+        let mut reply: DescribeResponse = self.config(name.as_str(), &client).await?;
+        self.stats(name.as_str(), &client, &mut reply).await?;
+        Ok(Response::new(reply))
+    }
 }
 
 pub fn topic_svc_new<T>(pool: &Pool, topic_conv: T) -> impl TopicService
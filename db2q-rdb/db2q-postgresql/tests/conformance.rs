@@ -0,0 +1,65 @@
+use core::time::Duration;
+use std::env;
+
+use db2q_postgresql::db2q::conformance::{backend_new_with_groups, run_all};
+
+use db2q_postgresql::deadpool_postgres;
+
+use deadpool_postgres::tokio_postgres;
+use tokio_postgres::{Config, NoTls};
+
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+
+use db2q_postgresql::common::minimal::catalog::init_pool;
+use db2q_postgresql::common::minimal::listener::listener_new;
+use db2q_postgresql::count::minimal::svc::count_svc_new;
+use db2q_postgresql::queue::minimal::svc::queue_svc_new_with_listener;
+use db2q_postgresql::sweep::minimal::retention::retention_worker_new;
+use db2q_postgresql::sweep::minimal::ttl::sweeper_new;
+use db2q_postgresql::topic::minimal::svc::topic_svc_new;
+use db2q_postgresql::topic::minimal::topic2table::topic2table_prefix_default;
+
+// small batches to check bounded deletes
+const WORKER_INTERVAL: Duration = Duration::from_millis(100);
+const WORKER_BATCH_SIZE: u64 = 2;
+
+// uses a local postgres(same env vars as the example): cargo test -- --ignored
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a postgresql server"]
+async fn postgresql() -> Result<(), String> {
+    let pghost: String = env::var("PGHOST").unwrap_or_else(|_| "/var/run/postgresql".into());
+    let pguser: String = env::var("PGUSER").unwrap_or_else(|_| "postgres".into());
+    let pgpass: String = env::var("PGPASSWORD").unwrap_or_else(|_| "postgres".into());
+    let pgdb: String = env::var("PGDATABASE").unwrap_or_else(|_| "postgres".into());
+
+    let mut pgcfg: Config = Config::new();
+    pgcfg
+        .user(&pguser)
+        .dbname(&pgdb)
+        .password(&pgpass)
+        .host(&pghost);
+
+    let mgcfg: ManagerConfig = ManagerConfig {
+        recycling_method: RecyclingMethod::Fast,
+    };
+    let mg: Manager = Manager::from_config(pgcfg.clone(), NoTls, mgcfg);
+    let pool: Pool = Pool::builder(mg)
+        .max_size(4)
+        .build()
+        .map_err(|e| format!("Unable to build pool: {e}"))?;
+
+    init_pool(&pool)
+        .await
+        .map_err(|e| format!("Unable to create a catalog: {e}"))?;
+
+    sweeper_new(&pool, WORKER_INTERVAL, WORKER_BATCH_SIZE);
+    retention_worker_new(&pool, WORKER_INTERVAL, WORKER_BATCH_SIZE);
+
+    let listener = listener_new(pgcfg, NoTls);
+    let backend = backend_new_with_groups(
+        queue_svc_new_with_listener(&pool, topic2table_prefix_default(), &listener),
+        topic_svc_new(&pool, topic2table_prefix_default()),
+        count_svc_new(&pool, topic2table_prefix_default()),
+    );
+    run_all(&backend).await
+}
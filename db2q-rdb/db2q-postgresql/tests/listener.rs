@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use db2q_postgresql::deadpool_postgres;
+
+use deadpool_postgres::tokio_postgres;
+use tokio_postgres::{Config, NoTls};
+
+use tokio::sync::Notify;
+
+use db2q_postgresql::common::minimal::listener::{listener_new, Listener};
+
+// no server required; the listener keeps reconnecting in background
+#[tokio::test]
+async fn unwatched_topics_pruned() {
+    let mut pgcfg: Config = Config::new();
+    pgcfg.host_path("/nonexistent").dbname("postgres");
+    let listener: Arc<Listener> = listener_new(pgcfg, NoTls);
+
+    let t1: Arc<Notify> = listener.watch("t1");
+    let again: Arc<Notify> = listener.watch("t1");
+    assert!(Arc::ptr_eq(&t1, &again));
+    let t2: Arc<Notify> = listener.watch("t2");
+    assert_eq!(listener.as_watched(), 2);
+
+    drop(t1);
+    drop(again);
+    let _t3: Arc<Notify> = listener.watch("t3");
+    assert_eq!(listener.as_watched(), 2);
+
+    drop(t2);
+    let _t1: Arc<Notify> = listener.watch("t1");
+    assert_eq!(listener.as_watched(), 2);
+}
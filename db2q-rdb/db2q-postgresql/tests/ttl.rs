@@ -0,0 +1,164 @@
+use core::time::Duration;
+use std::env;
+
+use tokio::time::Instant;
+
+use db2q_postgresql::deadpool_postgres;
+use db2q_postgresql::tonic;
+
+use deadpool_postgres::tokio_postgres;
+use tokio_postgres::{Config, NoTls};
+
+use deadpool_postgres::{Client, Manager, ManagerConfig, Pool, RecyclingMethod};
+
+use tonic::Request;
+
+use db2q_postgresql::db2q::uuid::Uuid;
+
+use db2q_postgresql::db2q::db2q::proto::queue::v1::q_svc::{
+    NextRequest, PushBackRequest, PushBatchRequest,
+};
+use db2q_postgresql::db2q::db2q::proto::queue::v1::topic_svc::{CreateRequest, DropRequest};
+use db2q_postgresql::queue_service_server::QueueService;
+use db2q_postgresql::topic_service_server::TopicService;
+
+use db2q_postgresql::common::minimal::catalog::init_pool;
+use db2q_postgresql::common::minimal::topic2table::{topic2table_prefix_default, Topic2Table};
+use db2q_postgresql::queue::minimal::svc::queue_svc_new;
+use db2q_postgresql::sweep::minimal::ttl::{sweep_batch, sweep_topic};
+use db2q_postgresql::topic::minimal::svc::topic_svc_new;
+
+const EXPIRED: usize = 1000;
+const PUSHED_WHILE_SWEEPING: usize = 20;
+
+// a push or a next while sweeping in batches of one
+const RESPONSIVE_WITHIN: Duration = Duration::from_secs(2);
+
+async fn pool_new() -> Result<Pool, String> {
+    let pghost: String = env::var("PGHOST").unwrap_or_else(|_| "/var/run/postgresql".into());
+    let pguser: String = env::var("PGUSER").unwrap_or_else(|_| "postgres".into());
+    let pgpass: String = env::var("PGPASSWORD").unwrap_or_else(|_| "postgres".into());
+    let pgdb: String = env::var("PGDATABASE").unwrap_or_else(|_| "postgres".into());
+
+    let mut pgcfg: Config = Config::new();
+    pgcfg
+        .user(&pguser)
+        .dbname(&pgdb)
+        .password(&pgpass)
+        .host(&pghost);
+
+    let mgcfg: ManagerConfig = ManagerConfig {
+        recycling_method: RecyclingMethod::Fast,
+    };
+    let mg: Manager = Manager::from_config(pgcfg, NoTls, mgcfg);
+    let pool: Pool = Pool::builder(mg)
+        .max_size(4)
+        .build()
+        .map_err(|e| format!("Unable to build pool: {e}"))?;
+    init_pool(&pool)
+        .await
+        .map_err(|e| format!("Unable to create a catalog: {e}"))?;
+    Ok(pool)
+}
+
+// pushes and reads live items while the expired ones are swept
+async fn push_and_next<Q>(queue: &Q, topic_id: Uuid) -> Result<Vec<i64>, String>
+where
+    Q: QueueService,
+{
+    let mut keys: Vec<i64> = Vec::new();
+    for _ in 0..PUSHED_WHILE_SWEEPING {
+        let started: Instant = Instant::now();
+        let req = PushBackRequest {
+            request_id: Some(Uuid::new_v4().into()),
+            topic_id: Some(topic_id.into()),
+            value: b"live".to_vec(),
+            ttl: Duration::from_secs(3600).try_into().ok(),
+            ..Default::default()
+        };
+        let pushed = queue
+            .push_back(Request::new(req))
+            .await
+            .map_err(|e| format!("Unable to push: {e}"))?;
+        keys.push(pushed.into_inner().key);
+        let req = NextRequest {
+            request_id: Some(Uuid::new_v4().into()),
+            topic_id: Some(topic_id.into()),
+            previous: -1,
+            ..Default::default()
+        };
+        let next = queue
+            .next(Request::new(req))
+            .await
+            .map_err(|e| format!("Unable to get the next: {e}"))?;
+        assert_eq!(next.into_inner().next, keys[0]);
+        assert!(started.elapsed() < RESPONSIVE_WITHIN);
+    }
+    Ok(keys)
+}
+
+// uses a local postgres(same env vars as the example): cargo test -- --ignored
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a postgresql server"]
+async fn expired_swept_in_batches() -> Result<(), String> {
+    let pool: Pool = pool_new().await?;
+    let topic = topic_svc_new(&pool, topic2table_prefix_default());
+    let queue = queue_svc_new(&pool, topic2table_prefix_default());
+
+    let topic_id: Uuid = Uuid::new_v4();
+    let req = CreateRequest {
+        request_id: Some(Uuid::new_v4().into()),
+        topic_id: Some(topic_id.into()),
+        default_ttl: Duration::from_millis(1).try_into().ok(),
+        ..Default::default()
+    };
+    topic
+        .create(Request::new(req))
+        .await
+        .map_err(|e| format!("Unable to create a topic: {e}"))?;
+    let req = PushBatchRequest {
+        request_id: Some(Uuid::new_v4().into()),
+        topic_id: Some(topic_id.into()),
+        values: vec![b"expired".to_vec(); EXPIRED],
+    };
+    queue
+        .push_batch(Request::new(req))
+        .await
+        .map_err(|e| format!("Unable to push: {e}"))?;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let name: String = topic2table_prefix_default().id2name(topic_id);
+    let client: Client = pool
+        .get()
+        .await
+        .map_err(|e| format!("Unable to get a client: {e}"))?;
+    let deleted: u64 = sweep_batch(&name, &client, 10)
+        .await
+        .map_err(|e| format!("Unable to sweep: {e}"))?;
+    assert_eq!(deleted, 10);
+
+    let (swept, pushed) = tokio::join!(
+        sweep_topic(&name, &client, 1),
+        push_and_next(&queue, topic_id)
+    );
+    let swept: u64 = swept.map_err(|e| format!("Unable to sweep: {e}"))?;
+    let pushed: Vec<i64> = pushed?;
+    assert_eq!(swept, (EXPIRED - 10) as u64);
+
+    let query: String = format!("SELECT key FROM {name} ORDER BY key");
+    let rows = client
+        .query(&query, &[])
+        .await
+        .map_err(|e| format!("Unable to select: {e}"))?;
+    let kept: Vec<i64> = rows.iter().map(|r| r.get(0)).collect();
+    assert_eq!(kept, pushed);
+
+    let req = DropRequest {
+        request_id: Some(Uuid::new_v4().into()),
+        topic_id: Some(topic_id.into()),
+    };
+    TopicService::drop(&topic, Request::new(req))
+        .await
+        .map_err(|e| format!("Unable to drop a topic: {e}"))?;
+    Ok(())
+}
@@ -0,0 +1,11 @@
+#!/bin/sh
+
+listen_addr="127.0.0.1:9115"
+wait_next_min_interval_ns=$( echo 1,000 | tr -d , )
+db_path="./db2q.sqlite3"
+
+RUST_LOG=info \
+ENV_INTERVAL_NS_MINIMUM="${wait_next_min_interval_ns}" \
+ENV_DB_PATH="${db_path}" \
+ENV_LISTEN_ADDR="${listen_addr}" \
+	./simple
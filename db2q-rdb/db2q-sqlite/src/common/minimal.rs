@@ -0,0 +1,5 @@
+pub mod catalog;
+pub mod notifier;
+pub mod pool;
+pub mod time;
+pub mod topic2table;
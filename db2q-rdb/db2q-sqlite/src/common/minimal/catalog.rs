@@ -0,0 +1,103 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use rusqlite::{Connection, OptionalExtension};
+
+use super::pool::{sqlite2status, Pool};
+use super::time::{dur2micros, now_micros};
+
+pub const TOPIC_CONFIG: &str = "db2q_topic_config";
+pub const PUSH_DEDUP: &str = "db2q_push_dedup";
+
+#[allow(clippy::result_large_err)]
+pub fn init(conn: &Connection) -> Result<(), Status> {
+    // durations: microseconds, timestamps: microseconds since the unix epoch
+    let query: String = format!(
+        r#"
+            CREATE TABLE IF NOT EXISTS {TOPIC_CONFIG} (
+                name TEXT PRIMARY KEY,
+                dead_letter TEXT REFERENCES {TOPIC_CONFIG} (name),
+                max_attempts INTEGER NOT NULL DEFAULT 0,
+                safe_tail INTEGER NOT NULL DEFAULT 0,
+                default_ttl INTEGER,
+                retain_messages INTEGER,
+                retain_age INTEGER,
+                retain_bytes INTEGER,
+                created INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS {PUSH_DEDUP} (
+                name TEXT NOT NULL REFERENCES {TOPIC_CONFIG} (name) ON DELETE CASCADE,
+                req_id TEXT NOT NULL,
+                key INTEGER NOT NULL,
+                pushed INTEGER NOT NULL,
+                PRIMARY KEY (name, req_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS {PUSH_DEDUP}_pushed
+            ON {PUSH_DEDUP} (pushed);
+        "#
+    );
+    conn.execute_batch(&query)
+        .map_err(|e| sqlite2status(e, "Unable to create a catalog"))
+}
+
+#[allow(clippy::result_large_err)]
+pub async fn init_pool(pool: &Pool) -> Result<(), Status> {
+    pool.run(|conn: &mut Connection| init(conn)).await
+}
+
+#[allow(clippy::result_large_err)]
+pub fn dead_letter(checked_name: &str, conn: &Connection) -> Result<Option<(String, u64)>, Status> {
+    let query: String = format!(
+        r#"
+            SELECT
+                dead_letter,
+                max_attempts
+            FROM {TOPIC_CONFIG}
+            WHERE
+                name = ?1
+                AND dead_letter IS NOT NULL
+        "#
+    );
+    let orow: Option<(String, i64)> = conn
+        .query_row(&query, [checked_name], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()
+        .map_err(|e| sqlite2status(e, "Unable to get a topic config"))?;
+    Ok(orow.map(|(dlq, max_attempts)| (dlq, max_attempts.max(0) as u64)))
+}
+
+#[allow(clippy::result_large_err)]
+pub fn names(conn: &Connection) -> Result<Vec<String>, Status> {
+    let query: String = format!(
+        r#"
+            SELECT name
+            FROM {TOPIC_CONFIG}
+            ORDER BY name
+        "#
+    );
+    let mut stmt = conn
+        .prepare(&query)
+        .map_err(|e| sqlite2status(e, "Unable to get topic names"))?;
+    let names = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| sqlite2status(e, "Unable to get topic names"))?;
+    names
+        .collect::<Result<_, _>>()
+        .map_err(|e| sqlite2status(e, "Unable to get a topic name"))
+}
+
+#[allow(clippy::result_large_err)]
+pub fn sweep_dedup(conn: &Connection, window: Duration) -> Result<u64, Status> {
+    let query: String = format!(
+        r#"
+            DELETE FROM {PUSH_DEDUP}
+            WHERE pushed < ?1
+        "#
+    );
+    let expired: i64 = now_micros().saturating_sub(dur2micros(window));
+    conn.execute(&query, [expired])
+        .map(|deleted: usize| deleted as u64)
+        .map_err(|e| sqlite2status(e, "Unable to delete old request ids"))
+}
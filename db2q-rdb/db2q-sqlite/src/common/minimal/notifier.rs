@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+use tokio::sync::Notify;
+
+// pushes of this process only; waiters keep polling for other writers
+// entries are removed once all of their watchers are dropped
+#[derive(Default)]
+pub struct Notifier {
+    waiters: Mutex<HashMap<String, Weak<Notify>>>,
+}
+
+impl Notifier {
+    pub fn watch(&self, checked_name: &str) -> Arc<Notify> {
+        match self.waiters.lock() {
+            Ok(mut guard) => match guard.get(checked_name).and_then(Weak::upgrade) {
+                Some(n) => n,
+                None => {
+                    guard.retain(|_, w| 0 < w.strong_count());
+                    let n: Arc<Notify> = Arc::new(Notify::new());
+                    guard.insert(checked_name.into(), Arc::downgrade(&n));
+                    n
+                }
+            },
+            Err(e) => {
+                log::warn!("Unable to lock: {e}");
+                Arc::new(Notify::new())
+            }
+        }
+    }
+
+    pub fn wake(&self, checked_name: &str) {
+        match self.waiters.lock() {
+            Ok(guard) => match guard.get(checked_name).and_then(Weak::upgrade) {
+                None => {}
+                Some(n) => n.notify_waiters(),
+            },
+            Err(e) => log::warn!("Unable to lock: {e}"),
+        }
+    }
+
+    pub fn as_watched(&self) -> usize {
+        match self.waiters.lock() {
+            Ok(guard) => guard.len(),
+            Err(e) => {
+                log::warn!("Unable to lock: {e}");
+                0
+            }
+        }
+    }
+}
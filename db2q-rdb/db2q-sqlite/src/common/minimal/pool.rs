@@ -0,0 +1,135 @@
+use core::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tonic::Status;
+
+use rusqlite::{Connection, ErrorCode, Transaction, TransactionBehavior};
+
+pub const BUSY_TIMEOUT_DEFAULT: Duration = Duration::from_secs(5);
+pub const IDLE_MAX_DEFAULT: usize = 16;
+
+struct Inner {
+    path: PathBuf,
+    busy_timeout: Duration,
+    idle_max: usize,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl Inner {
+    #[allow(clippy::result_large_err)]
+    fn open(&self) -> Result<Connection, Status> {
+        let conn: Connection = Connection::open(&self.path)
+            .map_err(|e| Status::unavailable(format!("Unable to open a database: {e}")))?;
+        conn.busy_timeout(self.busy_timeout)
+            .map_err(|e| Status::internal(format!("Unable to set a busy timeout: {e}")))?;
+        // readers never block the writer(and vice versa) in the WAL mode
+        conn.execute_batch(
+            r#"
+                PRAGMA journal_mode = WAL;
+                PRAGMA synchronous = NORMAL;
+                PRAGMA foreign_keys = ON;
+            "#,
+        )
+        .map_err(|e| Status::internal(format!("Unable to configure a connection: {e}")))?;
+        Ok(conn)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn checkout(&self) -> Result<Connection, Status> {
+        let idle: Option<Connection> = match self.idle.lock() {
+            Ok(mut guard) => guard.pop(),
+            Err(e) => {
+                log::warn!("Unable to lock: {e}");
+                None
+            }
+        };
+        match idle {
+            Some(conn) => Ok(conn),
+            None => self.open(),
+        }
+    }
+
+    fn checkin(&self, conn: Connection) {
+        match self.idle.lock() {
+            Ok(mut guard) => {
+                if guard.len() < self.idle_max {
+                    guard.push(conn)
+                }
+            }
+            Err(e) => log::warn!("Unable to lock: {e}"),
+        }
+    }
+}
+
+// connections are opened on demand and used only on the blocking pool
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+impl Pool {
+    #[allow(clippy::result_large_err)]
+    pub async fn run<F, R>(&self, f: F) -> Result<R, Status>
+    where
+        F: FnOnce(&mut Connection) -> Result<R, Status> + Send + 'static,
+        R: Send + 'static,
+    {
+        let inner: Arc<Inner> = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn: Connection = inner.checkout()?;
+            let rslt: Result<R, Status> = f(&mut conn);
+            inner.checkin(conn);
+            rslt
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Unable to run a blocking task: {e}")))?
+    }
+}
+
+// a file path is required: each connection to ":memory:" opens a different database
+pub fn pool_new<P>(path: P, busy_timeout: Duration, idle_max: usize) -> Pool
+where
+    P: AsRef<Path>,
+{
+    Pool {
+        inner: Arc::new(Inner {
+            path: path.as_ref().into(),
+            busy_timeout,
+            idle_max,
+            idle: Mutex::new(Vec::new()),
+        }),
+    }
+}
+
+pub fn sqlite2status(e: rusqlite::Error, message: &str) -> Status {
+    match e.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => {
+            Status::unavailable(format!("{message}: {e}"))
+        }
+        _ => Status::internal(format!("{message}: {e}")),
+    }
+}
+
+// a missing table is a dropped topic for pushes
+pub fn push2status(e: rusqlite::Error, message: &str) -> Status {
+    match &e {
+        rusqlite::Error::SqliteFailure(_, Some(m)) if m.starts_with("no such table") => {
+            Status::not_found(format!("{message}: {e}"))
+        }
+        _ => sqlite2status(e, message),
+    }
+}
+
+// takes the write lock first to avoid failing to upgrade a read transaction
+#[allow(clippy::result_large_err)]
+pub fn tx_immediate(conn: &mut Connection) -> Result<Transaction<'_>, Status> {
+    conn.transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|e| Status::unavailable(format!("Unable to start a transaction: {e}")))
+}
+
+#[allow(clippy::result_large_err)]
+pub fn commit(tx: Transaction<'_>) -> Result<(), Status> {
+    tx.commit()
+        .map_err(|e| Status::internal(format!("Unable to commit: {e}")))
+}
@@ -0,0 +1,180 @@
+use std::time::SystemTime;
+
+use tonic::{Request, Response, Status};
+
+use rusqlite::Connection;
+
+use db2q::uuid::Uuid;
+
+use db2q::count::cmd::exact::ExactReq;
+use db2q::count::cmd::fast::FastReq;
+use db2q::count::cmd::lag::LagReq;
+
+use db2q::db2q::proto::queue::v1::cnt_svc::{ExactRequest, ExactResponse};
+use db2q::db2q::proto::queue::v1::cnt_svc::{FastRequest, FastResponse};
+use db2q::db2q::proto::queue::v1::cnt_svc::{LagRequest, LagResponse};
+use db2q::db2q::proto::queue::v1::count_service_server::CountService;
+
+use crate::common::minimal::pool::{sqlite2status, Pool};
+use crate::common::minimal::time::{micros2dur, now_micros, time2micros};
+use crate::common::minimal::topic2table::Topic2Table;
+
+pub struct Svc<T> {
+    pool: Pool,
+    topic2table: T,
+}
+
+impl<T> Svc<T> {
+    // unset bounds are replaced with the widest ones to keep the key range scan
+    #[allow(clippy::result_large_err)]
+    pub fn count(
+        checked_name: &str,
+        keys: (Option<i64>, Option<i64>),
+        pushed: (Option<SystemTime>, Option<SystemTime>),
+        conn: &Connection,
+    ) -> Result<u64, Status> {
+        let query = format!(
+            r#"
+                SELECT
+                    COUNT(*) AS cnt
+                FROM {checked_name}
+                WHERE
+                    key >= COALESCE(?1, 0)
+                    AND key < COALESCE(?2, 9223372036854775807)
+                    AND pushed >= COALESCE(?3, -9223372036854775808)
+                    AND pushed < COALESCE(?4, 9223372036854775807)
+                    AND (expires_at IS NULL OR expires_at > ?5)
+            "#
+        );
+        let (lower, upper) = keys;
+        let since: Option<i64> = pushed.0.map(time2micros);
+        let until: Option<i64> = pushed.1.map(time2micros);
+        let cnt: i64 = conn
+            .query_row(
+                &query,
+                rusqlite::params![lower, upper, since, until, now_micros()],
+                |row| row.get(0),
+            )
+            .map_err(|e| sqlite2status(e, "Unable to count"))?;
+        Ok(cnt as u64)
+    }
+
+    // (remaining, age of the oldest remaining(micros), newest key)
+    #[allow(clippy::result_large_err)]
+    pub fn lag(
+        checked_name: &str,
+        prev: i64,
+        conn: &Connection,
+    ) -> Result<(u64, Option<i64>, i64), Status> {
+        let query = format!(
+            r#"
+                SELECT
+                    (
+                        SELECT COUNT(*)
+                        FROM {checked_name}
+                        WHERE
+                            key > ?1
+                            AND (expires_at IS NULL OR expires_at > ?2)
+                    ) AS remaining,
+                    (
+                        SELECT ?2 - pushed
+                        FROM {checked_name}
+                        WHERE
+                            key > ?1
+                            AND (expires_at IS NULL OR expires_at > ?2)
+                        ORDER BY key
+                        LIMIT 1
+                    ) AS oldest_age,
+                    COALESCE(
+                        (
+                            SELECT key
+                            FROM {checked_name}
+                            ORDER BY key DESC
+                            LIMIT 1
+                        ),
+                        -1
+                    ) AS newest
+            "#
+        );
+        conn.query_row(&query, rusqlite::params![prev, now_micros()], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| sqlite2status(e, "Unable to get a lag"))
+    }
+}
+
+#[tonic::async_trait]
+impl<T> CountService for Svc<T>
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    #[allow(clippy::result_large_err)]
+    async fn exact(&self, req: Request<ExactRequest>) -> Result<Response<ExactResponse>, Status> {
+        let er: ExactRequest = req.into_inner();
+        let checked: ExactReq = (&er).try_into()?;
+        let topic_id: Uuid = checked.as_topic();
+        let name: String = self.topic2table.id2name(topic_id);
+        let keys = (
+            checked.as_lower().map(|u| u as i64),
+            checked.as_upper().map(|u| u as i64),
+        );
+        let pushed = (checked.as_since(), checked.as_until());
+        let cnt: u64 = self
+            .pool
+            .run(move |conn: &mut Connection| Self::count(name.as_str(), keys, pushed, conn))
+            .await?;
+        let reply = ExactResponse { count: cnt };
+        Ok(Response::new(reply))
+    }
+
+    // no statistics kept: counts exactly
+    #[allow(clippy::result_large_err)]
+    async fn fast(&self, req: Request<FastRequest>) -> Result<Response<FastResponse>, Status> {
+        let fr: FastRequest = req.into_inner();
+        let checked: FastReq = (&fr).try_into()?;
+        let topic_id: Uuid = checked.as_topic();
+        let name: String = self.topic2table.id2name(topic_id);
+        let cnt: u64 = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                Self::count(name.as_str(), (None, None), (None, None), conn)
+            })
+            .await?;
+        let reply = FastResponse {
+            count_estimate: cnt,
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn lag(&self, req: Request<LagRequest>) -> Result<Response<LagResponse>, Status> {
+        let lr: LagRequest = req.into_inner();
+        let checked: LagReq = (&lr).try_into()?;
+        if checked.as_group().is_some() {
+            return Err(Status::unimplemented("consumer groups not supported"));
+        }
+        let topic_id: Uuid = checked.as_topic();
+        let name: String = self.topic2table.id2name(topic_id);
+        let prev: i64 = checked.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let (remaining, age_us, newest) = self
+            .pool
+            .run(move |conn: &mut Connection| Self::lag(name.as_str(), prev, conn))
+            .await?;
+        let reply = LagResponse {
+            remaining,
+            oldest_age: age_us.and_then(|us| micros2dur(us).try_into().ok()),
+            newest,
+        };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn count_svc_new<T>(pool: &Pool, topic2table: T) -> impl CountService
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    Svc {
+        pool: pool.clone(),
+        topic2table,
+    }
+}
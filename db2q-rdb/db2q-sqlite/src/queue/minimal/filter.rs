@@ -0,0 +1,45 @@
+use db2q::queue::cmd::filter::{Condition, Filter};
+use db2q::queue::cmd::headers::{encode_name, encode_one};
+
+// the encoded headers have the bytes(may be a part of another header)
+fn contains(n: usize) -> String {
+    format!("INSTR(headers, ?{n}) > 0")
+}
+
+fn prefixed(column: &str, n: usize) -> String {
+    format!("SUBSTR({column}, 1, LENGTH(?{n})) = ?{n}")
+}
+
+// AND-ed predicate using placeholders from ?first
+// header conditions only narrow the rows: the items must be matched again
+pub fn filter2sql(filter: Option<&Filter>, first: usize) -> (String, Vec<Vec<u8>>) {
+    let mut params: Vec<Vec<u8>> = Vec::new();
+    let mut preds: Vec<String> = Vec::new();
+    let conditions: &[Condition] = filter.map(|f| f.as_conditions()).unwrap_or_default();
+    for c in conditions {
+        let n: usize = first + params.len();
+        match c {
+            Condition::HeaderEq { name, value } => {
+                preds.push(contains(n));
+                params.push(encode_one(name, value));
+            }
+            Condition::HeaderPrefix { name, .. } => {
+                preds.push(contains(n));
+                params.push(encode_name(name));
+            }
+            Condition::HeaderIn { name, values } => {
+                let any: Vec<String> = (n..n + values.len()).map(contains).collect();
+                preds.push(format!("({})", any.join(" OR ")));
+                params.extend(values.iter().map(|v| encode_one(name, v)));
+            }
+            Condition::ValuePrefix(prefix) => {
+                preds.push(prefixed("val", n));
+                params.push(prefix.clone());
+            }
+        }
+    }
+    match preds.is_empty() {
+        true => ("TRUE".into(), params),
+        false => (preds.join(" AND "), params),
+    }
+}
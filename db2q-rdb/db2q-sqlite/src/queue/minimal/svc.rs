@@ -0,0 +1,1264 @@
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::sync::futures::Notified;
+use tokio::sync::{mpsc, Notify};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use rusqlite::{Connection, OptionalExtension, Row, Rows, ToSql, Transaction};
+
+use db2q::queue::cmd::ack::AckReq;
+use db2q::queue::cmd::count::CountReq;
+use db2q::queue::cmd::extend_lease::ExtendLeaseReq;
+use db2q::queue::cmd::filter::{matches, Filter};
+use db2q::queue::cmd::headers;
+use db2q::queue::cmd::keys::KeysReq;
+use db2q::queue::cmd::lease::LeaseReq;
+use db2q::queue::cmd::nack::NackReq;
+use db2q::queue::cmd::next::NextReq;
+use db2q::queue::cmd::pop::PopFrontReq;
+use db2q::queue::cmd::push::{PushBackReq, DEDUP_WINDOW};
+use db2q::queue::cmd::push_batch::PushBatchReq;
+use db2q::queue::cmd::redrive::RedriveReq;
+use db2q::queue::cmd::report_failure::ReportFailureReq;
+use db2q::queue::cmd::subscribe::SubscribeReq;
+use db2q::queue::cmd::wait_next::WaitNextReq;
+use db2q::uuid::Uuid;
+
+use db2q::db2q::proto::queue::v1::q_svc::{AckRequest, AckResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{CountRequest, CountResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{ExtendLeaseRequest, ExtendLeaseResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{KeysRequest, KeysResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{LeaseRequest, LeaseResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{NackRequest, NackResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{NextRequest, NextResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{PopFrontRequest, PopFrontResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{PushBackRequest, PushBackResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{PushBatchRequest, PushBatchResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{RedriveRequest, RedriveResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{ReportFailureRequest, ReportFailureResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{SubscribeRequest, SubscribeResponse};
+use db2q::db2q::proto::queue::v1::q_svc::{WaitNextRequest, WaitNextResponse};
+use db2q::db2q::proto::queue::v1::queue_service_server::QueueService;
+
+use super::filter::filter2sql;
+use super::topic2table::Topic2Table;
+
+use crate::common::minimal::catalog::{self, PUSH_DEDUP, TOPIC_CONFIG};
+use crate::common::minimal::notifier::Notifier;
+use crate::common::minimal::pool::{commit, push2status, sqlite2status, tx_immediate, Pool};
+use crate::common::minimal::time::{dur2micros, micros2time, now_micros, time2micros};
+
+const ITEM_COLUMNS: &str = "key, priority, val, headers, attempts, last_error";
+
+// a visible message(ITEM_COLUMNS)
+struct Item {
+    key: i64,
+    priority: i32,
+    val: Vec<u8>,
+    headers: HashMap<String, Vec<u8>>,
+    attempts: i64,
+    last_error: Option<String>,
+}
+
+impl Item {
+    #[allow(clippy::result_large_err)]
+    fn from_row(row: &Row) -> Result<Self, Status> {
+        let col = |e| sqlite2status(e, "Unable to get an item");
+        let encoded: Vec<u8> = row.get(3).map_err(col)?;
+        Ok(Self {
+            key: row.get(0).map_err(col)?,
+            priority: row.get(1).map_err(col)?,
+            val: row.get(2).map_err(col)?,
+            headers: headers::decode(&encoded)?,
+            attempts: row.get(4).map_err(col)?,
+            last_error: row.get(5).map_err(col)?,
+        })
+    }
+
+    // the first item which matches the filter(the pushed down one narrows the rows only)
+    #[allow(clippy::result_large_err)]
+    fn first_match(rows: &mut Rows, filter: Option<&Filter>) -> Result<Option<Self>, Status> {
+        while let Some(row) = rows
+            .next()
+            .map_err(|e| sqlite2status(e, "Unable to select"))?
+        {
+            let item: Self = Self::from_row(row)?;
+            if matches(filter, &item.val, &item.headers) {
+                return Ok(Some(item));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl From<Item> for NextResponse {
+    fn from(i: Item) -> Self {
+        Self {
+            next: i.key,
+            value: i.val,
+            priority: i.priority,
+            headers: i.headers,
+            attempts: i.attempts as u64,
+            last_error: i.last_error.unwrap_or_default(),
+        }
+    }
+}
+
+fn groups_unsupported() -> Status {
+    Status::unimplemented("consumer groups not supported")
+}
+
+pub struct Svc<T> {
+    pool: Pool,
+    topic2table: T,
+    notifier: Arc<Notifier>,
+}
+
+impl<T> Svc<T>
+where
+    T: Send + Sync + 'static,
+{
+    async fn pushed_or_elapsed(notified: Pin<&mut Notified<'_>>, interval: Duration) {
+        match tokio::time::timeout(interval, notified).await {
+            Ok(_) => {}
+            Err(_) => log::debug!("no notification. polling..."),
+        }
+    }
+
+    // the original push if the request id is within the window
+    #[allow(clippy::result_large_err)]
+    fn push(
+        checked_name: &str,
+        conn: &Connection,
+        checked: &PushBackReq,
+    ) -> Result<(i64, i64), Status> {
+        let request_id: String = checked.as_request_id().to_string();
+        let now: i64 = now_micros();
+        let query = format!(
+            r#"
+                SELECT
+                    key,
+                    pushed
+                FROM {PUSH_DEDUP}
+                WHERE
+                    name = ?1
+                    AND req_id = ?2
+                    AND ?3 <= pushed
+            "#
+        );
+        let expired: i64 = now.saturating_sub(dur2micros(DEDUP_WINDOW));
+        let opushed: Option<(i64, i64)> = conn
+            .query_row(
+                &query,
+                rusqlite::params![checked_name, request_id, expired],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| sqlite2status(e, "Unable to select"))?;
+        if let Some(pushed) = opushed {
+            return Ok(pushed);
+        }
+        let query = format!(
+            r#"
+                INSERT INTO {checked_name} (
+                    val,
+                    pushed,
+                    visible_at,
+                    expires_at,
+                    priority,
+                    headers
+                )
+                VALUES (
+                    ?1,
+                    ?2,
+                    COALESCE(?3, ?2 + COALESCE(?4, 0)),
+                    ?2 + COALESCE(
+                        ?5,
+                        (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = ?6)
+                    ),
+                    ?7,
+                    ?8
+                )
+                RETURNING key
+            "#
+        );
+        let not_before: Option<i64> = checked.as_not_before().map(time2micros);
+        let delay_us: Option<i64> = checked.as_delay().map(dur2micros);
+        let ttl_us: Option<i64> = checked.as_ttl().map(dur2micros);
+        let encoded: Vec<u8> = headers::encode(checked.as_headers());
+        let key: i64 = conn
+            .query_row(
+                &query,
+                rusqlite::params![
+                    checked.as_value(),
+                    now,
+                    not_before,
+                    delay_us,
+                    ttl_us,
+                    checked_name,
+                    checked.as_priority(),
+                    encoded,
+                ],
+                |row| row.get(0),
+            )
+            .map_err(|e| push2status(e, "Unable to insert"))?;
+        let query = format!(
+            r#"
+                INSERT INTO {PUSH_DEDUP} (
+                    name,
+                    req_id,
+                    key,
+                    pushed
+                )
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (name, req_id) DO UPDATE
+                SET
+                    key = excluded.key,
+                    pushed = excluded.pushed
+            "#
+        );
+        conn.execute(
+            &query,
+            rusqlite::params![checked_name, request_id, key, now],
+        )
+        .map_err(|e| sqlite2status(e, "Unable to record a request id"))?;
+        Ok((key, now))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn push_batch(
+        checked_name: &str,
+        conn: &Connection,
+        vals: &[Vec<u8>],
+    ) -> Result<(Vec<i64>, Option<i64>), Status> {
+        let query = format!(
+            r#"
+                INSERT INTO {checked_name} (
+                    val,
+                    pushed,
+                    visible_at,
+                    expires_at
+                )
+                VALUES (
+                    ?1,
+                    ?2,
+                    ?2,
+                    ?2 + (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = ?3)
+                )
+                RETURNING key
+            "#
+        );
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| push2status(e, "Unable to insert"))?;
+        let now: i64 = now_micros();
+        // keys are assigned in the order of the values
+        let keys: Vec<i64> = vals
+            .iter()
+            .map(|v: &Vec<u8>| {
+                stmt.query_row(rusqlite::params![v, now, checked_name], |row| row.get(0))
+                    .map_err(|e| push2status(e, "Unable to insert"))
+            })
+            .collect::<Result<_, _>>()?;
+        let pushed: Option<i64> = (!keys.is_empty()).then_some(now);
+        Ok((keys, pushed))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn count(checked_name: &str, conn: &Connection) -> Result<u64, Status> {
+        let query = format!(
+            r#"
+                SELECT
+                    COUNT(*) AS cnt
+                FROM {checked_name}
+                WHERE expires_at IS NULL OR expires_at > ?1
+            "#
+        );
+        let cnt: i64 = conn
+            .query_row(&query, [now_micros()], |row| row.get(0))
+            .map_err(|e| sqlite2status(e, "Unable to count"))?;
+        Ok(cnt as u64)
+    }
+
+    // filters are applied while scanning in key order
+    #[allow(clippy::result_large_err)]
+    fn next(
+        checked_name: &str,
+        prev: Option<i64>,
+        filter: Option<&Filter>,
+        conn: &Connection,
+    ) -> Result<Item, Status> {
+        let (pred, fparams) = filter2sql(filter, 3);
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    key > ?1
+                    AND visible_at <= ?2
+                    AND (expires_at IS NULL OR expires_at > ?2)
+                    AND {pred}
+                ORDER BY key
+            "#
+        );
+        let after: i64 = prev.unwrap_or(-1);
+        let now: i64 = now_micros();
+        let mut params: Vec<&dyn ToSql> = vec![&after, &now];
+        params.extend(fparams.iter().map(|p| p as &dyn ToSql));
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| sqlite2status(e, "Unable to select"))?;
+        let mut rows = stmt
+            .query(params.as_slice())
+            .map_err(|e| sqlite2status(e, "Unable to select"))?;
+        Item::first_match(&mut rows, filter)?.ok_or_else(|| match prev {
+            None => Status::not_found("Empty queue"),
+            Some(prev) => Status::not_found(format!("No more queue items. previous key: {prev}")),
+        })
+    }
+
+    // (priority desc, key) order
+    #[allow(clippy::result_large_err)]
+    fn next_prioritized(
+        checked_name: &str,
+        prev: Option<(i32, i64)>,
+        filter: Option<&Filter>,
+        conn: &Connection,
+    ) -> Result<Item, Status> {
+        let (pred, fparams) = filter2sql(filter, 4);
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    (
+                        ?1 IS NULL
+                        OR priority < ?1
+                        OR (priority = ?1 AND key > ?2)
+                    )
+                    AND visible_at <= ?3
+                    AND (expires_at IS NULL OR expires_at > ?3)
+                    AND {pred}
+                ORDER BY priority DESC, key
+            "#
+        );
+        let prev_priority: Option<i32> = prev.map(|p| p.0);
+        let prev_key: Option<i64> = prev.map(|p| p.1);
+        let now: i64 = now_micros();
+        let mut params: Vec<&dyn ToSql> = vec![&prev_priority, &prev_key, &now];
+        params.extend(fparams.iter().map(|p| p as &dyn ToSql));
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| sqlite2status(e, "Unable to select"))?;
+        let mut rows = stmt
+            .query(params.as_slice())
+            .map_err(|e| sqlite2status(e, "Unable to select"))?;
+        Item::first_match(&mut rows, filter)?
+            .ok_or_else(|| Status::not_found(format!("No more queue items. previous: {prev:?}")))
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub async fn wait_next(
+        &self,
+        checked_name: &str,
+        req: WaitNextReq,
+    ) -> Result<ReceiverStream<Result<WaitNextResponse, Status>>, Status> {
+        if req.as_group().is_some() {
+            return Err(groups_unsupported());
+        }
+        let prev: Option<i64> = req.as_previous_key().map(|u| u as i64);
+        let start: Instant = Instant::now();
+        let notify: Arc<Notify> = self.notifier.watch(checked_name);
+        let interval: Duration = req.as_interval();
+        let (tx, rx) = mpsc::channel(1);
+        let name: String = checked_name.into();
+        let pool: Pool = self.pool.clone();
+        let timeout: Duration = req.as_timeout();
+        let filter: Option<Arc<Filter>> = req.as_filter().cloned().map(Arc::new);
+        tokio::spawn(async move {
+            let mut retry_cnt: u64 = 0;
+            loop {
+                // registered before checking to avoid missing a notification
+                let notified = notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let n: String = name.clone();
+                let f: Option<Arc<Filter>> = filter.clone();
+                let rslt: Result<Item, Status> = pool
+                    .run(move |conn: &mut Connection| {
+                        Self::next(n.as_str(), prev, f.as_deref(), conn)
+                    })
+                    .await;
+                match rslt {
+                    Ok(item) => {
+                        let elapsed: Duration = start.elapsed();
+                        let reply = WaitNextResponse {
+                            next: Some(item.into()),
+                            elapsed: elapsed.try_into().ok(),
+                            retried: retry_cnt,
+                        };
+                        match tx.send(Ok(reply)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        };
+                        return;
+                    }
+                    Err(e) => match e.code() {
+                        Code::NotFound => {}
+                        _ => {
+                            match tx.send(Err(e)).await {
+                                Ok(_) => {}
+                                Err(e) => log::warn!("Unable to send: {e}"),
+                            }
+                            return;
+                        }
+                    },
+                }
+                let remaining: Duration = timeout.saturating_sub(start.elapsed());
+                if remaining.is_zero() {
+                    let e = Status::deadline_exceeded(format!(
+                        "timeout. table={name}, retried={retry_cnt}"
+                    ));
+                    match tx.send(Err(e)).await {
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Unable to send: {e}"),
+                    }
+                    return;
+                }
+                Self::pushed_or_elapsed(notified.as_mut(), interval.min(remaining)).await;
+                retry_cnt += 1;
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn pop(checked_name: &str, conn: &Connection, priority_order: bool) -> Result<Item, Status> {
+        let order: &str = match priority_order {
+            true => "priority DESC, key",
+            false => "key",
+        };
+        // a single statement: no other writer between the select and the delete
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE key = (
+                    SELECT key
+                    FROM {checked_name}
+                    WHERE
+                        visible_at <= ?1
+                        AND (expires_at IS NULL OR expires_at > ?1)
+                        AND (leased_until IS NULL OR leased_until <= ?1)
+                    ORDER BY {order}
+                    LIMIT 1
+                )
+                RETURNING {ITEM_COLUMNS}
+            "#
+        );
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| sqlite2status(e, "Unable to delete"))?;
+        let mut rows = stmt
+            .query([now_micros()])
+            .map_err(|e| sqlite2status(e, "Unable to delete"))?;
+        Item::first_match(&mut rows, None)?.ok_or_else(|| Status::not_found("Empty queue"))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn lease(
+        checked_name: &str,
+        conn: &Connection,
+        lease_id: Uuid,
+        timeout: Duration,
+    ) -> Result<(Item, SystemTime), Status> {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = ?1 + ?2,
+                    lease_id = ?3
+                WHERE key = (
+                    SELECT key
+                    FROM {checked_name}
+                    WHERE
+                        visible_at <= ?1
+                        AND (expires_at IS NULL OR expires_at > ?1)
+                        AND (leased_until IS NULL OR leased_until <= ?1)
+                    ORDER BY key
+                    LIMIT 1
+                )
+                RETURNING {ITEM_COLUMNS}, leased_until
+            "#
+        );
+        let micros: i64 = timeout
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("visibility timeout too large: {e}")))?;
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| sqlite2status(e, "Unable to lease"))?;
+        let mut rows = stmt
+            .query(rusqlite::params![
+                now_micros(),
+                micros,
+                lease_id.to_string()
+            ])
+            .map_err(|e| sqlite2status(e, "Unable to lease"))?;
+        let row = rows
+            .next()
+            .map_err(|e| sqlite2status(e, "Unable to lease"))?
+            .ok_or_else(|| Status::not_found("No visible queue items"))?;
+        let leased: Item = Item::from_row(row)?;
+        let deadline: i64 = row
+            .get(6)
+            .map_err(|e| sqlite2status(e, "Unable to get a deadline"))?;
+        Ok((leased, micros2time(deadline)))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn ack(checked_name: &str, conn: &Connection, key: i64, lease_id: Uuid) -> Result<(), Status> {
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE
+                    key = ?1
+                    AND lease_id = ?2
+                    AND leased_until > ?3
+            "#
+        );
+        let cnt: usize = conn
+            .execute(
+                &query,
+                rusqlite::params![key, lease_id.to_string(), now_micros()],
+            )
+            .map_err(|e| sqlite2status(e, "Unable to ack"))?;
+        match cnt {
+            0 => Err(Status::not_found(format!(
+                "No such lease(expired?). key: {key}, lease id: {lease_id}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn nack(
+        checked_name: &str,
+        conn: &Connection,
+        key: i64,
+        lease_id: Uuid,
+        delay: Option<Duration>,
+    ) -> Result<(), Status> {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = ?3 + ?4,
+                    lease_id = NULL
+                WHERE
+                    key = ?1
+                    AND lease_id = ?2
+                    AND leased_until > ?3
+            "#
+        );
+        let micros: i64 = delay
+            .unwrap_or_default()
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("delay too large: {e}")))?;
+        let cnt: usize = conn
+            .execute(
+                &query,
+                rusqlite::params![key, lease_id.to_string(), now_micros(), micros],
+            )
+            .map_err(|e| sqlite2status(e, "Unable to nack"))?;
+        match cnt {
+            0 => Err(Status::not_found(format!(
+                "No such lease(expired?). key: {key}, lease id: {lease_id}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn extend_lease(
+        checked_name: &str,
+        conn: &Connection,
+        key: i64,
+        lease_id: Uuid,
+        extension: Duration,
+    ) -> Result<SystemTime, Status> {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    leased_until = ?3 + ?4
+                WHERE
+                    key = ?1
+                    AND lease_id = ?2
+                    AND leased_until > ?3
+                RETURNING leased_until
+            "#
+        );
+        let micros: i64 = extension
+            .as_micros()
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("extension too large: {e}")))?;
+        let deadline: i64 = conn
+            .query_row(
+                &query,
+                rusqlite::params![key, lease_id.to_string(), now_micros(), micros],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| sqlite2status(e, "Unable to extend a lease"))?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No such lease(expired?). key: {key}, lease id: {lease_id}"
+                ))
+            })?;
+        Ok(micros2time(deadline))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn fail(checked_name: &str, conn: &Connection, key: i64, error: &str) -> Result<u64, Status> {
+        let query = format!(
+            r#"
+                UPDATE {checked_name}
+                SET
+                    attempts = attempts + 1,
+                    last_error = ?2
+                WHERE key = ?1
+                RETURNING attempts
+            "#
+        );
+        let attempts: i64 = conn
+            .query_row(&query, rusqlite::params![key, error], |row| row.get(0))
+            .optional()
+            .map_err(|e| sqlite2status(e, "Unable to record a failure"))?
+            .ok_or_else(|| Status::not_found(format!("No such queue item. key: {key}")))?;
+        Ok(attempts as u64)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn dead_letter(
+        checked_name: &str,
+        dlq_name: &str,
+        conn: &Connection,
+        key: i64,
+    ) -> Result<usize, Status> {
+        let query = format!(
+            r#"
+                INSERT INTO {dlq_name} (
+                    val,
+                    attempts,
+                    last_error,
+                    priority,
+                    headers,
+                    pushed,
+                    visible_at,
+                    expires_at
+                )
+                SELECT
+                    val,
+                    attempts,
+                    last_error,
+                    priority,
+                    headers,
+                    ?2,
+                    ?2,
+                    ?2 + (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = ?3)
+                FROM {checked_name}
+                WHERE key = ?1
+            "#
+        );
+        conn.execute(&query, rusqlite::params![key, now_micros(), dlq_name])
+            .map_err(|e| sqlite2status(e, "Unable to move to a dead letter topic"))?;
+        let query = format!(
+            r#"
+                DELETE FROM {checked_name}
+                WHERE key = ?1
+            "#
+        );
+        conn.execute(&query, [key])
+            .map_err(|e| sqlite2status(e, "Unable to move to a dead letter topic"))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn redrive(
+        checked_name: &str,
+        dlq_name: &str,
+        conn: &Connection,
+        limit: Option<i64>,
+    ) -> Result<usize, Status> {
+        let query = format!(
+            r#"
+                INSERT INTO {checked_name} (
+                    val,
+                    priority,
+                    headers,
+                    pushed,
+                    visible_at,
+                    expires_at
+                )
+                SELECT
+                    val,
+                    priority,
+                    headers,
+                    ?2,
+                    ?2,
+                    ?2 + (SELECT default_ttl FROM {TOPIC_CONFIG} WHERE name = ?3)
+                FROM {dlq_name}
+                ORDER BY key
+                LIMIT COALESCE(?1, -1)
+            "#
+        );
+        let redriven: usize = conn
+            .execute(&query, rusqlite::params![limit, now_micros(), checked_name])
+            .map_err(|e| sqlite2status(e, "Unable to redrive"))?;
+        let query = format!(
+            r#"
+                DELETE FROM {dlq_name}
+                WHERE key IN (
+                    SELECT key
+                    FROM {dlq_name}
+                    ORDER BY key
+                    LIMIT ?1
+                )
+            "#
+        );
+        conn.execute(&query, [redriven as i64])
+            .map_err(|e| sqlite2status(e, "Unable to redrive"))?;
+        Ok(redriven)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn next_batch(
+        checked_name: &str,
+        prev: i64,
+        limit: i64,
+        conn: &Connection,
+    ) -> Result<Vec<Item>, Status> {
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    key > ?1
+                    AND visible_at <= ?3
+                    AND (expires_at IS NULL OR expires_at > ?3)
+                ORDER BY key
+                LIMIT ?2
+            "#
+        );
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| sqlite2status(e, "Unable to select"))?;
+        let mut rows = stmt
+            .query(rusqlite::params![prev, limit, now_micros()])
+            .map_err(|e| sqlite2status(e, "Unable to select"))?;
+        let mut items: Vec<Item> = Vec::new();
+        while let Some(item) = Item::first_match(&mut rows, None)? {
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub async fn subscribe(
+        &self,
+        checked_name: &str,
+        req: SubscribeReq,
+    ) -> Result<ReceiverStream<Result<SubscribeResponse, Status>>, Status> {
+        let mut prev: i64 = req.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let batch_size: u64 = req.as_batch_size();
+        let limit: i64 = batch_size as i64;
+        let notify: Arc<Notify> = self.notifier.watch(checked_name);
+        let interval: Duration = req.as_interval();
+        // bounded: a slow client stops the polling instead of buffering
+        let (tx, rx) = mpsc::channel(batch_size as usize);
+        let name: String = checked_name.into();
+        let pool: Pool = self.pool.clone();
+        tokio::spawn(async move {
+            loop {
+                let notified = notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let n: String = name.clone();
+                let rslt: Result<Vec<Item>, Status> = pool
+                    .run(move |conn: &mut Connection| {
+                        Self::next_batch(n.as_str(), prev, limit, conn)
+                    })
+                    .await;
+                let items: Vec<Item> = match rslt {
+                    Ok(items) => items,
+                    Err(e) => {
+                        match tx.send(Err(e)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        }
+                        return;
+                    }
+                };
+                if items.is_empty() {
+                    tokio::select! {
+                        _ = Self::pushed_or_elapsed(notified.as_mut(), interval) => {},
+                        _ = tx.closed() => { return },
+                    };
+                    continue;
+                }
+                for item in items {
+                    prev = item.key;
+                    let reply = SubscribeResponse {
+                        next: Some(item.into()),
+                    };
+                    match tx.send(Ok(reply)).await {
+                        Ok(_) => {}
+                        Err(_) => return, // cancelled
+                    }
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+
+    // one more key is found to know whether the page is full
+    #[allow(clippy::result_large_err)]
+    fn keys(
+        checked_name: &str,
+        conn: &Connection,
+        checked: &KeysReq,
+    ) -> Result<Vec<KeysResponse>, Status> {
+        let (lower, upper, order) = match checked.as_descending() {
+            false => ("?1", "?2", "ASC"),
+            true => ("?2", "?1", "DESC"),
+        };
+        let (pred, fparams) = filter2sql(checked.as_filter(), 4);
+        let query = format!(
+            r#"
+                SELECT {ITEM_COLUMNS}
+                FROM {checked_name}
+                WHERE
+                    key > COALESCE({lower}, 0)
+                    AND key < COALESCE({upper}, 9223372036854775807)
+                    AND visible_at <= ?3
+                    AND (expires_at IS NULL OR expires_at > ?3)
+                    AND {pred}
+                ORDER BY key {order}
+            "#
+        );
+        let limit: u64 = checked.as_max_keys().min(i64::MAX as u64 - 1);
+        let start_after: Option<i64> = checked.as_start_after().map(|u| u as i64);
+        let end_before: Option<i64> = checked.as_end_before().map(|u| u as i64);
+        let now: i64 = now_micros();
+        let mut params: Vec<&dyn ToSql> = vec![&start_after, &end_before, &now];
+        params.extend(fparams.iter().map(|p| p as &dyn ToSql));
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|e| sqlite2status(e, "Unable to get keys"))?;
+        let mut rows = stmt
+            .query(params.as_slice())
+            .map_err(|e| sqlite2status(e, "Unable to get keys"))?;
+        let mut keys: Vec<KeysResponse> = Vec::new();
+        while let Some(item) = Item::first_match(&mut rows, checked.as_filter())? {
+            if limit <= keys.len() as u64 {
+                if let Some(last) = keys.last_mut() {
+                    last.continuation = last.key as i64;
+                }
+                break;
+            }
+            keys.push(KeysResponse {
+                key: item.key as u64,
+                continuation: -1,
+            });
+        }
+        Ok(keys)
+    }
+}
+
+#[tonic::async_trait]
+impl<T> QueueService for Svc<T>
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    #[allow(clippy::result_large_err)]
+    async fn push_back(
+        &self,
+        req: Request<PushBackRequest>,
+    ) -> Result<Response<PushBackResponse>, Status> {
+        let pbr: PushBackRequest = req.into_inner();
+        let checked: PushBackReq = pbr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let n: String = name.clone();
+        // keys are committed in key order: the write lock is held until the end
+        let (key, pushed) = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                let tx: Transaction = tx_immediate(conn)?;
+                let pushed = Self::push(n.as_str(), &tx, &checked)?;
+                commit(tx)?;
+                Ok(pushed)
+            })
+            .await?;
+        self.notifier.wake(&name);
+        let reply = PushBackResponse {
+            pushed: Some(micros2time(pushed).into()),
+            key,
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn push_batch(
+        &self,
+        req: Request<PushBatchRequest>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let pbr: PushBatchRequest = req.into_inner();
+        let checked: PushBatchReq = pbr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let n: String = name.clone();
+        let (keys, pushed) = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                let tx: Transaction = tx_immediate(conn)?;
+                let pushed = Self::push_batch(n.as_str(), &tx, checked.as_values())?;
+                commit(tx)?;
+                Ok(pushed)
+            })
+            .await?;
+        self.notifier.wake(&name);
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: i64| micros2time(t).into()),
+            keys,
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn push_batch_stream(
+        &self,
+        req: Request<Streaming<PushBatchRequest>>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let mut chunks: Streaming<PushBatchRequest> = req.into_inner();
+        let first: PushBatchRequest = chunks
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("empty batch stream"))?;
+        let mut checked: PushBatchReq = first.try_into()?;
+        while let Some(chunk) = chunks.message().await? {
+            let next: PushBatchReq = chunk.try_into()?;
+            checked.append(next)?;
+        }
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let n: String = name.clone();
+        let (keys, pushed) = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                let tx: Transaction = tx_immediate(conn)?;
+                let pushed = Self::push_batch(n.as_str(), &tx, checked.as_values())?;
+                commit(tx)?;
+                Ok(pushed)
+            })
+            .await?;
+        self.notifier.wake(&name);
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: i64| micros2time(t).into()),
+            keys,
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn pop_front(
+        &self,
+        req: Request<PopFrontRequest>,
+    ) -> Result<Response<PopFrontResponse>, Status> {
+        let pfr: PopFrontRequest = req.into_inner();
+        let checked: PopFrontReq = pfr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let priority_order: bool = checked.as_priority_order();
+        let item: Item = self
+            .pool
+            .run(move |conn: &mut Connection| Self::pop(&name, conn, priority_order))
+            .await?;
+        let popped: SystemTime = SystemTime::now();
+        let reply = PopFrontResponse {
+            popped: Some(popped.into()),
+            value: item.val,
+            headers: item.headers,
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn count(&self, req: Request<CountRequest>) -> Result<Response<CountResponse>, Status> {
+        let cr: CountRequest = req.into_inner();
+        let checked: CountReq = cr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let cnt: u64 = self
+            .pool
+            .run(move |conn: &mut Connection| Self::count(&name, conn))
+            .await?;
+        let reply = CountResponse { count: cnt };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn next(&self, req: Request<NextRequest>) -> Result<Response<NextResponse>, Status> {
+        let nr: NextRequest = req.into_inner();
+        let checked: NextReq = (&nr).try_into()?;
+        if checked.as_group().is_some() {
+            return Err(groups_unsupported());
+        }
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let item: Item = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                let prev_key: Option<i64> = checked.as_previous_key().map(|u| u as i64);
+                let filter: Option<&Filter> = checked.as_filter();
+                match checked.as_priority_order() {
+                    false => Self::next(name.as_str(), prev_key, filter, conn),
+                    true => {
+                        let prev: Option<(i32, i64)> =
+                            prev_key.map(|k| (checked.as_previous_priority(), k));
+                        Self::next_prioritized(name.as_str(), prev, filter, conn)
+                    }
+                }
+            })
+            .await?;
+        let reply: NextResponse = item.into();
+        Ok(Response::new(reply))
+    }
+
+    type WaitNextStream = ReceiverStream<Result<WaitNextResponse, Status>>;
+
+    async fn wait_next(
+        &self,
+        req: Request<WaitNextRequest>,
+    ) -> Result<Response<Self::WaitNextStream>, Status> {
+        let wnr: WaitNextRequest = req.into_inner();
+        let checked: WaitNextReq = (&wnr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let reply: Self::WaitNextStream = self.wait_next(name.as_str(), checked).await?;
+        Ok(Response::new(reply))
+    }
+
+    type SubscribeStream = ReceiverStream<Result<SubscribeResponse, Status>>;
+
+    async fn subscribe(
+        &self,
+        req: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let sr: SubscribeRequest = req.into_inner();
+        let checked: SubscribeReq = (&sr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let reply: Self::SubscribeStream = self.subscribe(name.as_str(), checked).await?;
+        Ok(Response::new(reply))
+    }
+
+    type KeysStream = ReceiverStream<Result<KeysResponse, Status>>;
+
+    #[allow(clippy::result_large_err)]
+    async fn keys(&self, req: Request<KeysRequest>) -> Result<Response<Self::KeysStream>, Status> {
+        let kr: KeysRequest = req.into_inner();
+        let checked: KeysReq = (&kr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let keys: Vec<KeysResponse> = self
+            .pool
+            .run(move |conn: &mut Connection| Self::keys(name.as_str(), conn, &checked))
+            .await?;
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            for key in keys {
+                match tx.send(Ok(key)).await {
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("Error while sending keys: {e}");
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn lease(&self, req: Request<LeaseRequest>) -> Result<Response<LeaseResponse>, Status> {
+        let lr: LeaseRequest = req.into_inner();
+        let checked: LeaseReq = (&lr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let lease_id: Uuid = Uuid::new_v4();
+        let timeout: Duration = checked.as_visibility_timeout();
+        let (item, deadline) = self
+            .pool
+            .run(move |conn: &mut Connection| Self::lease(&name, conn, lease_id, timeout))
+            .await?;
+        let reply = LeaseResponse {
+            key: item.key,
+            value: item.val,
+            lease_id: Some(lease_id.into()),
+            deadline: Some(deadline.into()),
+            headers: item.headers,
+            attempts: item.attempts as u64,
+            last_error: item.last_error.unwrap_or_default(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn ack(&self, req: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        let ar: AckRequest = req.into_inner();
+        let checked: AckReq = (&ar).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let lease_id: Uuid = checked.as_lease_id();
+        self.pool
+            .run(move |conn: &mut Connection| Self::ack(&name, conn, key, lease_id))
+            .await?;
+        let acked: SystemTime = SystemTime::now();
+        let reply = AckResponse {
+            acked: Some(acked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn nack(&self, req: Request<NackRequest>) -> Result<Response<NackResponse>, Status> {
+        let nr: NackRequest = req.into_inner();
+        let checked: NackReq = (&nr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let lease_id: Uuid = checked.as_lease_id();
+        let delay: Option<Duration> = checked.as_delay();
+        self.pool
+            .run(move |conn: &mut Connection| Self::nack(&name, conn, key, lease_id, delay))
+            .await?;
+        let nacked: SystemTime = SystemTime::now();
+        let reply = NackResponse {
+            nacked: Some(nacked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn extend_lease(
+        &self,
+        req: Request<ExtendLeaseRequest>,
+    ) -> Result<Response<ExtendLeaseResponse>, Status> {
+        let er: ExtendLeaseRequest = req.into_inner();
+        let checked: ExtendLeaseReq = (&er).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let lease_id: Uuid = checked.as_lease_id();
+        let extension: Duration = checked.as_extension();
+        let deadline: SystemTime = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                Self::extend_lease(&name, conn, key, lease_id, extension)
+            })
+            .await?;
+        let reply = ExtendLeaseResponse {
+            deadline: Some(deadline.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn report_failure(
+        &self,
+        req: Request<ReportFailureRequest>,
+    ) -> Result<Response<ReportFailureResponse>, Status> {
+        let rfr: ReportFailureRequest = req.into_inner();
+        let checked: ReportFailureReq = rfr.try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let key: i64 = checked.as_key() as i64;
+        let (attempts, dead_lettered) = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                let tx: Transaction = tx_immediate(conn)?;
+                let attempts: u64 = Self::fail(&name, &tx, key, checked.as_error())?;
+                let dead_lettered: Option<String> = match catalog::dead_letter(&name, &tx)? {
+                    Some((dlq, max_attempts)) if max_attempts <= attempts => {
+                        Self::dead_letter(&name, &dlq, &tx, key)?;
+                        Some(dlq)
+                    }
+                    _ => None,
+                };
+                commit(tx)?;
+                Ok((attempts, dead_lettered))
+            })
+            .await?;
+        if let Some(dlq) = &dead_lettered {
+            self.notifier.wake(dlq);
+        }
+        let reply = ReportFailureResponse {
+            attempts,
+            dead_lettered: dead_lettered.is_some(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn redrive(
+        &self,
+        req: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        let rr: RedriveRequest = req.into_inner();
+        let checked: RedriveReq = (&rr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic2table.id2name(topic_id);
+        let limit: Option<i64> = checked
+            .as_max_messages()
+            .map(|u| u.min(i64::MAX as u64) as i64);
+        let n: String = name.clone();
+        let redriven: usize = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                let tx: Transaction = tx_immediate(conn)?;
+                let (dlq, _) = catalog::dead_letter(&n, &tx)?.ok_or_else(|| {
+                    Status::failed_precondition(format!(
+                        "No dead letter topic configured: {topic_id}"
+                    ))
+                })?;
+                let redriven: usize = Self::redrive(&n, &dlq, &tx, limit)?;
+                commit(tx)?;
+                Ok(redriven)
+            })
+            .await?;
+        self.notifier.wake(&name);
+        let reply = RedriveResponse {
+            redriven: redriven as u64,
+        };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn queue_svc_new<T>(pool: &Pool, topic2table: T) -> impl QueueService
+where
+    T: Send + Sync + 'static + Topic2Table,
+{
+    Svc {
+        pool: pool.clone(),
+        topic2table,
+        notifier: Arc::new(Notifier::default()),
+    }
+}
@@ -0,0 +1,180 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use tokio::task::JoinHandle;
+
+use rusqlite::{Connection, Row};
+
+use crate::common::minimal::catalog::TOPIC_CONFIG;
+use crate::common::minimal::pool::{sqlite2status, Pool};
+use crate::common::minimal::time::now_micros;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+pub const BATCH_SIZE_DEFAULT: u64 = 1024;
+
+#[derive(Clone)]
+struct Limits {
+    name: String,
+    max_messages: Option<i64>,
+    max_age_us: Option<i64>,
+    max_bytes: Option<i64>,
+}
+
+#[allow(clippy::result_large_err)]
+fn limits(conn: &Connection) -> Result<Vec<Limits>, Status> {
+    let query = format!(
+        r#"
+            SELECT
+                name,
+                retain_messages,
+                retain_age,
+                retain_bytes
+            FROM {TOPIC_CONFIG}
+            WHERE
+                retain_messages IS NOT NULL
+                OR retain_age IS NOT NULL
+                OR retain_bytes IS NOT NULL
+            ORDER BY name
+        "#
+    );
+    let mut stmt = conn
+        .prepare(&query)
+        .map_err(|e| sqlite2status(e, "Unable to get retentions"))?;
+    let rows = stmt
+        .query_map([], |row: &Row| {
+            Ok(Limits {
+                name: row.get(0)?,
+                max_messages: row.get(1)?,
+                max_age_us: row.get(2)?,
+                max_bytes: row.get(3)?,
+            })
+        })
+        .map_err(|e| sqlite2status(e, "Unable to get retentions"))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| sqlite2status(e, "Unable to get a retention"))
+}
+
+// the highest key to be trimmed(if any)
+#[allow(clippy::result_large_err)]
+fn boundary(checked_name: &str, conn: &Connection, limits: &Limits) -> Result<Option<i64>, Status> {
+    // the aggregate MAX ignores NULLs(the scalar one does not)
+    let query = format!(
+        r#"
+            SELECT MAX(key)
+            FROM (
+                SELECT * FROM (
+                    SELECT key
+                    FROM {checked_name}
+                    WHERE ?1 IS NOT NULL
+                    ORDER BY key DESC
+                    LIMIT 1
+                    OFFSET ?1
+                )
+                UNION ALL
+                SELECT MAX(key) AS key
+                FROM {checked_name}
+                WHERE pushed < ?4 - ?2
+                UNION ALL
+                SELECT MAX(key) AS key
+                FROM (
+                    SELECT
+                        key,
+                        SUM(LENGTH(val)) OVER (ORDER BY key DESC) AS total
+                    FROM {checked_name}
+                    WHERE ?3 IS NOT NULL
+                )
+                WHERE total > ?3
+            )
+        "#
+    );
+    conn.query_row(
+        &query,
+        rusqlite::params![
+            limits.max_messages,
+            limits.max_age_us,
+            limits.max_bytes,
+            now_micros(),
+        ],
+        |row| row.get(0),
+    )
+    .map_err(|e| sqlite2status(e, "Unable to get a retention boundary"))
+}
+
+#[allow(clippy::result_large_err)]
+fn trim_batch(
+    checked_name: &str,
+    conn: &Connection,
+    upper: i64,
+    limit: i64,
+) -> Result<u64, Status> {
+    let query = format!(
+        r#"
+            DELETE FROM {checked_name}
+            WHERE key IN (
+                SELECT key
+                FROM {checked_name}
+                WHERE key <= ?1
+                ORDER BY key
+                LIMIT ?2
+            )
+        "#
+    );
+    conn.execute(&query, [upper, limit])
+        .map(|cnt: usize| cnt as u64)
+        .map_err(|e| sqlite2status(e, "Unable to trim a topic"))
+}
+
+// small batches; the write lock is released between batches
+#[allow(clippy::result_large_err)]
+async fn trim_topic(pool: &Pool, limits: Limits, batch_size: u64) -> Result<u64, Status> {
+    let l: Limits = limits.clone();
+    let oupper: Option<i64> = pool
+        .run(move |conn: &mut Connection| boundary(l.name.as_str(), conn, &l))
+        .await?;
+    let upper: i64 = match oupper {
+        None => return Ok(0),
+        Some(upper) => upper,
+    };
+    let limit: i64 = batch_size.clamp(1, i64::MAX as u64) as i64;
+    let mut trimmed: u64 = 0;
+    loop {
+        let l: Limits = limits.clone();
+        let deleted: u64 = pool
+            .run(move |conn: &mut Connection| trim_batch(l.name.as_str(), conn, upper, limit))
+            .await?;
+        trimmed += deleted;
+        if deleted < limit as u64 {
+            return Ok(trimmed);
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub async fn trim(pool: &Pool, batch_size: u64) -> Result<u64, Status> {
+    let all: Vec<Limits> = pool.run(|conn: &mut Connection| limits(conn)).await?;
+    let mut trimmed: u64 = 0;
+    for l in all {
+        let name: String = l.name.clone();
+        // a topic may be dropped while trimming
+        match trim_topic(pool, l, batch_size).await {
+            Ok(cnt) => trimmed += cnt,
+            Err(e) => log::warn!("Unable to trim {name}: {e}"),
+        }
+    }
+    Ok(trimmed)
+}
+
+pub fn retention_worker_new(pool: &Pool, interval: Duration, batch_size: u64) -> JoinHandle<()> {
+    let pool: Pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            match trim(&pool, batch_size).await {
+                Ok(0) => {}
+                Ok(cnt) => log::debug!("Messages trimmed: {cnt}"),
+                Err(e) => log::warn!("Unable to trim: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -0,0 +1,90 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use tokio::task::JoinHandle;
+
+use rusqlite::Connection;
+
+use db2q::queue::cmd::push::DEDUP_WINDOW;
+
+use crate::common::minimal::catalog;
+use crate::common::minimal::pool::{sqlite2status, Pool};
+use crate::common::minimal::time::now_micros;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+pub const BATCH_SIZE_DEFAULT: u64 = 1024;
+
+#[allow(clippy::result_large_err)]
+fn sweep_batch(checked_name: &str, conn: &Connection, batch_size: i64) -> Result<u64, Status> {
+    let query = format!(
+        r#"
+            DELETE FROM {checked_name}
+            WHERE key IN (
+                SELECT key
+                FROM {checked_name}
+                WHERE expires_at <= ?1
+                ORDER BY expires_at
+                LIMIT ?2
+            )
+        "#
+    );
+    conn.execute(&query, [now_micros(), batch_size])
+        .map(|cnt: usize| cnt as u64)
+        .map_err(|e| sqlite2status(e, "Unable to delete expired messages"))
+}
+
+// each batch is a short transaction(the write lock is released between batches)
+#[allow(clippy::result_large_err)]
+pub async fn sweep_topic(checked_name: &str, pool: &Pool, batch_size: u64) -> Result<u64, Status> {
+    let limit: i64 = batch_size.clamp(1, i64::MAX as u64) as i64;
+    let mut swept: u64 = 0;
+    loop {
+        let name: String = checked_name.into();
+        let deleted: u64 = pool
+            .run(move |conn: &mut Connection| sweep_batch(name.as_str(), conn, limit))
+            .await?;
+        swept += deleted;
+        if deleted < limit as u64 {
+            return Ok(swept);
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub async fn sweep(pool: &Pool, batch_size: u64) -> Result<u64, Status> {
+    let names: Vec<String> = pool
+        .run(|conn: &mut Connection| catalog::names(conn))
+        .await?;
+    let mut swept: u64 = 0;
+    match pool
+        .run(|conn: &mut Connection| catalog::sweep_dedup(conn, DEDUP_WINDOW))
+        .await
+    {
+        Ok(0) => {}
+        Ok(cnt) => log::debug!("Old request ids deleted: {cnt}"),
+        Err(e) => log::warn!("Unable to sweep request ids: {e}"),
+    }
+    for name in names {
+        // a topic may be dropped while sweeping
+        match sweep_topic(name.as_str(), pool, batch_size).await {
+            Ok(cnt) => swept += cnt,
+            Err(e) => log::warn!("Unable to sweep {name}: {e}"),
+        }
+    }
+    Ok(swept)
+}
+
+pub fn sweeper_new(pool: &Pool, interval: Duration, batch_size: u64) -> JoinHandle<()> {
+    let pool: Pool = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            match sweep(&pool, batch_size).await {
+                Ok(0) => {}
+                Ok(cnt) => log::debug!("Expired messages deleted: {cnt}"),
+                Err(e) => log::warn!("Unable to sweep: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
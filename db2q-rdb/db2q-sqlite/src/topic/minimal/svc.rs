@@ -0,0 +1,462 @@
+use core::time::Duration;
+use std::time::SystemTime;
+
+use tonic::{Request, Response, Status};
+
+use rusqlite::{Connection, Transaction};
+
+use db2q::uuid::Uuid;
+
+use db2q::topic::cmd::create::CreateReq;
+use db2q::topic::cmd::describe::DescribeReq;
+use db2q::topic::cmd::drop::DropReq;
+use db2q::topic::cmd::list::ListReq;
+use db2q::topic::cmd::retention::Retention;
+use db2q::topic::cmd::update_retention::UpdateRetentionReq;
+
+use db2q::db2q::proto::queue::v1::Uuid as Guid;
+
+use db2q::db2q::proto::queue::v1::topic_service_server::TopicService;
+use db2q::db2q::proto::queue::v1::topic_svc::{CreateRequest, CreateResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{
+    DeadLetter as DeadLetterPb, Retention as RetentionPb,
+};
+use db2q::db2q::proto::queue::v1::topic_svc::{DescribeRequest, DescribeResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{DropRequest, DropResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{ListRequest, ListResponse};
+use db2q::db2q::proto::queue::v1::topic_svc::{UpdateRetentionRequest, UpdateRetentionResponse};
+
+use crate::common::minimal::catalog::{self, TOPIC_CONFIG};
+use crate::common::minimal::pool::{commit, sqlite2status, tx_immediate, Pool};
+use crate::common::minimal::time::{dur2micros, micros2dur, micros2time, now_micros};
+use crate::topic::minimal::topic2table::TopicConv;
+
+// limits in the catalog(microseconds for the age)
+struct Limits {
+    max_messages: Option<i64>,
+    max_age_us: Option<i64>,
+    max_bytes: Option<i64>,
+}
+
+impl From<Option<&Retention>> for Limits {
+    fn from(r: Option<&Retention>) -> Self {
+        Self {
+            max_messages: r
+                .and_then(|r| r.as_max_messages())
+                .map(|u| u.min(i64::MAX as u64) as i64),
+            max_age_us: r.and_then(|r| r.as_max_age()).map(dur2micros),
+            max_bytes: r
+                .and_then(|r| r.as_max_bytes())
+                .map(|u| u.min(i64::MAX as u64) as i64),
+        }
+    }
+}
+
+fn is_fk_violation(e: &rusqlite::Error) -> bool {
+    match e {
+        rusqlite::Error::SqliteFailure(f, _) => {
+            f.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY
+        }
+        _ => false,
+    }
+}
+
+pub struct Svc<T> {
+    pool: Pool,
+    topic_conv: T,
+}
+
+impl<T> Svc<T>
+where
+    T: TopicConv,
+{
+    #[allow(clippy::result_large_err)]
+    fn create(checked_name: &str, priority: bool, conn: &Connection) -> Result<(), Status> {
+        let priority_index: String = match priority {
+            true => format!(
+                r#"
+                    CREATE INDEX {checked_name}_priority
+                    ON {checked_name} (priority DESC, key);
+                "#
+            ),
+            false => String::new(),
+        };
+        let query = format!(
+            r#"
+                CREATE TABLE {checked_name} (
+                    key INTEGER PRIMARY KEY AUTOINCREMENT,
+                    val BLOB NOT NULL,
+                    pushed INTEGER NOT NULL,
+                    visible_at INTEGER NOT NULL,
+                    expires_at INTEGER,
+                    priority INTEGER NOT NULL DEFAULT 0,
+                    headers BLOB NOT NULL DEFAULT X'',
+                    leased_until INTEGER,
+                    lease_id TEXT,
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    last_error TEXT
+                );
+
+                CREATE INDEX {checked_name}_expires
+                ON {checked_name} (expires_at)
+                WHERE expires_at IS NOT NULL;
+
+                CREATE INDEX {checked_name}_pushed
+                ON {checked_name} (pushed);
+                {priority_index}
+            "#
+        );
+        conn.execute_batch(&query)
+            .map_err(|e| sqlite2status(e, "Unexpected error"))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn configure(
+        checked_name: &str,
+        dead_letter: Option<(&str, u64)>,
+        safe_tail: bool,
+        default_ttl: Option<Duration>,
+        conn: &Connection,
+    ) -> Result<usize, Status> {
+        let query = format!(
+            r#"
+                INSERT INTO {TOPIC_CONFIG} (
+                    name,
+                    dead_letter,
+                    max_attempts,
+                    safe_tail,
+                    default_ttl,
+                    created
+                )
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#
+        );
+        let dlq: Option<&str> = dead_letter.map(|d| d.0);
+        let max_attempts: i64 = dead_letter
+            .map(|d| d.1.min(i64::MAX as u64) as i64)
+            .unwrap_or_default();
+        let ttl_us: Option<i64> = default_ttl.map(dur2micros);
+        conn.execute(
+            &query,
+            rusqlite::params![
+                checked_name,
+                dlq,
+                max_attempts,
+                safe_tail,
+                ttl_us,
+                now_micros()
+            ],
+        )
+        .map_err(|e| match is_fk_violation(&e) {
+            true => Status::failed_precondition(format!("dead letter topic missing: {e}")),
+            false => sqlite2status(e, "Unexpected error"),
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn retain(checked_name: &str, limits: &Limits, conn: &Connection) -> Result<usize, Status> {
+        let query = format!(
+            r#"
+                UPDATE {TOPIC_CONFIG}
+                SET
+                    retain_messages = ?2,
+                    retain_age = ?3,
+                    retain_bytes = ?4
+                WHERE name = ?1
+            "#
+        );
+        conn.execute(
+            &query,
+            rusqlite::params![
+                checked_name,
+                limits.max_messages,
+                limits.max_age_us,
+                limits.max_bytes
+            ],
+        )
+        .map_err(|e| sqlite2status(e, "Unable to update a retention"))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn unconfigure(checked_name: &str, conn: &Connection) -> Result<usize, Status> {
+        let query = format!(
+            r#"
+                DELETE FROM {TOPIC_CONFIG}
+                WHERE name = ?1
+            "#
+        );
+        conn.execute(&query, [checked_name])
+            .map_err(|e| match is_fk_violation(&e) {
+                true => {
+                    Status::failed_precondition(format!("still used as a dead letter topic: {e}"))
+                }
+                false => sqlite2status(e, "Unexpected error"),
+            })
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn drop(checked_name: &str, conn: &Connection) -> Result<usize, Status> {
+        let query = format!(
+            r#"
+                DROP TABLE IF EXISTS {checked_name}
+            "#
+        );
+        conn.execute(&query, [])
+            .map_err(|e| sqlite2status(e, "Unexpected error"))
+    }
+
+    // (described without the dead letter, the dead letter name)
+    #[allow(clippy::result_large_err)]
+    fn config(
+        checked_name: &str,
+        conn: &Connection,
+    ) -> Result<(DescribeResponse, Option<String>), Status> {
+        let query = format!(
+            r#"
+                SELECT
+                    created,
+                    dead_letter,
+                    max_attempts,
+                    safe_tail,
+                    default_ttl,
+                    retain_messages,
+                    retain_age,
+                    retain_bytes,
+                    EXISTS(
+                        SELECT 1
+                        FROM sqlite_schema
+                        WHERE
+                            type = 'index'
+                            AND name = ?1 || '_priority'
+                    )
+                FROM {TOPIC_CONFIG}
+                WHERE name = ?1
+            "#
+        );
+        let col = |e| sqlite2status(e, "Unable to get a config");
+        let mut stmt = conn.prepare(&query).map_err(col)?;
+        let mut rows = stmt.query([checked_name]).map_err(col)?;
+        let row = rows
+            .next()
+            .map_err(col)?
+            .ok_or_else(|| Status::not_found(format!("No such topic: {checked_name}")))?;
+        let created: i64 = row.get(0).map_err(col)?;
+        let dead_letter: Option<String> = row.get(1).map_err(col)?;
+        let max_attempts: i64 = row.get(2).map_err(col)?;
+        let safe_tail: bool = row.get(3).map_err(col)?;
+        let ttl_us: Option<i64> = row.get(4).map_err(col)?;
+        let max_messages: Option<i64> = row.get(5).map_err(col)?;
+        let max_age_us: Option<i64> = row.get(6).map_err(col)?;
+        let max_bytes: Option<i64> = row.get(7).map_err(col)?;
+        let priority: bool = row.get(8).map_err(col)?;
+        let us2dur = |us: i64| micros2dur(us).try_into().ok();
+        let retention: Option<RetentionPb> = match (max_messages, max_age_us, max_bytes) {
+            (None, None, None) => None,
+            _ => Some(RetentionPb {
+                max_messages: max_messages.unwrap_or_default().max(0) as u64,
+                max_age: max_age_us.and_then(us2dur),
+                max_bytes: max_bytes.unwrap_or_default().max(0) as u64,
+            }),
+        };
+        let described = DescribeResponse {
+            created: Some(micros2time(created).into()),
+            dead_letter: dead_letter.as_ref().map(|_| DeadLetterPb {
+                topic_id: None,
+                max_attempts: max_attempts.max(0) as u64,
+            }),
+            safe_tail,
+            default_ttl: ttl_us.and_then(us2dur),
+            retention,
+            priority,
+            ..Default::default()
+        };
+        Ok((described, dead_letter))
+    }
+
+    // no statistics kept: the count and the payload bytes require a full scan
+    #[allow(clippy::result_large_err)]
+    fn stats(
+        checked_name: &str,
+        conn: &Connection,
+        described: &mut DescribeResponse,
+    ) -> Result<(), Status> {
+        let query = format!(
+            r#"
+                SELECT
+                    COALESCE(MIN(key), -1),
+                    COALESCE(MAX(key), -1),
+                    COUNT(*),
+                    COALESCE(SUM(LENGTH(val)), 0),
+                    (
+                        SELECT COALESCE(SUM(pgsize), 0)
+                        FROM dbstat
+                        WHERE name IN (
+                            SELECT name
+                            FROM sqlite_schema
+                            WHERE tbl_name = ?1
+                        )
+                    )
+                FROM {checked_name}
+            "#
+        );
+        let (min_key, max_key, cnt, payload_bytes, relation_size) = conn
+            .query_row(&query, [checked_name], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })
+            .map_err(|e| sqlite2status(e, "Unable to get stats"))?;
+        described.min_key = min_key;
+        described.max_key = max_key;
+        described.count_estimate = cnt.max(0) as u64;
+        described.payload_bytes = payload_bytes.max(0) as u64;
+        described.relation_size = relation_size.max(0) as u64;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl<T> TopicService for Svc<T>
+where
+    T: Send + Sync + 'static + TopicConv,
+{
+    #[allow(clippy::result_large_err)]
+    async fn create(
+        &self,
+        req: Request<CreateRequest>,
+    ) -> Result<Response<CreateResponse>, Status> {
+        let cr: CreateRequest = req.into_inner();
+        let checked: CreateReq = (&cr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let dead_letter: Option<(String, u64)> = checked.as_dead_letter().map(|d| {
+            (
+                self.topic_conv.id2name(d.as_topic_id()),
+                d.as_max_attempts(),
+            )
+        });
+        self.pool
+            .run(move |conn: &mut Connection| {
+                let tx: Transaction = tx_immediate(conn)?;
+                Self::create(name.as_str(), checked.as_priority(), &tx)?;
+                Self::configure(
+                    name.as_str(),
+                    dead_letter.as_ref().map(|(n, m)| (n.as_str(), *m)),
+                    checked.as_safe_tail(),
+                    checked.as_default_ttl(),
+                    &tx,
+                )?;
+                Self::retain(name.as_str(), &checked.as_retention().into(), &tx)?;
+                commit(tx)
+            })
+            .await?;
+        let created: SystemTime = SystemTime::now();
+        let reply = CreateResponse {
+            created: Some(created.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn drop(&self, req: Request<DropRequest>) -> Result<Response<DropResponse>, Status> {
+        let cr: DropRequest = req.into_inner();
+        let checked: DropReq = (&cr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        self.pool
+            .run(move |conn: &mut Connection| {
+                let tx: Transaction = tx_immediate(conn)?;
+                Self::unconfigure(name.as_str(), &tx)?;
+                Self::drop(name.as_str(), &tx)?;
+                commit(tx)
+            })
+            .await?;
+        let dropped: SystemTime = SystemTime::now();
+        let reply = DropResponse {
+            dropped: Some(dropped.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn list(&self, req: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        let lr: ListRequest = req.into_inner();
+        let _checked: ListReq = (&lr).try_into()?;
+        let names: Vec<String> = self
+            .pool
+            .run(|conn: &mut Connection| catalog::names(conn))
+            .await?;
+        let topics: Vec<Guid> = names
+            .iter()
+            .map(|name: &String| self.topic_conv.name2id(name).map(Guid::from))
+            .collect::<Result<_, _>>()?;
+        let reply = ListResponse { topics };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn update_retention(
+        &self,
+        req: Request<UpdateRetentionRequest>,
+    ) -> Result<Response<UpdateRetentionResponse>, Status> {
+        let ur: UpdateRetentionRequest = req.into_inner();
+        let checked: UpdateRetentionReq = (&ur).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let limits: Limits = checked.as_retention().into();
+        let updated: usize = self
+            .pool
+            .run(move |conn: &mut Connection| Self::retain(name.as_str(), &limits, conn))
+            .await?;
+        match updated {
+            0 => Err(Status::not_found(format!("No such topic: {topic_id}"))),
+            _ => Ok(()),
+        }?;
+        let updated: SystemTime = SystemTime::now();
+        let reply = UpdateRetentionResponse {
+            updated: Some(updated.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn describe(
+        &self,
+        req: Request<DescribeRequest>,
+    ) -> Result<Response<DescribeResponse>, Status> {
+        let dr: DescribeRequest = req.into_inner();
+        let checked: DescribeReq = (&dr).try_into()?;
+        let topic_id: Uuid = checked.as_topic_id();
+        let name: String = self.topic_conv.id2name(topic_id);
+        let (mut reply, dead_letter) = self
+            .pool
+            .run(move |conn: &mut Connection| {
+                // a snapshot of the config and the stats
+                let tx: Transaction = conn.transaction().map_err(|e| {
+                    Status::unavailable(format!("Unable to start a transaction: {e}"))
+                })?;
+                let (mut described, dead_letter) = Self::config(name.as_str(), &tx)?;
+                Self::stats(name.as_str(), &tx, &mut described)?;
+                Ok((described, dead_letter))
+            })
+            .await?;
+        if let (Some(d), Some(dlq)) = (reply.dead_letter.as_mut(), dead_letter) {
+            d.topic_id = Some(self.topic_conv.name2id(dlq.as_str())?.into());
+        }
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn topic_svc_new<T>(pool: &Pool, topic_conv: T) -> impl TopicService
+where
+    T: Send + Sync + 'static + TopicConv,
+{
+    Svc {
+        pool: pool.clone(),
+        topic_conv,
+    }
+}
@@ -0,0 +1,43 @@
+use core::time::Duration;
+
+use db2q_sqlite::db2q::conformance::{backend_new, run_all};
+
+use db2q_sqlite::common::minimal::catalog::init_pool;
+use db2q_sqlite::common::minimal::pool::{pool_new, Pool};
+use db2q_sqlite::common::minimal::pool::{BUSY_TIMEOUT_DEFAULT, IDLE_MAX_DEFAULT};
+use db2q_sqlite::count::minimal::svc::count_svc_new;
+use db2q_sqlite::queue::minimal::svc::queue_svc_new;
+use db2q_sqlite::sweep::minimal::retention::retention_worker_new;
+use db2q_sqlite::sweep::minimal::ttl::sweeper_new;
+use db2q_sqlite::topic::minimal::svc::topic_svc_new;
+use db2q_sqlite::topic::minimal::topic2table::topic2table_prefix_default;
+
+use tempfile::TempDir;
+
+// small batches to check bounded deletes
+const WORKER_INTERVAL: Duration = Duration::from_millis(100);
+const WORKER_BATCH_SIZE: u64 = 2;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sqlite() -> Result<(), String> {
+    let dir: TempDir = tempfile::tempdir().map_err(|e| format!("Unable to create a dir: {e}"))?;
+    let pool: Pool = pool_new(
+        dir.path().join("db2q.sqlite3"),
+        BUSY_TIMEOUT_DEFAULT,
+        IDLE_MAX_DEFAULT,
+    );
+
+    init_pool(&pool)
+        .await
+        .map_err(|e| format!("Unable to create a catalog: {e}"))?;
+
+    sweeper_new(&pool, WORKER_INTERVAL, WORKER_BATCH_SIZE);
+    retention_worker_new(&pool, WORKER_INTERVAL, WORKER_BATCH_SIZE);
+
+    let backend = backend_new(
+        queue_svc_new(&pool, topic2table_prefix_default()),
+        topic_svc_new(&pool, topic2table_prefix_default()),
+        count_svc_new(&pool, topic2table_prefix_default()),
+    );
+    run_all(&backend).await
+}
@@ -0,0 +1,1190 @@
+use core::fmt::Debug;
+use core::future::Future;
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::HashMap;
+
+use tokio_stream::{Stream, StreamExt};
+
+use tokio::time::Instant;
+
+use tonic::{Code, Request, Status};
+
+use crate::queue::cmd::headers::encode_one;
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::Uuid as Guid;
+
+use crate::db2q::proto::queue::v1::count_service_server::CountService;
+use crate::db2q::proto::queue::v1::queue_service_server::QueueService;
+use crate::db2q::proto::queue::v1::topic_service_server::TopicService;
+
+use crate::db2q::proto::queue::v1::cnt_svc::{ExactRequest, FastRequest, LagRequest, LagResponse};
+use crate::db2q::proto::queue::v1::q_svc::filter::{HeaderEq, HeaderIn, HeaderPrefix};
+use crate::db2q::proto::queue::v1::q_svc::{
+    AckRequest, CountRequest, ExtendLeaseRequest, Filter, KeysRequest, KeysResponse, LeaseRequest,
+    LeaseResponse, NackRequest, NextRequest, NextResponse, PopFrontRequest, PushBackRequest,
+    PushBatchRequest, RedriveRequest, ReportFailureRequest, ReportFailureResponse,
+    SubscribeRequest, SubscribeResponse, WaitNextRequest, WaitNextResponse,
+};
+use crate::db2q::proto::queue::v1::topic_svc::{
+    CreateRequest, DeadLetter, DescribeRequest, DescribeResponse, DropRequest, ListRequest,
+    Retention, UpdateRetentionRequest,
+};
+
+// the semantics shared by all backends(the postgresql backend is the reference)
+
+const LEASE_DEFAULT: Duration = Duration::from_secs(30);
+
+const FAILURE: &str = "conformance";
+
+// the workers of a backend(sweepers, retention workers) run within this
+const WORKED_WITHIN: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const TTL_SHORT: Duration = Duration::from_millis(200);
+
+const CONSUMED_ITEMS: usize = 32;
+
+// more items than a batch are read slowly
+const SUBSCRIBED_BATCH: u64 = 3;
+const SUBSCRIBED_ITEMS: usize = 10;
+const SLOW_READ: Duration = Duration::from_millis(20);
+
+fn request_id() -> Option<Guid> {
+    Some(Uuid::new_v4().into())
+}
+
+fn eq<T>(name: &str, got: T, expected: T) -> Result<(), String>
+where
+    T: PartialEq + Debug,
+{
+    match got == expected {
+        true => Ok(()),
+        false => Err(format!("{name}: expected {expected:?}, got {got:?}")),
+    }
+}
+
+fn ok<T>(name: &str, r: Result<T, Status>) -> Result<T, String> {
+    r.map_err(|e| format!("{name}: unexpected error: {e}"))
+}
+
+fn code<T>(name: &str, r: Result<T, Status>, expected: Code) -> Result<(), String>
+where
+    T: Debug,
+{
+    match r {
+        Ok(t) => Err(format!("{name}: expected {expected:?}, got {t:?}")),
+        Err(e) => eq(name, e.code(), expected),
+    }
+}
+
+async fn collect<S, T>(s: S) -> Result<Vec<T>, Status>
+where
+    S: Stream<Item = Result<T, Status>>,
+{
+    tokio::pin!(s);
+    let mut items: Vec<T> = Vec::new();
+    while let Some(item) = s.next().await {
+        items.push(item?);
+    }
+    Ok(items)
+}
+
+// the next item of a stream within WORKED_WITHIN
+async fn streamed<S, T>(name: &str, s: &mut Pin<&mut S>) -> Result<T, String>
+where
+    S: Stream<Item = Result<T, Status>>,
+{
+    match tokio::time::timeout(WORKED_WITHIN, s.next()).await {
+        Err(_) => Err(format!("{name}: no item in time")),
+        Ok(None) => Err(format!("{name}: ended")),
+        Ok(Some(r)) => ok(name, r),
+    }
+}
+
+async fn named<F>(name: &str, check: F) -> Result<(), String>
+where
+    F: Future<Output = Result<(), String>>,
+{
+    log::info!("checking {name}...");
+    check.await.map_err(|e| format!("{name}: {e}"))
+}
+
+pub struct Backend<Q, T, C> {
+    queue: Q,
+    topic: T,
+    count: C,
+    // consumer groups are optional(Unimplemented otherwise)
+    groups: bool,
+}
+
+impl<Q, T, C> Backend<Q, T, C>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    pub fn as_queue(&self) -> &Q {
+        &self.queue
+    }
+
+    pub fn as_topic(&self) -> &T {
+        &self.topic
+    }
+
+    pub fn as_count(&self) -> &C {
+        &self.count
+    }
+
+    async fn create(&self, req: CreateRequest) -> Result<Uuid, String> {
+        let topic_id: Uuid = Uuid::new_v4();
+        let req = CreateRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            ..req
+        };
+        ok("create", self.topic.create(Request::new(req)).await)?;
+        Ok(topic_id)
+    }
+
+    async fn drop_topic(&self, topic_id: Uuid) -> Result<(), String> {
+        let req = DropRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+        };
+        ok(
+            "drop",
+            TopicService::drop(&self.topic, Request::new(req)).await,
+        )?;
+        Ok(())
+    }
+
+    async fn push(&self, topic_id: Uuid, req: PushBackRequest) -> Result<i64, Status> {
+        let req = PushBackRequest {
+            request_id: req.request_id.or_else(request_id),
+            topic_id: Some(topic_id.into()),
+            ..req
+        };
+        let pushed = self.queue.push_back(Request::new(req)).await?;
+        Ok(pushed.into_inner().key)
+    }
+
+    async fn push_value(&self, topic_id: Uuid, value: &[u8]) -> Result<i64, String> {
+        let req = PushBackRequest {
+            value: value.into(),
+            ..Default::default()
+        };
+        ok("push", self.push(topic_id, req).await)
+    }
+
+    async fn next(&self, topic_id: Uuid, req: NextRequest) -> Result<NextResponse, Status> {
+        let req = NextRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            ..req
+        };
+        let next = self.queue.next(Request::new(req)).await?;
+        Ok(next.into_inner())
+    }
+
+    async fn next_after(&self, topic_id: Uuid, previous: i64) -> Result<NextResponse, Status> {
+        let req = NextRequest {
+            previous,
+            ..Default::default()
+        };
+        self.next(topic_id, req).await
+    }
+
+    async fn pop(&self, topic_id: Uuid, priority_order: bool) -> Result<Vec<u8>, Status> {
+        let req = PopFrontRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            priority_order,
+        };
+        let popped = self.queue.pop_front(Request::new(req)).await?;
+        Ok(popped.into_inner().value)
+    }
+
+    async fn exact(&self, topic_id: Uuid, lower: i64, upper: i64) -> Result<u64, Status> {
+        let req = ExactRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            lower,
+            upper,
+            ..Default::default()
+        };
+        let counted = self.count.exact(Request::new(req)).await?;
+        Ok(counted.into_inner().count)
+    }
+
+    async fn lag(&self, topic_id: Uuid, previous: i64) -> Result<LagResponse, Status> {
+        let req = LagRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            previous,
+            ..Default::default()
+        };
+        let lag = self.count.lag(Request::new(req)).await?;
+        Ok(lag.into_inner())
+    }
+
+    async fn keys(&self, topic_id: Uuid, req: KeysRequest) -> Result<Vec<(u64, i64)>, Status> {
+        let req = KeysRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            ..req
+        };
+        let keys = self.queue.keys(Request::new(req)).await?;
+        let keys: Vec<KeysResponse> = collect(keys.into_inner()).await?;
+        Ok(keys.into_iter().map(|k| (k.key, k.continuation)).collect())
+    }
+
+    async fn lease(&self, topic_id: Uuid) -> Result<LeaseResponse, Status> {
+        let req = LeaseRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            visibility_timeout: LEASE_DEFAULT.try_into().ok(),
+        };
+        let leased = self.queue.lease(Request::new(req)).await?;
+        Ok(leased.into_inner())
+    }
+
+    async fn ack(&self, topic_id: Uuid, key: i64, lease_id: Option<Guid>) -> Result<(), Status> {
+        let req = AckRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            key,
+            lease_id,
+        };
+        self.queue.ack(Request::new(req)).await?;
+        Ok(())
+    }
+
+    async fn report_failure(
+        &self,
+        topic_id: Uuid,
+        key: i64,
+    ) -> Result<ReportFailureResponse, Status> {
+        let req = ReportFailureRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            key,
+            error: FAILURE.into(),
+        };
+        let reported = self.queue.report_failure(Request::new(req)).await?;
+        Ok(reported.into_inner())
+    }
+
+    async fn redrive(&self, topic_id: Uuid) -> Result<u64, Status> {
+        let req = RedriveRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            max_messages: 0,
+        };
+        let redriven = self.queue.redrive(Request::new(req)).await?;
+        Ok(redriven.into_inner().redriven)
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn wait_next(
+        &self,
+        topic_id: Uuid,
+        timeout: Duration,
+    ) -> Result<WaitNextResponse, Status> {
+        let req = WaitNextRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            previous: -1,
+            interval: Duration::from_millis(50).try_into().ok(),
+            timeout: timeout.try_into().ok(),
+            ..Default::default()
+        };
+        let waited = self.queue.wait_next(Request::new(req)).await?;
+        let s = waited.into_inner();
+        tokio::pin!(s);
+        s.next()
+            .await
+            .unwrap_or_else(|| Err(Status::internal("no reply from wait_next")))
+    }
+
+    async fn update_retention(
+        &self,
+        topic_id: Uuid,
+        retention: Option<Retention>,
+    ) -> Result<(), String> {
+        let req = UpdateRetentionRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            retention,
+        };
+        ok(
+            "update retention",
+            self.topic.update_retention(Request::new(req)).await,
+        )?;
+        Ok(())
+    }
+
+    // the min key is polled until the lower keys are deleted by the workers of the backend
+    async fn min_key_eventually(
+        &self,
+        name: &str,
+        topic_id: Uuid,
+        min_key: i64,
+    ) -> Result<(), String> {
+        let deadline: Instant = Instant::now() + WORKED_WITHIN;
+        loop {
+            let described = ok(name, self.describe(topic_id).await)?;
+            if described.min_key == min_key || deadline <= Instant::now() {
+                return eq(name, described.min_key, min_key);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    // popped until empty; retried on Unavailable or Aborted
+    async fn pop_all(&self, topic_id: Uuid) -> Result<Vec<Vec<u8>>, String> {
+        let mut popped: Vec<Vec<u8>> = Vec::new();
+        loop {
+            match self.pop(topic_id, false).await {
+                Ok(value) => popped.push(value),
+                Err(e) => match e.code() {
+                    Code::NotFound => return Ok(popped),
+                    Code::Unavailable | Code::Aborted => {}
+                    _ => return Err(format!("pop: unexpected error: {e}")),
+                },
+            }
+        }
+    }
+
+    // leased and acked until empty; retried on Unavailable or Aborted
+    async fn lease_all(&self, topic_id: Uuid) -> Result<Vec<Vec<u8>>, String> {
+        let mut acked: Vec<Vec<u8>> = Vec::new();
+        loop {
+            let leased: LeaseResponse = match self.lease(topic_id).await {
+                Ok(leased) => leased,
+                Err(e) => match e.code() {
+                    Code::NotFound => return Ok(acked),
+                    Code::Unavailable | Code::Aborted => continue,
+                    _ => return Err(format!("lease: unexpected error: {e}")),
+                },
+            };
+            ok("ack", self.ack(topic_id, leased.key, leased.lease_id).await)?;
+            acked.push(leased.value);
+        }
+    }
+
+    async fn subscribe(&self, topic_id: Uuid) -> Result<Q::SubscribeStream, Status> {
+        let req = SubscribeRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+            previous: -1,
+            interval: Duration::from_millis(50).try_into().ok(),
+            batch_size: SUBSCRIBED_BATCH,
+        };
+        let subscribed = self.queue.subscribe(Request::new(req)).await?;
+        Ok(subscribed.into_inner())
+    }
+
+    async fn describe(&self, topic_id: Uuid) -> Result<DescribeResponse, Status> {
+        let req = DescribeRequest {
+            request_id: request_id(),
+            topic_id: Some(topic_id.into()),
+        };
+        let described = self.topic.describe(Request::new(req)).await?;
+        Ok(described.into_inner())
+    }
+
+    async fn list(&self) -> Result<Vec<u128>, String> {
+        let req = ListRequest {
+            request_id: request_id(),
+        };
+        let listed = ok("list", self.topic.list(Request::new(req)).await)?;
+        Ok(listed
+            .into_inner()
+            .topics
+            .iter()
+            .map(|g: &Guid| Uuid::from(g).as_u128())
+            .collect())
+    }
+}
+
+pub fn backend_new<Q, T, C>(queue: Q, topic: T, count: C) -> Backend<Q, T, C>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    Backend {
+        queue,
+        topic,
+        count,
+        groups: false,
+    }
+}
+
+pub fn backend_new_with_groups<Q, T, C>(queue: Q, topic: T, count: C) -> Backend<Q, T, C>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    Backend {
+        groups: true,
+        ..backend_new(queue, topic, count)
+    }
+}
+
+// created, listed, described and dropped
+pub async fn topic_lifecycle<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let listed: Vec<u128> = b.list().await?;
+    eq("listed", listed.contains(&topic_id.as_u128()), true)?;
+    let k1: i64 = b.push_value(topic_id, b"a").await?;
+    let k2: i64 = b.push_value(topic_id, b"b").await?;
+    let described = ok("describe", b.describe(topic_id).await)?;
+    eq("min key", described.min_key, k1)?;
+    eq("max key", described.max_key, k2)?;
+    b.drop_topic(topic_id).await?;
+    let listed: Vec<u128> = b.list().await?;
+    eq(
+        "listed after drop",
+        listed.contains(&topic_id.as_u128()),
+        false,
+    )?;
+    code(
+        "describe dropped",
+        b.describe(topic_id).await,
+        Code::NotFound,
+    )?;
+    code(
+        "push to dropped",
+        b.push(topic_id, PushBackRequest::default()).await,
+        Code::NotFound,
+    )
+}
+
+// NotFound from an empty topic; a negative previous key means the first item
+pub async fn empty_topic<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    code("next", b.next_after(topic_id, -1).await, Code::NotFound)?;
+    code(
+        "next after 5",
+        b.next_after(topic_id, 5).await,
+        Code::NotFound,
+    )?;
+    code("pop", b.pop(topic_id, false).await, Code::NotFound)?;
+    code("lease", b.lease(topic_id).await, Code::NotFound)?;
+    eq("exact", ok("exact", b.exact(topic_id, 0, 0).await)?, 0)?;
+    let lag: LagResponse = ok("lag", b.lag(topic_id, -1).await)?;
+    eq("lag remaining", lag.remaining, 0)?;
+    eq("lag newest", lag.newest, -1)?;
+    // an estimate may be unavailable(NotFound) for a new topic
+    let req = FastRequest {
+        request_id: request_id(),
+        topic_id: Some(topic_id.into()),
+    };
+    match b.count.fast(Request::new(req)).await {
+        Ok(_) => {}
+        Err(e) => eq("fast", e.code(), Code::NotFound)?,
+    }
+    b.drop_topic(topic_id).await
+}
+
+// keys increase in the pushed order; next iterates by key
+pub async fn next_order<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let k1: i64 = b.push_value(topic_id, b"a").await?;
+    let k2: i64 = b.push_value(topic_id, b"b").await?;
+    eq("increasing", k1 < k2, true)?;
+    let first: NextResponse = ok("first", b.next_after(topic_id, -1).await)?;
+    eq("first key", first.next, k1)?;
+    eq("first value", first.value, b"a".to_vec())?;
+    let second: NextResponse = ok("second", b.next_after(topic_id, k1).await)?;
+    eq("second key", second.next, k2)?;
+    eq("second value", second.value, b"b".to_vec())?;
+    code(
+        "after last",
+        b.next_after(topic_id, k2).await,
+        Code::NotFound,
+    )?;
+    b.drop_topic(topic_id).await
+}
+
+// a retried push returns the original key(even after the item is removed)
+pub async fn push_dedupe<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let req = PushBackRequest {
+        request_id: request_id(),
+        value: b"once".to_vec(),
+        ..Default::default()
+    };
+    let original: i64 = ok("push", b.push(topic_id, req.clone()).await)?;
+    let retried: i64 = ok("retry", b.push(topic_id, req.clone()).await)?;
+    eq("key", retried, original)?;
+    eq("exact", ok("exact", b.exact(topic_id, 0, 0).await)?, 1)?;
+    eq(
+        "popped",
+        ok("pop", b.pop(topic_id, false).await)?,
+        b"once".to_vec(),
+    )?;
+    let repushed: i64 = ok("retry after pop", b.push(topic_id, req).await)?;
+    eq("key after pop", repushed, original)?;
+    eq(
+        "exact after pop",
+        ok("exact", b.exact(topic_id, 0, 0).await)?,
+        0,
+    )?;
+    b.drop_topic(topic_id).await
+}
+
+// pushed as a batch; popped in the key order
+pub async fn push_batch_pop<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let req = PushBatchRequest {
+        request_id: request_id(),
+        topic_id: Some(topic_id.into()),
+        values: vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()],
+    };
+    let pushed = ok("push batch", b.queue.push_batch(Request::new(req)).await)?;
+    let keys: Vec<i64> = pushed.into_inner().keys;
+    eq("keys", keys.len(), 3)?;
+    eq("ordered", keys.windows(2).all(|w| w[0] < w[1]), true)?;
+    let req = CountRequest {
+        request_id: request_id(),
+        topic_id: Some(topic_id.into()),
+    };
+    let counted = ok("count", b.queue.count(Request::new(req)).await)?;
+    eq("count", counted.into_inner().count, 3)?;
+    for expected in [b"a", b"b", b"c"] {
+        eq(
+            "popped",
+            ok("pop", b.pop(topic_id, false).await)?,
+            expected.to_vec(),
+        )?;
+    }
+    code("pop empty", b.pop(topic_id, false).await, Code::NotFound)?;
+    b.drop_topic(topic_id).await
+}
+
+// single and batch pushes to a safe tail topic are read in the key order
+pub async fn safe_tail<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let req = CreateRequest {
+        safe_tail: true,
+        ..Default::default()
+    };
+    let topic_id: Uuid = b.create(req).await?;
+    let first: i64 = b.push_value(topic_id, b"a").await?;
+    let req = PushBatchRequest {
+        request_id: request_id(),
+        topic_id: Some(topic_id.into()),
+        values: vec![b"b".to_vec(), b"c".to_vec()],
+    };
+    let pushed = ok("push batch", b.queue.push_batch(Request::new(req)).await)?;
+    let mut keys: Vec<i64> = vec![first];
+    keys.extend(pushed.into_inner().keys);
+    let mut previous: i64 = -1;
+    for (key, expected) in keys.into_iter().zip([b"a", b"b", b"c"]) {
+        let got: NextResponse = ok("next", b.next_after(topic_id, previous).await)?;
+        eq("next key", got.next, key)?;
+        eq("next value", got.value, expected.to_vec())?;
+        previous = key;
+    }
+    b.drop_topic(topic_id).await
+}
+
+// (priority desc, key) order
+pub async fn priority_order<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let req = CreateRequest {
+        priority: true,
+        ..Default::default()
+    };
+    let topic_id: Uuid = b.create(req).await?;
+    for (value, priority) in [(b"low", 1), (b"hi1", 5), (b"hi2", 5)] {
+        let req = PushBackRequest {
+            value: value.to_vec(),
+            priority,
+            ..Default::default()
+        };
+        ok("push", b.push(topic_id, req).await)?;
+    }
+    let mut previous: i64 = -1;
+    let mut previous_priority: i32 = 0;
+    for expected in [b"hi1", b"hi2", b"low"] {
+        let req = NextRequest {
+            previous,
+            previous_priority,
+            priority_order: true,
+            ..Default::default()
+        };
+        let next: NextResponse = ok("next", b.next(topic_id, req).await)?;
+        eq("next", next.value, expected.to_vec())?;
+        previous = next.next;
+        previous_priority = next.priority;
+    }
+    eq(
+        "popped",
+        ok("pop", b.pop(topic_id, true).await)?,
+        b"hi1".to_vec(),
+    )?;
+    b.drop_topic(topic_id).await
+}
+
+// header filters and hidden(delayed) items
+pub async fn filter_and_delay<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let req = PushBackRequest {
+        value: b"later".to_vec(),
+        delay: Duration::from_secs(3600).try_into().ok(),
+        ..Default::default()
+    };
+    ok("push delayed", b.push(topic_id, req).await)?;
+    code(
+        "next delayed",
+        b.next_after(topic_id, -1).await,
+        Code::NotFound,
+    )?;
+    b.push_value(topic_id, b"plain").await?;
+    let req = PushBackRequest {
+        value: b"typed".to_vec(),
+        headers: HashMap::from([("content-type".into(), b"text/plain".to_vec())]),
+        ..Default::default()
+    };
+    let typed: i64 = ok("push typed", b.push(topic_id, req).await)?;
+    let req = NextRequest {
+        previous: -1,
+        filter: Some(Filter {
+            header_eq: vec![HeaderEq {
+                name: "content-type".into(),
+                value: b"text/plain".to_vec(),
+            }],
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let next: NextResponse = ok("next filtered", b.next(topic_id, req).await)?;
+    eq("filtered", next.next, typed)?;
+    eq(
+        "headers",
+        next.headers.get("content-type").cloned(),
+        Some(b"text/plain".to_vec()),
+    )?;
+    // a header value having another header(encoded) must not match
+    let req = PushBackRequest {
+        value: b"noted".to_vec(),
+        headers: HashMap::from([("note".into(), encode_one("content-type", b"text/html"))]),
+        ..Default::default()
+    };
+    ok("push noted", b.push(topic_id, req).await)?;
+    let filtered = |filter: Filter| NextRequest {
+        previous: -1,
+        filter: Some(filter),
+        ..Default::default()
+    };
+    let prefixed = Filter {
+        header_prefix: vec![HeaderPrefix {
+            name: "content-type".into(),
+            prefix: b"text/".to_vec(),
+        }],
+        ..Default::default()
+    };
+    let next: NextResponse = ok("next prefixed", b.next(topic_id, filtered(prefixed)).await)?;
+    eq("prefixed", next.next, typed)?;
+    let any = Filter {
+        header_in: vec![HeaderIn {
+            name: "content-type".into(),
+            values: vec![b"text/html".to_vec(), b"text/plain".to_vec()],
+        }],
+        ..Default::default()
+    };
+    let next: NextResponse = ok("next in", b.next(topic_id, filtered(any)).await)?;
+    eq("in", next.next, typed)?;
+    let valued = Filter {
+        value_prefix: b"typ".to_vec(),
+        ..Default::default()
+    };
+    let next: NextResponse = ok("next valued", b.next(topic_id, filtered(valued)).await)?;
+    eq("valued", next.next, typed)?;
+    let html = Filter {
+        header_eq: vec![HeaderEq {
+            name: "content-type".into(),
+            value: b"text/html".to_vec(),
+        }],
+        ..Default::default()
+    };
+    code(
+        "next html",
+        b.next(topic_id, filtered(html.clone())).await,
+        Code::NotFound,
+    )?;
+    let req = KeysRequest {
+        max_keys: 8,
+        filter: Some(html),
+        ..Default::default()
+    };
+    eq(
+        "html keys",
+        ok("keys html", b.keys(topic_id, req).await)?,
+        vec![],
+    )?;
+    b.drop_topic(topic_id).await
+}
+
+// exact counts with key bounds; lags from a consumed key
+pub async fn count_and_lag<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let k1: i64 = b.push_value(topic_id, b"a").await?;
+    let k2: i64 = b.push_value(topic_id, b"b").await?;
+    let k3: i64 = b.push_value(topic_id, b"c").await?;
+    eq("exact", ok("exact", b.exact(topic_id, 0, 0).await)?, 3)?;
+    eq(
+        "exact from k2",
+        ok("exact", b.exact(topic_id, k2, 0).await)?,
+        2,
+    )?;
+    eq(
+        "exact k1..k3",
+        ok("exact", b.exact(topic_id, k1, k3).await)?,
+        2,
+    )?;
+    let lag: LagResponse = ok("lag", b.lag(topic_id, -1).await)?;
+    eq("lag remaining", lag.remaining, 3)?;
+    eq("lag newest", lag.newest, k3)?;
+    let lag: LagResponse = ok("lag from k2", b.lag(topic_id, k2).await)?;
+    eq("lag remaining from k2", lag.remaining, 1)?;
+    b.drop_topic(topic_id).await
+}
+
+// pages continue from the last key of a full page
+pub async fn keys_paging<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let mut keys: Vec<u64> = Vec::new();
+    for value in [b"a", b"b", b"c", b"d", b"e"] {
+        keys.push(b.push_value(topic_id, value).await? as u64);
+    }
+    let page = |start_after: i64, descending: bool| KeysRequest {
+        max_keys: 2,
+        start_after,
+        descending,
+        ..Default::default()
+    };
+    let first = ok("first page", b.keys(topic_id, page(0, false)).await)?;
+    eq(
+        "first page",
+        first,
+        vec![(keys[0], -1), (keys[1], keys[1] as i64)],
+    )?;
+    let second = ok(
+        "second page",
+        b.keys(topic_id, page(keys[1] as i64, false)).await,
+    )?;
+    eq(
+        "second page",
+        second,
+        vec![(keys[2], -1), (keys[3], keys[3] as i64)],
+    )?;
+    let last = ok(
+        "last page",
+        b.keys(topic_id, page(keys[3] as i64, false)).await,
+    )?;
+    eq("last page", last, vec![(keys[4], -1)])?;
+    let desc = ok("descending", b.keys(topic_id, page(0, true)).await)?;
+    eq(
+        "descending",
+        desc,
+        vec![(keys[4], -1), (keys[3], keys[3] as i64)],
+    )?;
+    b.drop_topic(topic_id).await
+}
+
+// a leased item is hidden until acked or nacked
+pub async fn lease_ack_nack<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let key: i64 = b.push_value(topic_id, b"job").await?;
+    let leased: LeaseResponse = ok("lease", b.lease(topic_id).await)?;
+    eq("leased key", leased.key, key)?;
+    eq("leased value", leased.value, b"job".to_vec())?;
+    code("lease again", b.lease(topic_id).await, Code::NotFound)?;
+    let req = ExtendLeaseRequest {
+        request_id: request_id(),
+        topic_id: Some(topic_id.into()),
+        key,
+        lease_id: leased.lease_id.clone(),
+        extension: Some(prost_types::Duration {
+            seconds: -1,
+            nanos: 0,
+        }),
+    };
+    code(
+        "extend with a negative extension",
+        b.queue.extend_lease(Request::new(req)).await,
+        Code::InvalidArgument,
+    )?;
+    let req = ExtendLeaseRequest {
+        request_id: request_id(),
+        topic_id: Some(topic_id.into()),
+        key,
+        lease_id: leased.lease_id.clone(),
+        extension: Duration::from_secs(60).try_into().ok(),
+    };
+    ok("extend", b.queue.extend_lease(Request::new(req)).await)?;
+    let req = NackRequest {
+        request_id: request_id(),
+        topic_id: Some(topic_id.into()),
+        key,
+        lease_id: leased.lease_id.clone(),
+        delay: None,
+    };
+    ok("nack", b.queue.nack(Request::new(req)).await)?;
+    let released: LeaseResponse = ok("lease after nack", b.lease(topic_id).await)?;
+    eq("released key", released.key, key)?;
+    code(
+        "ack with a stale lease",
+        b.ack(topic_id, key, leased.lease_id).await,
+        Code::NotFound,
+    )?;
+    ok("ack", b.ack(topic_id, key, released.lease_id).await)?;
+    code(
+        "next after ack",
+        b.next_after(topic_id, -1).await,
+        Code::NotFound,
+    )?;
+    b.drop_topic(topic_id).await
+}
+
+// moved to the dead letter topic after max attempts(the failures readable); redriven back
+pub async fn dead_letter<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let dlq: Uuid = b.create(CreateRequest::default()).await?;
+    let req = CreateRequest {
+        dead_letter: Some(DeadLetter {
+            topic_id: Some(dlq.into()),
+            max_attempts: 2,
+        }),
+        ..Default::default()
+    };
+    let topic_id: Uuid = b.create(req).await?;
+    code(
+        "redrive from a dlq",
+        b.redrive(dlq).await,
+        Code::FailedPrecondition,
+    )?;
+    let key: i64 = b.push_value(topic_id, b"poison").await?;
+    let pushed: NextResponse = ok("next", b.next_after(topic_id, -1).await)?;
+    eq("pushed attempts", pushed.attempts, 0)?;
+    eq("pushed error", pushed.last_error, String::new())?;
+    let first = ok("first failure", b.report_failure(topic_id, key).await)?;
+    eq("first attempts", first.attempts, 1)?;
+    eq("first dead lettered", first.dead_lettered, false)?;
+    let failed: NextResponse = ok("next", b.next_after(topic_id, -1).await)?;
+    eq("next attempts", failed.attempts, 1)?;
+    eq("next error", failed.last_error, FAILURE.to_string())?;
+    let leased: LeaseResponse = ok("lease", b.lease(topic_id).await)?;
+    eq("leased attempts", leased.attempts, 1)?;
+    eq("leased error", leased.last_error, FAILURE.to_string())?;
+    let second = ok("second failure", b.report_failure(topic_id, key).await)?;
+    eq("second attempts", second.attempts, 2)?;
+    eq("second dead lettered", second.dead_lettered, true)?;
+    let dead: NextResponse = ok("next", b.next_after(dlq, -1).await)?;
+    eq("dead letter attempts", dead.attempts, 2)?;
+    eq("dead letter error", dead.last_error, FAILURE.to_string())?;
+    eq("source", ok("exact", b.exact(topic_id, 0, 0).await)?, 0)?;
+    eq("dead letters", ok("exact", b.exact(dlq, 0, 0).await)?, 1)?;
+    eq("redriven", ok("redrive", b.redrive(topic_id).await)?, 1)?;
+    eq(
+        "source redriven",
+        ok("exact", b.exact(topic_id, 0, 0).await)?,
+        1,
+    )?;
+    eq(
+        "dead letters redriven",
+        ok("exact", b.exact(dlq, 0, 0).await)?,
+        0,
+    )?;
+    let redriven: NextResponse = ok("next", b.next_after(topic_id, -1).await)?;
+    eq("redriven attempts", redriven.attempts, 0)?;
+    eq("redriven error", redriven.last_error, String::new())?;
+    b.drop_topic(topic_id).await?;
+    b.drop_topic(dlq).await
+}
+
+// DeadlineExceeded on timeout; woken by a push otherwise
+pub async fn wait_next<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    code(
+        "timeout",
+        b.wait_next(topic_id, Duration::from_millis(200)).await,
+        Code::DeadlineExceeded,
+    )?;
+    let push_later = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        b.push_value(topic_id, b"woken").await
+    };
+    let (waited, pushed) = tokio::join!(b.wait_next(topic_id, Duration::from_secs(10)), push_later);
+    let key: i64 = pushed?;
+    let waited: WaitNextResponse = ok("wait", waited)?;
+    let next: NextResponse = waited.next.unwrap_or_default();
+    eq("waited key", next.next, key)?;
+    eq("waited value", next.value, b"woken".to_vec())?;
+    b.drop_topic(topic_id).await
+}
+
+// streamed in the key order to a slow reader(more items than a batch); woken by a push
+pub async fn subscribe_order<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let mut expected: Vec<(i64, Vec<u8>)> = Vec::new();
+    for i in 0..SUBSCRIBED_ITEMS {
+        let value: Vec<u8> = format!("{i:02}").into_bytes();
+        expected.push((b.push_value(topic_id, &value).await?, value));
+    }
+    let s = ok("subscribe", b.subscribe(topic_id).await)?;
+    tokio::pin!(s);
+    let mut received: Vec<(i64, Vec<u8>)> = Vec::new();
+    while received.len() < SUBSCRIBED_ITEMS {
+        tokio::time::sleep(SLOW_READ).await;
+        let next: SubscribeResponse = streamed("subscribed", &mut s).await?;
+        let next: NextResponse = next.next.unwrap_or_default();
+        received.push((next.next, next.value));
+    }
+    eq("subscribed", &received, &expected)?;
+    let pushed: i64 = b.push_value(topic_id, b"late").await?;
+    let late: SubscribeResponse = streamed("subscribed late", &mut s).await?;
+    eq("subscribed late", late.next.map(|n| n.next), Some(pushed))?;
+    b.drop_topic(topic_id).await
+}
+
+// expired messages are hidden, then deleted by the sweeper of the backend
+pub async fn ttl_sweep<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let req = CreateRequest {
+        default_ttl: TTL_SHORT.try_into().ok(),
+        ..Default::default()
+    };
+    let topic_id: Uuid = b.create(req).await?;
+    let expiring: i64 = b.push_value(topic_id, b"expiring").await?;
+    let req = PushBackRequest {
+        value: b"kept".to_vec(),
+        ttl: Duration::from_secs(3600).try_into().ok(),
+        ..Default::default()
+    };
+    let kept: i64 = ok("push", b.push(topic_id, req).await)?;
+    let described = ok("describe", b.describe(topic_id).await)?;
+    eq("min key", described.min_key, expiring)?;
+    tokio::time::sleep(TTL_SHORT).await;
+    let first: NextResponse = ok("next", b.next_after(topic_id, -1).await)?;
+    eq("next of unexpired", first.next, kept)?;
+    eq("exact", ok("exact", b.exact(topic_id, 0, 0).await)?, 1)?;
+    b.min_key_eventually("min key after sweep", topic_id, kept)
+        .await?;
+    b.drop_topic(topic_id).await
+}
+
+// the lowest keys are trimmed by the retention worker of the backend; updated retentions apply
+pub async fn retention_trim<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let retention = Retention {
+        max_messages: 3,
+        ..Default::default()
+    };
+    let req = CreateRequest {
+        retention: Some(retention.clone()),
+        ..Default::default()
+    };
+    let topic_id: Uuid = b.create(req).await?;
+    let described = ok("describe", b.describe(topic_id).await)?;
+    eq("retention", described.retention, Some(retention))?;
+    let mut keys: Vec<i64> = Vec::new();
+    for value in [b"a", b"b", b"c", b"d", b"e"] {
+        keys.push(b.push_value(topic_id, value).await?);
+    }
+    b.min_key_eventually("min key trimmed to 3", topic_id, keys[2])
+        .await?;
+    eq("exact", ok("exact", b.exact(topic_id, 0, 0).await)?, 3)?;
+    let retention = Retention {
+        max_messages: 1,
+        ..Default::default()
+    };
+    b.update_retention(topic_id, Some(retention.clone()))
+        .await?;
+    let described = ok("describe", b.describe(topic_id).await)?;
+    eq("updated retention", described.retention, Some(retention))?;
+    b.min_key_eventually("min key trimmed to 1", topic_id, keys[4])
+        .await?;
+    eq("exact", ok("exact", b.exact(topic_id, 0, 0).await)?, 1)?;
+    b.update_retention(topic_id, None).await?;
+    let described = ok("describe", b.describe(topic_id).await)?;
+    eq("unset retention", described.retention, None)?;
+    b.drop_topic(topic_id).await
+}
+
+// competing consumers never receive an item twice
+pub async fn competing_consumers<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let expected: Vec<Vec<u8>> = (0..CONSUMED_ITEMS)
+        .map(|i: usize| format!("{i:02}").into_bytes())
+        .collect();
+    for value in &expected {
+        b.push_value(topic_id, value).await?;
+    }
+    let (p1, p2, p3, p4) = tokio::join!(
+        b.pop_all(topic_id),
+        b.pop_all(topic_id),
+        b.pop_all(topic_id),
+        b.pop_all(topic_id),
+    );
+    let mut popped: Vec<Vec<u8>> = [p1?, p2?, p3?, p4?].concat();
+    popped.sort();
+    eq("popped", &popped, &expected)?;
+
+    for value in &expected {
+        b.push_value(topic_id, value).await?;
+    }
+    let (l1, l2, l3, l4) = tokio::join!(
+        b.lease_all(topic_id),
+        b.lease_all(topic_id),
+        b.lease_all(topic_id),
+        b.lease_all(topic_id),
+    );
+    let mut acked: Vec<Vec<u8>> = [l1?, l2?, l3?, l4?].concat();
+    acked.sort();
+    eq("acked", &acked, &expected)?;
+    eq("exact", ok("exact", b.exact(topic_id, 0, 0).await)?, 0)?;
+    b.drop_topic(topic_id).await
+}
+
+// starts from the first item without a committed offset(previous is ignored);
+// Unimplemented unless the backend keeps groups
+pub async fn group_next<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    let topic_id: Uuid = b.create(CreateRequest::default()).await?;
+    let first: i64 = b.push_value(topic_id, b"first").await?;
+    b.push_value(topic_id, b"second").await?;
+    let req = NextRequest {
+        previous: first,
+        group: "conformance".into(),
+        ..Default::default()
+    };
+    let r: Result<NextResponse, Status> = b.next(topic_id, req).await;
+    match b.groups {
+        false => code("next of a group", r, Code::Unimplemented)?,
+        true => {
+            let got: NextResponse = ok("next of a new group", r)?;
+            eq("next of a new group", got.next, first)?;
+            eq("value of a new group", got.value, b"first".to_vec())?;
+        }
+    }
+    b.drop_topic(topic_id).await
+}
+
+// the first failed check(the workers of the backend are expected to be running)
+pub async fn run_all<Q, T, C>(b: &Backend<Q, T, C>) -> Result<(), String>
+where
+    Q: QueueService,
+    T: TopicService,
+    C: CountService,
+{
+    named("topic lifecycle", topic_lifecycle(b)).await?;
+    named("empty topic", empty_topic(b)).await?;
+    named("next order", next_order(b)).await?;
+    named("push dedupe", push_dedupe(b)).await?;
+    named("push batch & pop", push_batch_pop(b)).await?;
+    named("safe tail", safe_tail(b)).await?;
+    named("priority order", priority_order(b)).await?;
+    named("filter & delay", filter_and_delay(b)).await?;
+    named("count & lag", count_and_lag(b)).await?;
+    named("keys paging", keys_paging(b)).await?;
+    named("lease, ack & nack", lease_ack_nack(b)).await?;
+    named("dead letter", dead_letter(b)).await?;
+    named("ttl sweep", ttl_sweep(b)).await?;
+    named("retention trim", retention_trim(b)).await?;
+    named("competing consumers", competing_consumers(b)).await?;
+    named("group next", group_next(b)).await?;
+    named("wait next", wait_next(b)).await?;
+    named("subscribe order", subscribe_order(b)).await
+}
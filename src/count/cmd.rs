@@ -1,2 +1,3 @@
 pub mod exact;
 pub mod fast;
+pub mod lag;
@@ -1,3 +1,5 @@
+use std::time::SystemTime;
+
 use tonic::Status;
 
 use crate::uuid::Uuid;
@@ -7,6 +9,10 @@ use crate::db2q::proto::queue::v1::cnt_svc::ExactRequest;
 pub struct ExactReq {
     request_id: Uuid,
     topic_id: Uuid,
+    lower: Option<u64>,
+    upper: Option<u64>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
 }
 
 impl ExactReq {
@@ -16,6 +22,39 @@ impl ExactReq {
     pub fn as_topic(&self) -> Uuid {
         self.topic_id
     }
+    pub fn as_lower(&self) -> Option<u64> {
+        self.lower
+    }
+    pub fn as_upper(&self) -> Option<u64> {
+        self.upper
+    }
+    pub fn as_since(&self) -> Option<SystemTime> {
+        self.since
+    }
+    pub fn as_until(&self) -> Option<SystemTime> {
+        self.until
+    }
+}
+
+fn key_bound(key: i64) -> Option<u64> {
+    match key {
+        1.. => Some(key as u64),
+        _ => None,
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn time_bound(
+    t: Option<&prost_types::Timestamp>,
+    label: &str,
+    request_id: Uuid,
+) -> Result<Option<SystemTime>, Status> {
+    match t {
+        None => Ok(None),
+        Some(t) => SystemTime::try_from(t.clone()).map(Some).map_err(|e| {
+            Status::invalid_argument(format!("invalid {label}. request id: {request_id}: {e}"))
+        }),
+    }
 }
 
 impl TryFrom<&ExactRequest> for ExactReq {
@@ -30,9 +69,31 @@ impl TryFrom<&ExactRequest> for ExactReq {
         let topic_id: Uuid = r.topic_id.as_ref().try_into().map_err(|_| {
             Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
         })?;
+        let lower: Option<u64> = key_bound(r.lower);
+        let upper: Option<u64> = key_bound(r.upper);
+        if let (Some(l), Some(u)) = (lower, upper) {
+            if u <= l {
+                return Err(Status::invalid_argument(format!(
+                    "empty key range({l}..{u}). request id: {request_id}"
+                )));
+            }
+        }
+        let since: Option<SystemTime> = time_bound(r.since.as_ref(), "since", request_id)?;
+        let until: Option<SystemTime> = time_bound(r.until.as_ref(), "until", request_id)?;
+        if let (Some(s), Some(u)) = (since, until) {
+            if u <= s {
+                return Err(Status::invalid_argument(format!(
+                    "empty time range. request id: {request_id}"
+                )));
+            }
+        }
         Ok(Self {
             request_id,
             topic_id,
+            lower,
+            upper,
+            since,
+            until,
         })
     }
 }
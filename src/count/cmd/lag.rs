@@ -0,0 +1,60 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::cnt_svc::LagRequest;
+
+use crate::group::cmd::name::group_checked;
+
+pub struct LagReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    previous: Option<u64>,
+    group: Option<String>,
+}
+
+impl LagReq {
+    pub fn as_request(&self) -> Uuid {
+        self.request_id
+    }
+    pub fn as_topic(&self) -> Uuid {
+        self.topic_id
+    }
+    pub fn as_previous_key(&self) -> Option<u64> {
+        self.previous
+    }
+    pub fn as_group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+}
+
+impl TryFrom<&LagRequest> for LagReq {
+    type Error = Status;
+
+    fn try_from(r: &LagRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = r
+            .request_id
+            .as_ref()
+            .try_into()
+            .map_err(|_| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = r.topic_id.as_ref().try_into().map_err(|_| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let previous: Option<u64> = match r.previous {
+            0.. => Some(r.previous.try_into().map_err(|e| {
+                Status::invalid_argument(format!("the key out of range({}): {e}", r.previous))
+            })?),
+            ..=-1 => None,
+        };
+        let group: Option<String> = match r.group.is_empty() {
+            true => None,
+            false => Some(group_checked(&r.group, request_id)?),
+        };
+        Ok(Self {
+            request_id,
+            topic_id,
+            previous,
+            group,
+        })
+    }
+}
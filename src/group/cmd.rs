@@ -0,0 +1,7 @@
+pub mod name;
+
+pub mod commit;
+pub mod get;
+
+pub mod delete;
+pub mod list;
@@ -0,0 +1,56 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::grp_svc::CommitOffsetRequest;
+
+use super::name::group_checked;
+
+pub struct CommitOffsetReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    group: String,
+    offset: u64,
+}
+
+impl CommitOffsetReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn as_offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl TryFrom<&CommitOffsetRequest> for CommitOffsetReq {
+    type Error = Status;
+    fn try_from(g: &CommitOffsetRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let group: String = group_checked(&g.group, request_id)?;
+        let offset: u64 = g.offset.try_into().map_err(|e| {
+            Status::invalid_argument(format!("the offset out of range({}): {e}", g.offset))
+        })?;
+        Ok(Self {
+            request_id,
+            topic_id,
+            group,
+            offset,
+        })
+    }
+}
@@ -0,0 +1,47 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::grp_svc::GetOffsetRequest;
+
+use super::name::group_checked;
+
+pub struct GetOffsetReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    group: String,
+}
+
+impl GetOffsetReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_group(&self) -> &str {
+        &self.group
+    }
+}
+
+impl TryFrom<&GetOffsetRequest> for GetOffsetReq {
+    type Error = Status;
+    fn try_from(g: &GetOffsetRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let group: String = group_checked(&g.group, request_id)?;
+        Ok(Self {
+            request_id,
+            topic_id,
+            group,
+        })
+    }
+}
@@ -0,0 +1,18 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+pub const GROUP_NAME_MAX: usize = 255;
+
+#[allow(clippy::result_large_err)]
+pub fn group_checked(group: &str, request_id: Uuid) -> Result<String, Status> {
+    match group.len() {
+        0 => Err(Status::invalid_argument(format!(
+            "group missing. request id: {request_id}"
+        ))),
+        1..=GROUP_NAME_MAX => Ok(group.into()),
+        n => Err(Status::invalid_argument(format!(
+            "group name too long({n} > {GROUP_NAME_MAX}). request id: {request_id}"
+        ))),
+    }
+}
@@ -0,0 +1,45 @@
+use core::ops::Deref;
+
+use tonic::{Request, Response, Status};
+
+use crate::db2q::proto::queue::v1::group_service_server::GroupService;
+
+use crate::db2q::proto::queue::v1::grp_svc::{CommitOffsetRequest, CommitOffsetResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{DeleteGroupRequest, DeleteGroupResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{GetOffsetRequest, GetOffsetResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{ListGroupsRequest, ListGroupsResponse};
+
+#[tonic::async_trait]
+impl<G> GroupService for G
+where
+    G: Sync + Send + 'static + Deref,
+    <G as Deref>::Target: GroupService,
+{
+    async fn commit_offset(
+        &self,
+        req: Request<CommitOffsetRequest>,
+    ) -> Result<Response<CommitOffsetResponse>, Status> {
+        self.deref().commit_offset(req).await
+    }
+
+    async fn get_offset(
+        &self,
+        req: Request<GetOffsetRequest>,
+    ) -> Result<Response<GetOffsetResponse>, Status> {
+        self.deref().get_offset(req).await
+    }
+
+    async fn list_groups(
+        &self,
+        req: Request<ListGroupsRequest>,
+    ) -> Result<Response<ListGroupsResponse>, Status> {
+        self.deref().list_groups(req).await
+    }
+
+    async fn delete_group(
+        &self,
+        req: Request<DeleteGroupRequest>,
+    ) -> Result<Response<DeleteGroupResponse>, Status> {
+        self.deref().delete_group(req).await
+    }
+}
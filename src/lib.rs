@@ -13,4 +13,11 @@ pub mod uuid;
 pub mod queue;
 
 pub mod count;
+pub mod group;
 pub mod topic;
+
+#[cfg(feature = "mem")]
+pub mod mem;
+
+#[cfg(feature = "conformance")]
+pub mod conformance;
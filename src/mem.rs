@@ -0,0 +1,6 @@
+pub mod store;
+
+pub mod count;
+pub mod queue;
+pub mod sweep;
+pub mod topic;
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use crate::count::cmd::exact::ExactReq;
+use crate::count::cmd::fast::FastReq;
+use crate::count::cmd::lag::LagReq;
+
+use crate::db2q::proto::queue::v1::cnt_svc::{ExactRequest, ExactResponse};
+use crate::db2q::proto::queue::v1::cnt_svc::{FastRequest, FastResponse};
+use crate::db2q::proto::queue::v1::cnt_svc::{LagRequest, LagResponse};
+use crate::db2q::proto::queue::v1::count_service_server::CountService;
+
+use crate::mem::store::Store;
+
+pub struct Svc {
+    store: Arc<Store>,
+}
+
+#[tonic::async_trait]
+impl CountService for Svc {
+    async fn exact(&self, req: Request<ExactRequest>) -> Result<Response<ExactResponse>, Status> {
+        let er: ExactRequest = req.into_inner();
+        let checked: ExactReq = (&er).try_into()?;
+        let keys = (
+            checked.as_lower().map(|u| u as i64),
+            checked.as_upper().map(|u| u as i64),
+        );
+        let pushed = (checked.as_since(), checked.as_until());
+        let cnt: u64 = self.store.count(checked.as_topic(), keys, pushed)?;
+        let reply = ExactResponse { count: cnt };
+        Ok(Response::new(reply))
+    }
+
+    // counted exactly: always cheap in memory
+    async fn fast(&self, req: Request<FastRequest>) -> Result<Response<FastResponse>, Status> {
+        let fr: FastRequest = req.into_inner();
+        let checked: FastReq = (&fr).try_into()?;
+        let cnt: u64 = self
+            .store
+            .count(checked.as_topic(), (None, None), (None, None))?;
+        let reply = FastResponse {
+            count_estimate: cnt,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn lag(&self, req: Request<LagRequest>) -> Result<Response<LagResponse>, Status> {
+        let lr: LagRequest = req.into_inner();
+        let checked: LagReq = (&lr).try_into()?;
+        if checked.as_group().is_some() {
+            return Err(Status::unimplemented("consumer groups not supported"));
+        }
+        let prev: i64 = checked.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let (remaining, oldest_age, newest) = self.store.lag(checked.as_topic(), prev)?;
+        let reply = LagResponse {
+            remaining,
+            oldest_age: oldest_age.and_then(|d| d.try_into().ok()),
+            newest,
+        };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn count_svc_new(store: &Arc<Store>) -> impl CountService {
+    Svc {
+        store: store.clone(),
+    }
+}
@@ -0,0 +1,455 @@
+use core::pin::Pin;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::sync::futures::Notified;
+use tokio::sync::{mpsc, Notify};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::uuid::Uuid;
+
+use crate::queue::cmd::ack::AckReq;
+use crate::queue::cmd::count::CountReq;
+use crate::queue::cmd::extend_lease::ExtendLeaseReq;
+use crate::queue::cmd::filter::Filter;
+use crate::queue::cmd::keys::KeysReq;
+use crate::queue::cmd::lease::LeaseReq;
+use crate::queue::cmd::nack::NackReq;
+use crate::queue::cmd::next::NextReq;
+use crate::queue::cmd::pop::PopFrontReq;
+use crate::queue::cmd::push::PushBackReq;
+use crate::queue::cmd::push_batch::PushBatchReq;
+use crate::queue::cmd::redrive::RedriveReq;
+use crate::queue::cmd::report_failure::ReportFailureReq;
+use crate::queue::cmd::subscribe::SubscribeReq;
+use crate::queue::cmd::wait_next::WaitNextReq;
+
+use crate::db2q::proto::queue::v1::q_svc::{AckRequest, AckResponse};
+use crate::db2q::proto::queue::v1::q_svc::{CountRequest, CountResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ExtendLeaseRequest, ExtendLeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{KeysRequest, KeysResponse};
+use crate::db2q::proto::queue::v1::q_svc::{LeaseRequest, LeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{NackRequest, NackResponse};
+use crate::db2q::proto::queue::v1::q_svc::{NextRequest, NextResponse};
+use crate::db2q::proto::queue::v1::q_svc::{PopFrontRequest, PopFrontResponse};
+use crate::db2q::proto::queue::v1::q_svc::{PushBackRequest, PushBackResponse};
+use crate::db2q::proto::queue::v1::q_svc::{PushBatchRequest, PushBatchResponse};
+use crate::db2q::proto::queue::v1::q_svc::{RedriveRequest, RedriveResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ReportFailureRequest, ReportFailureResponse};
+use crate::db2q::proto::queue::v1::q_svc::{SubscribeRequest, SubscribeResponse};
+use crate::db2q::proto::queue::v1::q_svc::{WaitNextRequest, WaitNextResponse};
+use crate::db2q::proto::queue::v1::queue_service_server::QueueService;
+
+use crate::mem::store::Store;
+
+fn groups_unsupported() -> Status {
+    Status::unimplemented("consumer groups not supported")
+}
+
+pub struct Svc {
+    store: Arc<Store>,
+}
+
+impl Svc {
+    // polls on elapsed: delayed items become visible without a push
+    async fn pushed_or_elapsed(notified: Pin<&mut Notified<'_>>, interval: Duration) {
+        match tokio::time::timeout(interval, notified).await {
+            Ok(_) => {}
+            Err(_) => log::debug!("no notification. polling..."),
+        }
+    }
+
+    pub async fn wait_next(
+        &self,
+        req: WaitNextReq,
+    ) -> Result<ReceiverStream<Result<WaitNextResponse, Status>>, Status> {
+        if req.as_group().is_some() {
+            return Err(groups_unsupported());
+        }
+        let topic_id: Uuid = req.as_topic_id();
+        let prev: Option<i64> = req.as_previous_key().map(|u| u as i64);
+        let start: Instant = Instant::now();
+        let interval: Duration = req.as_interval();
+        let timeout: Duration = req.as_timeout();
+        let filter: Option<Filter> = req.as_filter().cloned();
+        let (tx, rx) = mpsc::channel(1);
+        let store: Arc<Store> = self.store.clone();
+        tokio::spawn(async move {
+            let mut retry_cnt: u64 = 0;
+            loop {
+                let notify: Arc<Notify> = match store.watch(topic_id) {
+                    Ok(notify) => notify,
+                    Err(e) => {
+                        match tx.send(Err(e)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        }
+                        return;
+                    }
+                };
+                // registered before checking to avoid missing a notification
+                let notified = notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                match store.next(topic_id, prev, filter.as_ref()) {
+                    Ok(item) => {
+                        let elapsed: Duration = start.elapsed();
+                        let reply = WaitNextResponse {
+                            next: Some(item),
+                            elapsed: elapsed.try_into().ok(),
+                            retried: retry_cnt,
+                        };
+                        match tx.send(Ok(reply)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        };
+                        return;
+                    }
+                    Err(e) => match e.code() {
+                        Code::NotFound => {}
+                        _ => {
+                            match tx.send(Err(e)).await {
+                                Ok(_) => {}
+                                Err(e) => log::warn!("Unable to send: {e}"),
+                            }
+                            return;
+                        }
+                    },
+                }
+                let remaining: Duration = timeout.saturating_sub(start.elapsed());
+                if remaining.is_zero() {
+                    let e = Status::deadline_exceeded(format!(
+                        "timeout. topic={topic_id}, retried={retry_cnt}"
+                    ));
+                    match tx.send(Err(e)).await {
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Unable to send: {e}"),
+                    }
+                    return;
+                }
+                Self::pushed_or_elapsed(notified.as_mut(), interval.min(remaining)).await;
+                retry_cnt += 1;
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+
+    pub async fn subscribe(
+        &self,
+        req: SubscribeReq,
+    ) -> Result<ReceiverStream<Result<SubscribeResponse, Status>>, Status> {
+        let topic_id: Uuid = req.as_topic_id();
+        let mut prev: i64 = req.as_previous_key().map(|u| u as i64).unwrap_or(-1);
+        let batch_size: u64 = req.as_batch_size();
+        let limit: usize = batch_size as usize;
+        let interval: Duration = req.as_interval();
+        // bounded: a slow client stops the polling instead of buffering
+        let (tx, rx) = mpsc::channel(limit);
+        let store: Arc<Store> = self.store.clone();
+        tokio::spawn(async move {
+            loop {
+                let notify: Arc<Notify> = match store.watch(topic_id) {
+                    Ok(notify) => notify,
+                    Err(e) => {
+                        match tx.send(Err(e)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        }
+                        return;
+                    }
+                };
+                let notified = notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let items: Vec<NextResponse> = match store.next_batch(topic_id, prev, limit) {
+                    Ok(items) => items,
+                    Err(e) => {
+                        match tx.send(Err(e)).await {
+                            Ok(_) => {}
+                            Err(e) => log::warn!("Unable to send: {e}"),
+                        }
+                        return;
+                    }
+                };
+                if items.is_empty() {
+                    tokio::select! {
+                        _ = Self::pushed_or_elapsed(notified.as_mut(), interval) => {},
+                        _ = tx.closed() => { return },
+                    };
+                    continue;
+                }
+                for item in items {
+                    prev = item.next;
+                    let reply = SubscribeResponse { next: Some(item) };
+                    match tx.send(Ok(reply)).await {
+                        Ok(_) => {}
+                        Err(_) => return, // cancelled
+                    }
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+#[tonic::async_trait]
+impl QueueService for Svc {
+    async fn push_back(
+        &self,
+        req: Request<PushBackRequest>,
+    ) -> Result<Response<PushBackResponse>, Status> {
+        let pbr: PushBackRequest = req.into_inner();
+        let checked: PushBackReq = pbr.try_into()?;
+        let (key, pushed) = self.store.push(&checked)?;
+        let reply = PushBackResponse {
+            pushed: Some(pushed.into()),
+            key,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn push_batch(
+        &self,
+        req: Request<PushBatchRequest>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let pbr: PushBatchRequest = req.into_inner();
+        let checked: PushBatchReq = pbr.try_into()?;
+        let (keys, pushed) = self
+            .store
+            .push_batch(checked.as_topic_id(), checked.as_values())?;
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: SystemTime| t.into()),
+            keys,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn push_batch_stream(
+        &self,
+        req: Request<Streaming<PushBatchRequest>>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let mut chunks: Streaming<PushBatchRequest> = req.into_inner();
+        let first: PushBatchRequest = chunks
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("empty batch stream"))?;
+        let mut checked: PushBatchReq = first.try_into()?;
+        while let Some(chunk) = chunks.message().await? {
+            let next: PushBatchReq = chunk.try_into()?;
+            checked.append(next)?;
+        }
+        let (keys, pushed) = self
+            .store
+            .push_batch(checked.as_topic_id(), checked.as_values())?;
+        let reply = PushBatchResponse {
+            pushed: pushed.map(|t: SystemTime| t.into()),
+            keys,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn pop_front(
+        &self,
+        req: Request<PopFrontRequest>,
+    ) -> Result<Response<PopFrontResponse>, Status> {
+        let pfr: PopFrontRequest = req.into_inner();
+        let checked: PopFrontReq = pfr.try_into()?;
+        let item: NextResponse = self
+            .store
+            .pop(checked.as_topic_id(), checked.as_priority_order())?;
+        let popped: SystemTime = SystemTime::now();
+        let reply = PopFrontResponse {
+            popped: Some(popped.into()),
+            value: item.value,
+            headers: item.headers,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn count(&self, req: Request<CountRequest>) -> Result<Response<CountResponse>, Status> {
+        let cr: CountRequest = req.into_inner();
+        let checked: CountReq = cr.try_into()?;
+        let cnt: u64 = self
+            .store
+            .count(checked.as_topic_id(), (None, None), (None, None))?;
+        let reply = CountResponse { count: cnt };
+        Ok(Response::new(reply))
+    }
+
+    async fn next(&self, req: Request<NextRequest>) -> Result<Response<NextResponse>, Status> {
+        let nr: NextRequest = req.into_inner();
+        let checked: NextReq = (&nr).try_into()?;
+        if checked.as_group().is_some() {
+            return Err(groups_unsupported());
+        }
+        let topic_id: Uuid = checked.as_topic_id();
+        let prev_key: Option<i64> = checked.as_previous_key().map(|u| u as i64);
+        let filter: Option<&Filter> = checked.as_filter();
+        let reply: NextResponse = match checked.as_priority_order() {
+            false => self.store.next(topic_id, prev_key, filter)?,
+            true => {
+                let prev: Option<(i32, i64)> =
+                    prev_key.map(|k| (checked.as_previous_priority(), k));
+                self.store.next_prioritized(topic_id, prev, filter)?
+            }
+        };
+        Ok(Response::new(reply))
+    }
+
+    type WaitNextStream = ReceiverStream<Result<WaitNextResponse, Status>>;
+
+    async fn wait_next(
+        &self,
+        req: Request<WaitNextRequest>,
+    ) -> Result<Response<Self::WaitNextStream>, Status> {
+        let wnr: WaitNextRequest = req.into_inner();
+        let checked: WaitNextReq = (&wnr).try_into()?;
+        let reply: Self::WaitNextStream = self.wait_next(checked).await?;
+        Ok(Response::new(reply))
+    }
+
+    type SubscribeStream = ReceiverStream<Result<SubscribeResponse, Status>>;
+
+    async fn subscribe(
+        &self,
+        req: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let sr: SubscribeRequest = req.into_inner();
+        let checked: SubscribeReq = (&sr).try_into()?;
+        let reply: Self::SubscribeStream = self.subscribe(checked).await?;
+        Ok(Response::new(reply))
+    }
+
+    type KeysStream = ReceiverStream<Result<KeysResponse, Status>>;
+
+    async fn keys(&self, req: Request<KeysRequest>) -> Result<Response<Self::KeysStream>, Status> {
+        let kr: KeysRequest = req.into_inner();
+        let checked: KeysReq = (&kr).try_into()?;
+        let keys: Vec<KeysResponse> = self.store.keys(&checked)?;
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            for key in keys {
+                match tx.send(Ok(key)).await {
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("Error while sending keys: {e}");
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn lease(&self, req: Request<LeaseRequest>) -> Result<Response<LeaseResponse>, Status> {
+        let lr: LeaseRequest = req.into_inner();
+        let checked: LeaseReq = (&lr).try_into()?;
+        let lease_id: Uuid = Uuid::new_v4();
+        let (item, deadline) = self.store.lease(
+            checked.as_topic_id(),
+            lease_id,
+            checked.as_visibility_timeout(),
+        )?;
+        let headers: HashMap<String, Vec<u8>> = item.headers;
+        let reply = LeaseResponse {
+            key: item.next,
+            value: item.value,
+            lease_id: Some(lease_id.into()),
+            deadline: Some(deadline.into()),
+            headers,
+            attempts: item.attempts,
+            last_error: item.last_error,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn ack(&self, req: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        let ar: AckRequest = req.into_inner();
+        let checked: AckReq = (&ar).try_into()?;
+        self.store.ack(
+            checked.as_topic_id(),
+            checked.as_key() as i64,
+            checked.as_lease_id(),
+        )?;
+        let acked: SystemTime = SystemTime::now();
+        let reply = AckResponse {
+            acked: Some(acked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn nack(&self, req: Request<NackRequest>) -> Result<Response<NackResponse>, Status> {
+        let nr: NackRequest = req.into_inner();
+        let checked: NackReq = (&nr).try_into()?;
+        self.store.nack(
+            checked.as_topic_id(),
+            checked.as_key() as i64,
+            checked.as_lease_id(),
+            checked.as_delay(),
+        )?;
+        let nacked: SystemTime = SystemTime::now();
+        let reply = NackResponse {
+            nacked: Some(nacked.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn extend_lease(
+        &self,
+        req: Request<ExtendLeaseRequest>,
+    ) -> Result<Response<ExtendLeaseResponse>, Status> {
+        let er: ExtendLeaseRequest = req.into_inner();
+        let checked: ExtendLeaseReq = (&er).try_into()?;
+        let deadline: SystemTime = self.store.extend_lease(
+            checked.as_topic_id(),
+            checked.as_key() as i64,
+            checked.as_lease_id(),
+            checked.as_extension(),
+        )?;
+        let reply = ExtendLeaseResponse {
+            deadline: Some(deadline.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn report_failure(
+        &self,
+        req: Request<ReportFailureRequest>,
+    ) -> Result<Response<ReportFailureResponse>, Status> {
+        let rfr: ReportFailureRequest = req.into_inner();
+        let checked: ReportFailureReq = rfr.try_into()?;
+        let (attempts, dead_lettered) = self.store.report_failure(
+            checked.as_topic_id(),
+            checked.as_key() as i64,
+            checked.as_error(),
+        )?;
+        let reply = ReportFailureResponse {
+            attempts,
+            dead_lettered,
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn redrive(
+        &self,
+        req: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        let rr: RedriveRequest = req.into_inner();
+        let checked: RedriveReq = (&rr).try_into()?;
+        let redriven: u64 = self
+            .store
+            .redrive(checked.as_topic_id(), checked.as_max_messages())?;
+        let reply = RedriveResponse { redriven };
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn queue_svc_new(store: &Arc<Store>) -> impl QueueService {
+    Svc {
+        store: store.clone(),
+    }
+}
@@ -0,0 +1,770 @@
+use core::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use tokio::sync::Notify;
+
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::queue::cmd::filter::{matches, Filter};
+use crate::queue::cmd::keys::KeysReq;
+use crate::queue::cmd::push::{PushBackReq, DEDUP_WINDOW};
+use crate::topic::cmd::create::CreateReq;
+use crate::topic::cmd::retention::Retention;
+
+use crate::db2q::proto::queue::v1::q_svc::{KeysResponse, NextResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{
+    DeadLetter as DeadLetterPb, DescribeResponse, Retention as RetentionPb,
+};
+
+struct Message {
+    val: Vec<u8>,
+    headers: HashMap<String, Vec<u8>>,
+    priority: i32,
+    pushed: SystemTime,
+    visible_at: SystemTime,
+    expires_at: Option<SystemTime>,
+    leased_until: Option<SystemTime>,
+    lease_id: Option<u128>,
+    attempts: u64,
+    last_error: Option<String>,
+}
+
+impl Message {
+    fn alive(&self, now: SystemTime) -> bool {
+        self.expires_at.map(|t| now < t).unwrap_or(true)
+    }
+
+    fn visible(&self, now: SystemTime) -> bool {
+        self.visible_at <= now && self.alive(now)
+    }
+
+    fn leasable(&self, now: SystemTime) -> bool {
+        self.visible(now) && self.leased_until.map(|t| t <= now).unwrap_or(true)
+    }
+
+    fn leased(&self, lease_id: Uuid, now: SystemTime) -> bool {
+        self.lease_id == Some(lease_id.as_u128())
+            && self.leased_until.map(|t| now < t).unwrap_or_default()
+    }
+
+    fn matches(&self, filter: Option<&Filter>) -> bool {
+        matches(filter, &self.val, &self.headers)
+    }
+
+    fn item(&self, key: i64) -> NextResponse {
+        NextResponse {
+            next: key,
+            value: self.val.clone(),
+            priority: self.priority,
+            headers: self.headers.clone(),
+            attempts: self.attempts,
+            last_error: self.last_error.clone().unwrap_or_default(),
+        }
+    }
+
+    fn into_item(self, key: i64) -> NextResponse {
+        NextResponse {
+            next: key,
+            value: self.val,
+            priority: self.priority,
+            headers: self.headers,
+            attempts: self.attempts,
+            last_error: self.last_error.unwrap_or_default(),
+        }
+    }
+
+    // a copy pushed to another topic(dead letters, redrives)
+    fn moved(self, now: SystemTime, expires_at: Option<SystemTime>, keep_attempts: bool) -> Self {
+        let (attempts, last_error) = match keep_attempts {
+            true => (self.attempts, self.last_error),
+            false => (0, None),
+        };
+        Self {
+            val: self.val,
+            headers: self.headers,
+            priority: self.priority,
+            pushed: now,
+            visible_at: now,
+            expires_at,
+            leased_until: None,
+            lease_id: None,
+            attempts,
+            last_error,
+        }
+    }
+}
+
+struct Limits {
+    max_messages: Option<u64>,
+    max_age: Option<Duration>,
+    max_bytes: Option<u64>,
+}
+
+impl From<Option<&Retention>> for Limits {
+    fn from(r: Option<&Retention>) -> Self {
+        Self {
+            max_messages: r.and_then(|r| r.as_max_messages()),
+            max_age: r.and_then(|r| r.as_max_age()),
+            max_bytes: r.and_then(|r| r.as_max_bytes()),
+        }
+    }
+}
+
+impl Limits {
+    fn to_pb(&self) -> Option<RetentionPb> {
+        match (self.max_messages, self.max_age, self.max_bytes) {
+            (None, None, None) => None,
+            _ => Some(RetentionPb {
+                max_messages: self.max_messages.unwrap_or_default(),
+                max_age: self.max_age.and_then(|d| d.try_into().ok()),
+                max_bytes: self.max_bytes.unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+struct Topic {
+    created: SystemTime,
+    dead_letter: Option<(Uuid, u64)>,
+    safe_tail: bool,
+    default_ttl: Option<Duration>,
+    priority: bool,
+    limits: Limits,
+    // keys are never reused(like a sequence)
+    last_key: i64,
+    messages: BTreeMap<i64, Message>,
+    // kept for the dedup window even after the items are removed
+    req_ids: HashMap<u128, (i64, SystemTime)>,
+    notify: Arc<Notify>,
+}
+
+impl Topic {
+    fn expires_at(&self, now: SystemTime, ttl: Option<Duration>) -> Option<SystemTime> {
+        ttl.or(self.default_ttl).and_then(|d| now.checked_add(d))
+    }
+
+    fn insert(&mut self, msg: Message) -> i64 {
+        self.last_key += 1;
+        let key: i64 = self.last_key;
+        self.messages.insert(key, msg);
+        key
+    }
+
+    fn remove(&mut self, key: i64) -> Option<Message> {
+        self.messages.remove(&key)
+    }
+
+    // the highest key to be trimmed(if any)
+    fn boundary(&self, now: SystemTime) -> Option<i64> {
+        let l: &Limits = &self.limits;
+        let by_count: Option<i64> = l.max_messages.and_then(|n| {
+            let skip: usize = n.try_into().unwrap_or(usize::MAX);
+            self.messages.keys().rev().nth(skip).copied()
+        });
+        let by_age: Option<i64> =
+            l.max_age
+                .and_then(|age| now.checked_sub(age))
+                .and_then(|oldest| {
+                    self.messages
+                        .iter()
+                        .rev()
+                        .find(|(_, m)| m.pushed < oldest)
+                        .map(|(k, _)| *k)
+                });
+        let by_bytes: Option<i64> = l.max_bytes.and_then(|max| {
+            let mut total: u64 = 0;
+            self.messages.iter().rev().find_map(|(k, m)| {
+                total = total.saturating_add(m.val.len() as u64);
+                (max < total).then_some(*k)
+            })
+        });
+        [by_count, by_age, by_bytes].into_iter().flatten().max()
+    }
+
+    // expired messages, messages out of the retention and request ids out of the dedup window
+    fn sweep(&mut self, now: SystemTime) -> u64 {
+        let boundary: i64 = self.boundary(now).unwrap_or(-1);
+        let swept: Vec<i64> = self
+            .messages
+            .iter()
+            .filter(|(k, m)| **k <= boundary || !m.alive(now))
+            .map(|(k, _)| *k)
+            .collect();
+        for key in &swept {
+            self.remove(*key);
+        }
+        let oldest: Option<SystemTime> = now.checked_sub(DEDUP_WINDOW);
+        self.req_ids
+            .retain(|_, (_, pushed)| oldest.map(|o| o <= *pushed).unwrap_or(true));
+        swept.len() as u64
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn lease_mut(
+        &mut self,
+        key: i64,
+        lease_id: Uuid,
+        now: SystemTime,
+    ) -> Result<&mut Message, Status> {
+        self.messages
+            .get_mut(&key)
+            .filter(|m| m.leased(lease_id, now))
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No such lease(expired?). key: {key}, lease id: {lease_id}"
+                ))
+            })
+    }
+}
+
+// the same codes as a missing table of the rdb backends
+fn missing(topic_id: Uuid, msg: &str) -> Status {
+    Status::internal(format!("{msg}: no such topic: {topic_id}"))
+}
+
+fn missing_to_push(topic_id: Uuid) -> Status {
+    Status::not_found(format!("No such topic: {topic_id}"))
+}
+
+#[derive(Default)]
+pub struct Store {
+    topics: Mutex<BTreeMap<u128, Topic>>,
+}
+
+impl Store {
+    #[allow(clippy::result_large_err)]
+    fn lock(&self) -> Result<MutexGuard<'_, BTreeMap<u128, Topic>>, Status> {
+        self.topics
+            .lock()
+            .map_err(|e| Status::internal(format!("Unable to lock: {e}")))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn with_topic<F, R>(&self, topic_id: Uuid, msg: &str, f: F) -> Result<R, Status>
+    where
+        F: FnOnce(&mut Topic, SystemTime) -> Result<R, Status>,
+    {
+        self.with_topic_or(topic_id, || missing(topic_id, msg), f)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn with_topic_or<M, F, R>(&self, topic_id: Uuid, missing: M, f: F) -> Result<R, Status>
+    where
+        M: FnOnce() -> Status,
+        F: FnOnce(&mut Topic, SystemTime) -> Result<R, Status>,
+    {
+        let mut topics = self.lock()?;
+        let topic: &mut Topic = topics.get_mut(&topic_id.as_u128()).ok_or_else(missing)?;
+        f(topic, SystemTime::now())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn create(&self, checked: &CreateReq) -> Result<(), Status> {
+        let topic_id: Uuid = checked.as_topic_id();
+        let mut topics = self.lock()?;
+        if topics.contains_key(&topic_id.as_u128()) {
+            return Err(Status::internal(format!(
+                "Unexpected error: topic already exists: {topic_id}"
+            )));
+        }
+        let dead_letter: Option<(Uuid, u64)> = checked
+            .as_dead_letter()
+            .map(|d| (d.as_topic_id(), d.as_max_attempts()));
+        if let Some((dlq, _)) = dead_letter {
+            let found: bool =
+                dlq.as_u128() == topic_id.as_u128() || topics.contains_key(&dlq.as_u128());
+            if !found {
+                return Err(Status::failed_precondition(format!(
+                    "dead letter topic missing: {dlq}"
+                )));
+            }
+        }
+        let topic = Topic {
+            created: SystemTime::now(),
+            dead_letter,
+            safe_tail: checked.as_safe_tail(),
+            default_ttl: checked.as_default_ttl(),
+            priority: checked.as_priority(),
+            limits: checked.as_retention().into(),
+            last_key: 0,
+            messages: BTreeMap::new(),
+            req_ids: HashMap::new(),
+            notify: Arc::new(Notify::new()),
+        };
+        topics.insert(topic_id.as_u128(), topic);
+        Ok(())
+    }
+
+    // waiters of the dropped topic get an error on their next check
+    #[allow(clippy::result_large_err)]
+    pub fn drop_topic(&self, topic_id: Uuid) -> Result<(), Status> {
+        let mut topics = self.lock()?;
+        let used: bool = topics.iter().any(|(id, t)| {
+            *id != topic_id.as_u128()
+                && t.dead_letter
+                    .map(|(dlq, _)| dlq.as_u128() == topic_id.as_u128())
+                    .unwrap_or_default()
+        });
+        if used {
+            return Err(Status::failed_precondition(format!(
+                "still used as a dead letter topic: {topic_id}"
+            )));
+        }
+        if let Some(dropped) = topics.remove(&topic_id.as_u128()) {
+            dropped.notify.notify_waiters();
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn list(&self) -> Result<Vec<Uuid>, Status> {
+        let topics = self.lock()?;
+        Ok(topics.keys().map(|id: &u128| Uuid::from(*id)).collect())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn update_retention(
+        &self,
+        topic_id: Uuid,
+        retention: Option<&Retention>,
+    ) -> Result<(), Status> {
+        let mut topics = self.lock()?;
+        let topic: &mut Topic = topics
+            .get_mut(&topic_id.as_u128())
+            .ok_or_else(|| Status::not_found(format!("No such topic: {topic_id}")))?;
+        topic.limits = retention.into();
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn describe(&self, topic_id: Uuid) -> Result<DescribeResponse, Status> {
+        let topics = self.lock()?;
+        let topic: &Topic = topics
+            .get(&topic_id.as_u128())
+            .ok_or_else(|| Status::not_found(format!("No such topic: {topic_id}")))?;
+        let payload_bytes: u64 = topic.messages.values().map(|m| m.val.len() as u64).sum();
+        let header_bytes: u64 = topic
+            .messages
+            .values()
+            .flat_map(|m| m.headers.iter())
+            .map(|(k, v)| (k.len() + v.len()) as u64)
+            .sum();
+        let described = DescribeResponse {
+            min_key: topic.messages.keys().next().copied().unwrap_or(-1),
+            max_key: topic.messages.keys().next_back().copied().unwrap_or(-1),
+            count_estimate: topic.messages.len() as u64,
+            payload_bytes,
+            relation_size: payload_bytes + header_bytes,
+            created: Some(topic.created.into()),
+            dead_letter: topic.dead_letter.map(|(dlq, max_attempts)| DeadLetterPb {
+                topic_id: Some(dlq.into()),
+                max_attempts,
+            }),
+            safe_tail: topic.safe_tail,
+            default_ttl: topic.default_ttl.and_then(|d| d.try_into().ok()),
+            retention: topic.limits.to_pb(),
+            priority: topic.priority,
+        };
+        Ok(described)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn watch(&self, topic_id: Uuid) -> Result<Arc<Notify>, Status> {
+        self.with_topic(topic_id, "Unable to select", |t, _| Ok(t.notify.clone()))
+    }
+
+    // the original key and the pushed time for a duplicate request
+    #[allow(clippy::result_large_err)]
+    pub fn push(&self, checked: &PushBackReq) -> Result<(i64, SystemTime), Status> {
+        let req_id: u128 = checked.as_request_id().as_u128();
+        let topic_id: Uuid = checked.as_topic_id();
+        self.with_topic_or(
+            topic_id,
+            || missing_to_push(topic_id),
+            |t, now| {
+                let oldest: Option<SystemTime> = now.checked_sub(DEDUP_WINDOW);
+                match t.req_ids.get(&req_id) {
+                    Some((key, pushed)) if oldest.map(|o| o <= *pushed).unwrap_or(true) => {
+                        return Ok((*key, *pushed));
+                    }
+                    _ => {}
+                }
+                let visible_at: SystemTime = checked
+                    .as_not_before()
+                    .or_else(|| now.checked_add(checked.as_delay().unwrap_or_default()))
+                    .unwrap_or(now);
+                let msg = Message {
+                    val: checked.as_value().into(),
+                    headers: checked.as_headers().clone(),
+                    priority: checked.as_priority(),
+                    pushed: now,
+                    visible_at,
+                    expires_at: t.expires_at(now, checked.as_ttl()),
+                    leased_until: None,
+                    lease_id: None,
+                    attempts: 0,
+                    last_error: None,
+                };
+                let key: i64 = t.insert(msg);
+                t.req_ids.insert(req_id, (key, now));
+                t.notify.notify_waiters();
+                Ok((key, now))
+            },
+        )
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn push_batch(
+        &self,
+        topic_id: Uuid,
+        vals: &[Vec<u8>],
+    ) -> Result<(Vec<i64>, Option<SystemTime>), Status> {
+        self.with_topic_or(
+            topic_id,
+            || missing_to_push(topic_id),
+            |t, now| {
+                let expires_at: Option<SystemTime> = t.expires_at(now, None);
+                let keys: Vec<i64> = vals
+                    .iter()
+                    .map(|v: &Vec<u8>| {
+                        t.insert(Message {
+                            val: v.clone(),
+                            headers: HashMap::new(),
+                            priority: 0,
+                            pushed: now,
+                            visible_at: now,
+                            expires_at,
+                            leased_until: None,
+                            lease_id: None,
+                            attempts: 0,
+                            last_error: None,
+                        })
+                    })
+                    .collect();
+                t.notify.notify_waiters();
+                Ok((keys.clone(), (!keys.is_empty()).then_some(now)))
+            },
+        )
+    }
+
+    // unset bounds are the widest ones
+    #[allow(clippy::result_large_err)]
+    pub fn count(
+        &self,
+        topic_id: Uuid,
+        keys: (Option<i64>, Option<i64>),
+        pushed: (Option<SystemTime>, Option<SystemTime>),
+    ) -> Result<u64, Status> {
+        self.with_topic(topic_id, "Unable to count", |t, now| {
+            let (lower, upper) = keys;
+            let (since, until) = pushed;
+            let lower: i64 = lower.unwrap_or(0);
+            let upper: i64 = upper.unwrap_or(i64::MAX);
+            if upper <= lower {
+                return Ok(0);
+            }
+            let cnt: usize = t
+                .messages
+                .range(lower..upper)
+                .filter(|(_, m)| m.alive(now))
+                .filter(|(_, m)| since.map(|s| s <= m.pushed).unwrap_or(true))
+                .filter(|(_, m)| until.map(|u| m.pushed < u).unwrap_or(true))
+                .count();
+            Ok(cnt as u64)
+        })
+    }
+
+    // (remaining, age of the oldest remaining, newest key)
+    #[allow(clippy::result_large_err)]
+    pub fn lag(&self, topic_id: Uuid, prev: i64) -> Result<(u64, Option<Duration>, i64), Status> {
+        self.with_topic(topic_id, "Unable to get a lag", |t, now| {
+            let mut remaining = t
+                .messages
+                .range(prev.saturating_add(1)..)
+                .filter(|(_, m)| m.alive(now))
+                .peekable();
+            let oldest_age: Option<Duration> = remaining
+                .peek()
+                .map(|(_, m)| now.duration_since(m.pushed).unwrap_or_default());
+            let cnt: u64 = remaining.count() as u64;
+            let newest: i64 = t.messages.keys().next_back().copied().unwrap_or(-1);
+            Ok((cnt, oldest_age, newest))
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn next(
+        &self,
+        topic_id: Uuid,
+        prev: Option<i64>,
+        filter: Option<&Filter>,
+    ) -> Result<NextResponse, Status> {
+        self.with_topic(topic_id, "Unable to select", |t, now| {
+            let lower: i64 = prev.unwrap_or(-1).saturating_add(1);
+            t.messages
+                .range(lower..)
+                .find(|(_, m)| m.visible(now) && m.matches(filter))
+                .map(|(k, m)| m.item(*k))
+                .ok_or_else(|| match prev {
+                    None => Status::not_found("Empty queue"),
+                    Some(prev) => {
+                        Status::not_found(format!("No more queue items. previous key: {prev}"))
+                    }
+                })
+        })
+    }
+
+    // (priority desc, key) order
+    #[allow(clippy::result_large_err)]
+    pub fn next_prioritized(
+        &self,
+        topic_id: Uuid,
+        prev: Option<(i32, i64)>,
+        filter: Option<&Filter>,
+    ) -> Result<NextResponse, Status> {
+        self.with_topic(topic_id, "Unable to select", |t, now| {
+            let after = |priority: i32, key: i64| match prev {
+                None => true,
+                Some((p, k)) => priority < p || (priority == p && k < key),
+            };
+            t.messages
+                .iter()
+                .filter(|(k, m)| after(m.priority, **k))
+                .filter(|(_, m)| m.visible(now) && m.matches(filter))
+                .min_by_key(|(k, m)| (-(m.priority as i64), **k))
+                .map(|(k, m)| m.item(*k))
+                .ok_or_else(|| {
+                    Status::not_found(format!("No more queue items. previous: {prev:?}"))
+                })
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn next_batch(
+        &self,
+        topic_id: Uuid,
+        prev: i64,
+        limit: usize,
+    ) -> Result<Vec<NextResponse>, Status> {
+        self.with_topic(topic_id, "Unable to select", |t, now| {
+            let items: Vec<NextResponse> = t
+                .messages
+                .range(prev.saturating_add(1)..)
+                .filter(|(_, m)| m.visible(now))
+                .take(limit)
+                .map(|(k, m)| m.item(*k))
+                .collect();
+            Ok(items)
+        })
+    }
+
+    // the continuation is set on the last key of a full page
+    #[allow(clippy::result_large_err)]
+    pub fn keys(&self, checked: &KeysReq) -> Result<Vec<KeysResponse>, Status> {
+        self.with_topic(checked.as_topic_id(), "Unable to get keys", |t, now| {
+            let (start, end) = (
+                checked.as_start_after().map(|u| u as i64),
+                checked.as_end_before().map(|u| u as i64),
+            );
+            let (lower, upper) = match checked.as_descending() {
+                false => (start, end),
+                true => (end, start),
+            };
+            let lower: i64 = lower.unwrap_or(0);
+            let upper: i64 = upper.unwrap_or(i64::MAX);
+            if upper <= lower.saturating_add(1) {
+                return Ok(Vec::new());
+            }
+            let range = t.messages.range(lower + 1..upper);
+            let found: Box<dyn Iterator<Item = (&i64, &Message)>> = match checked.as_descending() {
+                false => Box::new(range),
+                true => Box::new(range.rev()),
+            };
+            let limit: u64 = checked.as_max_keys();
+            let mut keys: Vec<KeysResponse> = Vec::new();
+            for (key, _) in found.filter(|(_, m)| m.visible(now) && m.matches(checked.as_filter()))
+            {
+                if limit <= keys.len() as u64 {
+                    if let Some(last) = keys.last_mut() {
+                        last.continuation = last.key as i64;
+                    }
+                    break;
+                }
+                keys.push(KeysResponse {
+                    key: *key as u64,
+                    continuation: -1,
+                });
+            }
+            Ok(keys)
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn pop(&self, topic_id: Uuid, priority_order: bool) -> Result<NextResponse, Status> {
+        self.with_topic(topic_id, "Unable to delete", |t, now| {
+            let leasable = t.messages.iter().filter(|(_, m)| m.leasable(now));
+            let found: Option<i64> = match priority_order {
+                false => leasable.map(|(k, _)| *k).next(),
+                true => leasable
+                    .min_by_key(|(k, m)| (-(m.priority as i64), **k))
+                    .map(|(k, _)| *k),
+            };
+            let key: i64 = found.ok_or_else(|| Status::not_found("Empty queue"))?;
+            let popped: Message = t
+                .remove(key)
+                .ok_or_else(|| Status::not_found("Empty queue"))?;
+            Ok(popped.into_item(key))
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn lease(
+        &self,
+        topic_id: Uuid,
+        lease_id: Uuid,
+        timeout: Duration,
+    ) -> Result<(NextResponse, SystemTime), Status> {
+        self.with_topic(topic_id, "Unable to lease", |t, now| {
+            let deadline: SystemTime = now.checked_add(timeout).ok_or_else(|| {
+                Status::invalid_argument("visibility timeout too large".to_string())
+            })?;
+            let (key, msg) = t
+                .messages
+                .iter_mut()
+                .find(|(_, m)| m.leasable(now))
+                .ok_or_else(|| Status::not_found("No visible queue items"))?;
+            msg.leased_until = Some(deadline);
+            msg.lease_id = Some(lease_id.as_u128());
+            Ok((msg.item(*key), deadline))
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn ack(&self, topic_id: Uuid, key: i64, lease_id: Uuid) -> Result<(), Status> {
+        self.with_topic(topic_id, "Unable to ack", |t, now| {
+            t.lease_mut(key, lease_id, now)?;
+            t.remove(key);
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn nack(
+        &self,
+        topic_id: Uuid,
+        key: i64,
+        lease_id: Uuid,
+        delay: Option<Duration>,
+    ) -> Result<(), Status> {
+        self.with_topic(topic_id, "Unable to nack", |t, now| {
+            let until: SystemTime = now
+                .checked_add(delay.unwrap_or_default())
+                .ok_or_else(|| Status::invalid_argument("delay too large".to_string()))?;
+            let msg: &mut Message = t.lease_mut(key, lease_id, now)?;
+            msg.leased_until = Some(until);
+            msg.lease_id = None;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn extend_lease(
+        &self,
+        topic_id: Uuid,
+        key: i64,
+        lease_id: Uuid,
+        extension: Duration,
+    ) -> Result<SystemTime, Status> {
+        self.with_topic(topic_id, "Unable to extend a lease", |t, now| {
+            let deadline: SystemTime = now
+                .checked_add(extension)
+                .ok_or_else(|| Status::invalid_argument("extension too large".to_string()))?;
+            let msg: &mut Message = t.lease_mut(key, lease_id, now)?;
+            msg.leased_until = Some(deadline);
+            Ok(deadline)
+        })
+    }
+
+    // (attempts, dead lettered)
+    #[allow(clippy::result_large_err)]
+    pub fn report_failure(
+        &self,
+        topic_id: Uuid,
+        key: i64,
+        error: &str,
+    ) -> Result<(u64, bool), Status> {
+        let mut topics = self.lock()?;
+        let topic: &mut Topic = topics
+            .get_mut(&topic_id.as_u128())
+            .ok_or_else(|| missing(topic_id, "Unable to record a failure"))?;
+        let msg: &mut Message = topic
+            .messages
+            .get_mut(&key)
+            .ok_or_else(|| Status::not_found(format!("No such queue item. key: {key}")))?;
+        msg.attempts += 1;
+        msg.last_error = Some(error.into());
+        let attempts: u64 = msg.attempts;
+        let dlq: Uuid = match topic.dead_letter {
+            Some((dlq, max_attempts)) if max_attempts <= attempts => dlq,
+            _ => return Ok((attempts, false)),
+        };
+        let failed: Message = topic
+            .remove(key)
+            .ok_or_else(|| Status::internal(format!("No such queue item. key: {key}")))?;
+        let dead: &mut Topic = topics
+            .get_mut(&dlq.as_u128())
+            .ok_or_else(|| missing(dlq, "Unable to move to a dead letter topic"))?;
+        let now: SystemTime = SystemTime::now();
+        let expires_at: Option<SystemTime> = dead.expires_at(now, None);
+        dead.insert(failed.moved(now, expires_at, true));
+        dead.notify.notify_waiters();
+        Ok((attempts, true))
+    }
+
+    // oldest dead letters first
+    #[allow(clippy::result_large_err)]
+    pub fn redrive(&self, topic_id: Uuid, limit: Option<u64>) -> Result<u64, Status> {
+        let mut topics = self.lock()?;
+        let (dlq, _) = topics
+            .get(&topic_id.as_u128())
+            .and_then(|t| t.dead_letter)
+            .ok_or_else(|| {
+                Status::failed_precondition(format!("No dead letter topic configured: {topic_id}"))
+            })?;
+        let dead: &mut Topic = topics
+            .get_mut(&dlq.as_u128())
+            .ok_or_else(|| missing(dlq, "Unable to redrive"))?;
+        let limit: usize = limit
+            .map(|u| u.try_into().unwrap_or(usize::MAX))
+            .unwrap_or(usize::MAX);
+        let keys: Vec<i64> = dead.messages.keys().take(limit).copied().collect();
+        let redriven: Vec<Message> = keys.iter().filter_map(|k| dead.remove(*k)).collect();
+        let topic: &mut Topic = topics
+            .get_mut(&topic_id.as_u128())
+            .ok_or_else(|| missing(topic_id, "Unable to redrive"))?;
+        let now: SystemTime = SystemTime::now();
+        let expires_at: Option<SystemTime> = topic.expires_at(now, None);
+        let cnt: u64 = redriven.len() as u64;
+        for msg in redriven {
+            topic.insert(msg.moved(now, expires_at, false));
+        }
+        topic.notify.notify_waiters();
+        Ok(cnt)
+    }
+
+    // removes expired messages and trims topics to their retentions
+    #[allow(clippy::result_large_err)]
+    pub fn sweep(&self) -> Result<u64, Status> {
+        let mut topics = self.lock()?;
+        let now: SystemTime = SystemTime::now();
+        Ok(topics.values_mut().map(|t| t.sweep(now)).sum())
+    }
+}
+
+pub fn store_new() -> Arc<Store> {
+    Arc::new(Store::default())
+}
@@ -0,0 +1,23 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+
+use crate::mem::store::Store;
+
+pub const INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+
+// expired messages and retentions(no per-write cleanup)
+pub fn sweeper_new(store: &Arc<Store>, interval: Duration) -> JoinHandle<()> {
+    let store: Arc<Store> = store.clone();
+    tokio::spawn(async move {
+        loop {
+            match store.sweep() {
+                Ok(0) => {}
+                Ok(cnt) => log::debug!("Messages swept: {cnt}"),
+                Err(e) => log::warn!("Unable to sweep: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tonic::{Request, Response, Status};
+
+use crate::uuid::Uuid;
+
+use crate::topic::cmd::create::CreateReq;
+use crate::topic::cmd::describe::DescribeReq;
+use crate::topic::cmd::drop::DropReq;
+use crate::topic::cmd::list::ListReq;
+use crate::topic::cmd::update_retention::UpdateRetentionReq;
+
+use crate::db2q::proto::queue::v1::Uuid as Guid;
+
+use crate::db2q::proto::queue::v1::topic_service_server::TopicService;
+use crate::db2q::proto::queue::v1::topic_svc::{CreateRequest, CreateResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{DescribeRequest, DescribeResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{DropRequest, DropResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{ListRequest, ListResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{UpdateRetentionRequest, UpdateRetentionResponse};
+
+use crate::mem::store::Store;
+
+pub struct Svc {
+    store: Arc<Store>,
+}
+
+#[tonic::async_trait]
+impl TopicService for Svc {
+    async fn create(
+        &self,
+        req: Request<CreateRequest>,
+    ) -> Result<Response<CreateResponse>, Status> {
+        let cr: CreateRequest = req.into_inner();
+        let checked: CreateReq = (&cr).try_into()?;
+        self.store.create(&checked)?;
+        let created: SystemTime = SystemTime::now();
+        let reply = CreateResponse {
+            created: Some(created.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn drop(&self, req: Request<DropRequest>) -> Result<Response<DropResponse>, Status> {
+        let cr: DropRequest = req.into_inner();
+        let checked: DropReq = (&cr).try_into()?;
+        self.store.drop_topic(checked.as_topic_id())?;
+        let dropped: SystemTime = SystemTime::now();
+        let reply = DropResponse {
+            dropped: Some(dropped.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn list(&self, req: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        let lr: ListRequest = req.into_inner();
+        let _checked: ListReq = (&lr).try_into()?;
+        let topics: Vec<Guid> = self
+            .store
+            .list()?
+            .into_iter()
+            .map(|id: Uuid| id.into())
+            .collect();
+        let reply = ListResponse { topics };
+        Ok(Response::new(reply))
+    }
+
+    async fn update_retention(
+        &self,
+        req: Request<UpdateRetentionRequest>,
+    ) -> Result<Response<UpdateRetentionResponse>, Status> {
+        let ur: UpdateRetentionRequest = req.into_inner();
+        let checked: UpdateRetentionReq = (&ur).try_into()?;
+        self.store
+            .update_retention(checked.as_topic_id(), checked.as_retention())?;
+        let updated: SystemTime = SystemTime::now();
+        let reply = UpdateRetentionResponse {
+            updated: Some(updated.into()),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn describe(
+        &self,
+        req: Request<DescribeRequest>,
+    ) -> Result<Response<DescribeResponse>, Status> {
+        let dr: DescribeRequest = req.into_inner();
+        let checked: DescribeReq = (&dr).try_into()?;
+        let reply: DescribeResponse = self.store.describe(checked.as_topic_id())?;
+        Ok(Response::new(reply))
+    }
+}
+
+pub fn topic_svc_new(store: &Arc<Store>) -> impl TopicService {
+    Svc {
+        store: store.clone(),
+    }
+}
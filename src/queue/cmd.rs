@@ -1,7 +1,19 @@
 pub mod pop;
 pub mod push;
+pub mod push_batch;
 
 pub mod count;
+pub mod filter;
+pub mod headers;
 pub mod keys;
 pub mod next;
+pub mod subscribe;
 pub mod wait_next;
+
+pub mod ack;
+pub mod extend_lease;
+pub mod lease;
+pub mod nack;
+
+pub mod redrive;
+pub mod report_failure;
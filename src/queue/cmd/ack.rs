@@ -0,0 +1,56 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::q_svc::AckRequest;
+
+pub struct AckReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    key: u64,
+    lease_id: Uuid,
+}
+
+impl AckReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn as_lease_id(&self) -> Uuid {
+        self.lease_id
+    }
+}
+
+impl TryFrom<&AckRequest> for AckReq {
+    type Error = Status;
+    fn try_from(g: &AckRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let key: u64 = g.key.try_into().map_err(|e| {
+            Status::invalid_argument(format!("the key out of range({}): {e}", g.key))
+        })?;
+        let lease_id: Uuid = g.lease_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("lease id missing. request id: {request_id}"))
+        })?;
+        Ok(Self {
+            request_id,
+            topic_id,
+            key,
+            lease_id,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::q_svc::Filter as FilterPb;
+
+// keeps the generated query small
+pub const CONDITIONS_MAX: usize = 16;
+
+#[derive(Clone)]
+pub enum Condition {
+    HeaderEq { name: String, value: Vec<u8> },
+    HeaderPrefix { name: String, prefix: Vec<u8> },
+    HeaderIn { name: String, values: Vec<Vec<u8>> },
+    ValuePrefix(Vec<u8>),
+}
+
+#[derive(Clone)]
+pub struct Filter {
+    conditions: Vec<Condition>,
+}
+
+impl Condition {
+    // None for the conditions on the value
+    pub fn matches_headers(&self, headers: &HashMap<String, Vec<u8>>) -> Option<bool> {
+        match self {
+            Self::HeaderEq { name, value } => Some(headers.get(name) == Some(value)),
+            Self::HeaderPrefix { name, prefix } => Some(
+                headers
+                    .get(name)
+                    .map(|v| v.starts_with(prefix))
+                    .unwrap_or_default(),
+            ),
+            Self::HeaderIn { name, values } => Some(
+                headers
+                    .get(name)
+                    .map(|v| values.contains(v))
+                    .unwrap_or_default(),
+            ),
+            Self::ValuePrefix(_) => None,
+        }
+    }
+
+    pub fn matches(&self, val: &[u8], headers: &HashMap<String, Vec<u8>>) -> bool {
+        match self {
+            Self::ValuePrefix(prefix) => val.starts_with(prefix),
+            _ => self.matches_headers(headers).unwrap_or_default(),
+        }
+    }
+}
+
+impl Filter {
+    pub fn as_conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+}
+
+// for the backends evaluating the filter on the items(no header columns to query)
+pub fn matches(filter: Option<&Filter>, val: &[u8], headers: &HashMap<String, Vec<u8>>) -> bool {
+    match filter {
+        None => true,
+        Some(f) => f
+            .as_conditions()
+            .iter()
+            .all(|c: &Condition| c.matches(val, headers)),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn name_checked(name: &str, request_id: Uuid) -> Result<String, Status> {
+    match name.is_empty() {
+        true => Err(Status::invalid_argument(format!(
+            "empty header name in a filter. request id: {request_id}"
+        ))),
+        false => Ok(name.into()),
+    }
+}
+
+// None if no condition set
+#[allow(clippy::result_large_err)]
+pub fn filter_checked(o: Option<&FilterPb>, request_id: Uuid) -> Result<Option<Filter>, Status> {
+    let g: &FilterPb = match o {
+        None => return Ok(None),
+        Some(g) => g,
+    };
+    let mut conditions: Vec<Condition> = Vec::new();
+    for h in &g.header_eq {
+        conditions.push(Condition::HeaderEq {
+            name: name_checked(&h.name, request_id)?,
+            value: h.value.clone(),
+        });
+    }
+    for h in &g.header_prefix {
+        conditions.push(Condition::HeaderPrefix {
+            name: name_checked(&h.name, request_id)?,
+            prefix: h.prefix.clone(),
+        });
+    }
+    for h in &g.header_in {
+        let name: String = name_checked(&h.name, request_id)?;
+        if h.values.is_empty() {
+            return Err(Status::invalid_argument(format!(
+                "no values for the header {name}. request id: {request_id}"
+            )));
+        }
+        conditions.push(Condition::HeaderIn {
+            name,
+            values: h.values.clone(),
+        });
+    }
+    if !g.value_prefix.is_empty() {
+        conditions.push(Condition::ValuePrefix(g.value_prefix.clone()));
+    }
+    match conditions.len() {
+        0 => Ok(None),
+        1..=CONDITIONS_MAX => Ok(Some(Filter { conditions })),
+        n => Err(Status::invalid_argument(format!(
+            "too many conditions({n} > {CONDITIONS_MAX}). request id: {request_id}"
+        ))),
+    }
+}
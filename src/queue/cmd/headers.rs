@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use tonic::Status;
+
+// for the backends storing the headers in a column
+// (name length: u32 BE, name, value length: u32 BE, value) for each header
+
+// an encoded header name(followed by its value when encoded)
+pub fn encode_name(name: &str) -> Vec<u8> {
+    let mut encoded: Vec<u8> = Vec::new();
+    encoded.extend_from_slice(&(name.len() as u32).to_be_bytes());
+    encoded.extend_from_slice(name.as_bytes());
+    encoded
+}
+
+// a part of the encoded headers having the header
+pub fn encode_one(name: &str, value: &[u8]) -> Vec<u8> {
+    let mut encoded: Vec<u8> = encode_name(name);
+    encoded.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    encoded.extend_from_slice(value);
+    encoded
+}
+
+pub fn encode(headers: &HashMap<String, Vec<u8>>) -> Vec<u8> {
+    let mut sorted: Vec<(&String, &Vec<u8>)> = headers.iter().collect();
+    sorted.sort_unstable();
+    sorted
+        .into_iter()
+        .flat_map(|(name, value)| encode_one(name, value))
+        .collect()
+}
+
+fn split(encoded: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, rest) = encoded.split_first_chunk::<4>()?;
+    let len: usize = u32::from_be_bytes(*len) as usize;
+    (len <= rest.len()).then(|| rest.split_at(len))
+}
+
+#[allow(clippy::result_large_err)]
+pub fn decode(encoded: &[u8]) -> Result<HashMap<String, Vec<u8>>, Status> {
+    let mut headers: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut rest: &[u8] = encoded;
+    while !rest.is_empty() {
+        let (name, r) = split(rest).ok_or_else(|| Status::internal("broken header name"))?;
+        let (value, r) = split(r).ok_or_else(|| Status::internal("broken header value"))?;
+        let name: String = String::from_utf8(name.into())
+            .map_err(|e| Status::internal(format!("invalid header name: {e}")))?;
+        headers.insert(name, value.into());
+        rest = r;
+    }
+    Ok(headers)
+}
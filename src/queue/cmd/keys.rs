@@ -2,12 +2,18 @@ use tonic::Status;
 
 use crate::uuid::Uuid;
 
+use super::filter::{filter_checked, Filter};
+
 use crate::db2q::proto::queue::v1::q_svc::KeysRequest;
 
 pub struct KeysReq {
     request_id: Uuid,
     topic_id: Uuid,
     max_keys: u64,
+    filter: Option<Filter>,
+    start_after: Option<u64>,
+    end_before: Option<u64>,
+    descending: bool,
 }
 
 impl KeysReq {
@@ -22,6 +28,29 @@ impl KeysReq {
     pub fn as_max_keys(&self) -> u64 {
         self.max_keys
     }
+
+    pub fn as_filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    pub fn as_start_after(&self) -> Option<u64> {
+        self.start_after
+    }
+
+    pub fn as_end_before(&self) -> Option<u64> {
+        self.end_before
+    }
+
+    pub fn as_descending(&self) -> bool {
+        self.descending
+    }
+}
+
+fn key_bound(key: i64) -> Option<u64> {
+    match key {
+        1.. => Some(key as u64),
+        _ => None,
+    }
 }
 
 impl TryFrom<&KeysRequest> for KeysReq {
@@ -36,10 +65,29 @@ impl TryFrom<&KeysRequest> for KeysReq {
             Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
         })?;
         let max_keys: u64 = g.max_keys;
+        let filter: Option<Filter> = filter_checked(g.filter.as_ref(), request_id)?;
+        let start_after: Option<u64> = key_bound(g.start_after);
+        let end_before: Option<u64> = key_bound(g.end_before);
+        let descending: bool = g.descending;
+        if let (Some(s), Some(e)) = (start_after, end_before) {
+            let inverted: bool = match descending {
+                true => s < e,
+                false => e < s,
+            };
+            if inverted {
+                return Err(Status::invalid_argument(format!(
+                    "inverted key range(start after: {s}, end before: {e}). request id: {request_id}"
+                )));
+            }
+        }
         Ok(Self {
             request_id,
             topic_id,
             max_keys,
+            filter,
+            start_after,
+            end_before,
+            descending,
         })
     }
 }
@@ -0,0 +1,64 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::q_svc::LeaseRequest;
+
+pub const VISIBILITY_TIMEOUT_DEFAULT: Duration = Duration::from_secs(30);
+
+pub struct LeaseReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    visibility_timeout: Duration,
+}
+
+impl LeaseReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_visibility_timeout(&self) -> Duration {
+        self.visibility_timeout
+    }
+}
+
+impl TryFrom<&LeaseRequest> for LeaseReq {
+    type Error = Status;
+    fn try_from(g: &LeaseRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let visibility_timeout: Duration = match g.visibility_timeout.clone() {
+            None => VISIBILITY_TIMEOUT_DEFAULT,
+            Some(d) => {
+                let d: Duration = Duration::try_from(d).map_err(|e| {
+                    Status::invalid_argument(format!(
+                        "invalid visibility timeout. request id: {request_id}: {e}"
+                    ))
+                })?;
+                if d.is_zero() {
+                    return Err(Status::invalid_argument(format!(
+                        "zero visibility timeout. request id: {request_id}"
+                    )));
+                }
+                d
+            }
+        };
+        Ok(Self {
+            request_id,
+            topic_id,
+            visibility_timeout,
+        })
+    }
+}
@@ -2,12 +2,20 @@ use tonic::Status;
 
 use crate::uuid::Uuid;
 
+use super::filter::{filter_checked, Filter};
+
+use crate::group::cmd::name::group_checked;
+
 use crate::db2q::proto::queue::v1::q_svc::NextRequest;
 
 pub struct NextReq {
     request_id: Uuid,
     topic_id: Uuid,
     previous: Option<u64>,
+    priority_order: bool,
+    previous_priority: i32,
+    filter: Option<Filter>,
+    group: Option<String>,
 }
 
 impl NextReq {
@@ -22,6 +30,22 @@ impl NextReq {
     pub fn as_previous_key(&self) -> Option<u64> {
         self.previous
     }
+
+    pub fn as_priority_order(&self) -> bool {
+        self.priority_order
+    }
+
+    pub fn as_previous_priority(&self) -> i32 {
+        self.previous_priority
+    }
+
+    pub fn as_filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    pub fn as_group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
 }
 
 impl TryFrom<&NextRequest> for NextReq {
@@ -41,10 +65,26 @@ impl TryFrom<&NextRequest> for NextReq {
             })?),
             ..=-1 => None,
         };
+        let priority_order: bool = g.priority_order;
+        let previous_priority: i32 = g.previous_priority;
+        let filter: Option<Filter> = filter_checked(g.filter.as_ref(), request_id)?;
+        let group: Option<String> = match g.group.is_empty() {
+            true => None,
+            false => Some(group_checked(&g.group, request_id)?),
+        };
+        if group.is_some() && priority_order {
+            return Err(Status::invalid_argument(format!(
+                "a group can not be used with the priority order. request id: {request_id}"
+            )));
+        }
         Ok(Self {
             request_id,
             topic_id,
             previous,
+            priority_order,
+            previous_priority,
+            filter,
+            group,
         })
     }
 }
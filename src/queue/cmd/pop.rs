@@ -7,6 +7,7 @@ use crate::db2q::proto::queue::v1::q_svc::PopFrontRequest;
 pub struct PopFrontReq {
     request_id: Uuid,
     topic_id: Uuid,
+    priority_order: bool,
 }
 
 impl PopFrontReq {
@@ -17,6 +18,10 @@ impl PopFrontReq {
     pub fn as_topic_id(&self) -> Uuid {
         self.topic_id
     }
+
+    pub fn as_priority_order(&self) -> bool {
+        self.priority_order
+    }
 }
 
 impl TryFrom<PopFrontRequest> for PopFrontReq {
@@ -30,9 +35,11 @@ impl TryFrom<PopFrontRequest> for PopFrontReq {
         let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
             Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
         })?;
+        let priority_order: bool = g.priority_order;
         Ok(Self {
             request_id,
             topic_id,
+            priority_order,
         })
     }
 }
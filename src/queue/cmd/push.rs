@@ -1,13 +1,25 @@
+use core::time::Duration;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
 use tonic::Status;
 
 use crate::uuid::Uuid;
 
 use crate::db2q::proto::queue::v1::q_svc::PushBackRequest;
 
+// request ids are remembered at least this long by the rdb backends
+pub const DEDUP_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
 pub struct PushBackReq {
     request_id: Uuid,
     topic_id: Uuid,
     value: Vec<u8>,
+    not_before: Option<SystemTime>,
+    delay: Option<Duration>,
+    ttl: Option<Duration>,
+    priority: i32,
+    headers: HashMap<String, Vec<u8>>,
 }
 
 impl PushBackReq {
@@ -26,6 +38,26 @@ impl PushBackReq {
     pub fn into_value(self) -> Vec<u8> {
         self.value
     }
+
+    pub fn as_not_before(&self) -> Option<SystemTime> {
+        self.not_before
+    }
+
+    pub fn as_delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    pub fn as_ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    pub fn as_priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn as_headers(&self) -> &HashMap<String, Vec<u8>> {
+        &self.headers
+    }
 }
 
 impl TryFrom<PushBackRequest> for PushBackReq {
@@ -39,11 +71,50 @@ impl TryFrom<PushBackRequest> for PushBackReq {
         let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
             Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
         })?;
+        let not_before: Option<SystemTime> = match g.not_before {
+            None => None,
+            Some(t) => Some(SystemTime::try_from(t).map_err(|e| {
+                Status::invalid_argument(format!(
+                    "invalid not before. request id: {request_id}: {e}"
+                ))
+            })?),
+        };
+        let delay: Option<Duration> = match g.delay {
+            None => None,
+            Some(d) => Some(Duration::try_from(d).map_err(|e| {
+                Status::invalid_argument(format!("invalid delay. request id: {request_id}: {e}"))
+            })?),
+        }
+        .filter(|d: &Duration| !d.is_zero());
+        if not_before.is_some() && delay.is_some() {
+            return Err(Status::invalid_argument(format!(
+                "both not before and delay set. request id: {request_id}"
+            )));
+        }
+        let ttl: Option<Duration> = match g.ttl {
+            None => None,
+            Some(d) => Some(Duration::try_from(d).map_err(|e| {
+                Status::invalid_argument(format!("invalid ttl. request id: {request_id}: {e}"))
+            })?),
+        }
+        .filter(|d: &Duration| !d.is_zero());
+        let priority: i32 = g.priority;
+        let headers: HashMap<String, Vec<u8>> = g.headers;
+        if headers.contains_key("") {
+            return Err(Status::invalid_argument(format!(
+                "empty header name. request id: {request_id}"
+            )));
+        }
         let value: Vec<u8> = g.value;
         Ok(Self {
             request_id,
             topic_id,
             value,
+            not_before,
+            delay,
+            ttl,
+            priority,
+            headers,
         })
     }
 }
@@ -0,0 +1,63 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::q_svc::PushBatchRequest;
+
+pub struct PushBatchReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    values: Vec<Vec<u8>>,
+}
+
+impl PushBatchReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_values(&self) -> &[Vec<u8>] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Vec<u8>> {
+        self.values
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn append(&mut self, other: PushBatchReq) -> Result<(), Status> {
+        match self.topic_id.as_u128() == other.topic_id.as_u128() {
+            true => {
+                self.values.extend(other.values);
+                Ok(())
+            }
+            false => Err(Status::invalid_argument(format!(
+                "topic id mismatch. expected: {}, got: {}, request id: {}",
+                self.topic_id, other.topic_id, other.request_id,
+            ))),
+        }
+    }
+}
+
+impl TryFrom<PushBatchRequest> for PushBatchReq {
+    type Error = Status;
+    fn try_from(g: PushBatchRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let values: Vec<Vec<u8>> = g.values;
+        Ok(Self {
+            request_id,
+            topic_id,
+            values,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::q_svc::RedriveRequest;
+
+pub struct RedriveReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    max_messages: Option<u64>,
+}
+
+impl RedriveReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_max_messages(&self) -> Option<u64> {
+        self.max_messages
+    }
+}
+
+impl TryFrom<&RedriveRequest> for RedriveReq {
+    type Error = Status;
+    fn try_from(g: &RedriveRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let max_messages: Option<u64> = match g.max_messages {
+            0 => None,
+            _ => Some(g.max_messages),
+        };
+        Ok(Self {
+            request_id,
+            topic_id,
+            max_messages,
+        })
+    }
+}
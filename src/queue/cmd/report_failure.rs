@@ -0,0 +1,54 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::q_svc::ReportFailureRequest;
+
+pub struct ReportFailureReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    key: u64,
+    error: String,
+}
+
+impl ReportFailureReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_key(&self) -> u64 {
+        self.key
+    }
+
+    pub fn as_error(&self) -> &str {
+        &self.error
+    }
+}
+
+impl TryFrom<ReportFailureRequest> for ReportFailureReq {
+    type Error = Status;
+    fn try_from(g: ReportFailureRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let key: u64 = g.key.try_into().map_err(|e| {
+            Status::invalid_argument(format!("the key out of range({}): {e}", g.key))
+        })?;
+        let error: String = g.error;
+        Ok(Self {
+            request_id,
+            topic_id,
+            key,
+            error,
+        })
+    }
+}
@@ -0,0 +1,79 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::q_svc::SubscribeRequest;
+
+use super::wait_next::{get_interval_minimum, INTERVAL_DEFAULT};
+
+pub const BATCH_SIZE_DEFAULT: u64 = 64;
+pub const BATCH_SIZE_MAX: u64 = 1024;
+
+pub struct SubscribeReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    previous: Option<u64>,
+    interval: Duration,
+    batch_size: u64,
+}
+
+impl SubscribeReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_previous_key(&self) -> Option<u64> {
+        self.previous
+    }
+
+    pub fn as_interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn as_batch_size(&self) -> u64 {
+        self.batch_size
+    }
+}
+
+impl TryFrom<&SubscribeRequest> for SubscribeReq {
+    type Error = Status;
+    fn try_from(g: &SubscribeRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let previous: Option<u64> = match g.previous {
+            0.. => Some(g.previous.try_into().map_err(|e| {
+                Status::invalid_argument(format!("the key out of range({}): {e}", g.previous))
+            })?),
+            ..=-1 => None,
+        };
+        let imin: Duration = get_interval_minimum();
+        let interval: Duration = match g.interval.clone() {
+            None => INTERVAL_DEFAULT,
+            Some(i) => Duration::try_from(i).ok().unwrap_or(INTERVAL_DEFAULT),
+        }
+        .max(imin);
+        let batch_size: u64 = match g.batch_size {
+            0 => BATCH_SIZE_DEFAULT,
+            _ => g.batch_size.min(BATCH_SIZE_MAX),
+        };
+        Ok(Self {
+            request_id,
+            topic_id,
+            previous,
+            interval,
+            batch_size,
+        })
+    }
+}
@@ -6,6 +6,10 @@ use tonic::Status;
 
 use crate::uuid::Uuid;
 
+use super::filter::{filter_checked, Filter};
+
+use crate::group::cmd::name::group_checked;
+
 use crate::db2q::proto::queue::v1::q_svc::WaitNextRequest;
 
 pub const INTERVAL_DEFAULT: Duration = Duration::from_millis(1000);
@@ -17,7 +21,7 @@ pub const INTERVAL_MINIMUM_KEY: &str = "ENV_INTERVAL_NS_MINIMUM";
 static INTERVAL_MINIMUM: RwLock<Option<Duration>> = RwLock::new(None);
 
 // TODO: rewrite using Once & unsafe if too slow
-fn get_interval_minimum() -> Duration {
+pub(crate) fn get_interval_minimum() -> Duration {
     let od: Option<Duration> = match INTERVAL_MINIMUM.read() {
         Err(e) => {
             log::warn!("Unable to lock: {e}");
@@ -35,11 +39,10 @@ fn get_interval_minimum() -> Duration {
             match mo {
                 Some(d) => *d,
                 None => env::var(INTERVAL_MINIMUM_KEY)
-                    .map_err(|_| Status::not_found("no interval minimum set"))
+                    .map_err(|_| String::from("no interval minimum set"))
                     .and_then(|s: String| {
-                        let u: u64 = str::parse(s.as_str()).map_err(|e| {
-                            Status::invalid_argument(format!("invalid interval integer: {e}"))
-                        })?;
+                        let u: u64 = str::parse(s.as_str())
+                            .map_err(|e| format!("invalid interval integer: {e}"))?;
                         let d: Duration = Duration::from_nanos(u);
                         Ok(d)
                     })
@@ -58,6 +61,8 @@ pub struct WaitNextReq {
     previous: Option<u64>,
     interval: Duration,
     timeout: Duration,
+    filter: Option<Filter>,
+    group: Option<String>,
 }
 
 impl WaitNextReq {
@@ -80,6 +85,14 @@ impl WaitNextReq {
     pub fn as_timeout(&self) -> Duration {
         self.timeout
     }
+
+    pub fn as_filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    pub fn as_group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
 }
 
 impl TryFrom<&WaitNextRequest> for WaitNextReq {
@@ -110,12 +123,19 @@ impl TryFrom<&WaitNextRequest> for WaitNextReq {
             None => TIMEOUT_DEFAULT,
             Some(i) => Duration::try_from(i).ok().unwrap_or(INTERVAL_DEFAULT),
         };
+        let filter: Option<Filter> = filter_checked(g.filter.as_ref(), request_id)?;
+        let group: Option<String> = match g.group.is_empty() {
+            true => None,
+            false => Some(group_checked(&g.group, request_id)?),
+        };
         Ok(Self {
             request_id,
             topic_id,
             previous,
             interval,
             timeout,
+            filter,
+            group,
         })
     }
 }
@@ -3,16 +3,31 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::{Receiver, Sender};
 
-use tonic::{Request, Response, Status};
+use tonic::{Request, Response, Status, Streaming};
 
 use crate::db2q::proto::queue::v1::q_svc::KeysRequest;
+use crate::db2q::proto::queue::v1::q_svc::SubscribeRequest;
 use crate::db2q::proto::queue::v1::q_svc::WaitNextRequest;
+use crate::db2q::proto::queue::v1::q_svc::{AckRequest, AckResponse};
 use crate::db2q::proto::queue::v1::q_svc::{CountRequest, CountResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ExtendLeaseRequest, ExtendLeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{LeaseRequest, LeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{NackRequest, NackResponse};
 use crate::db2q::proto::queue::v1::q_svc::{NextRequest, NextResponse};
 use crate::db2q::proto::queue::v1::q_svc::{PopFrontRequest, PopFrontResponse};
 use crate::db2q::proto::queue::v1::q_svc::{PushBackRequest, PushBackResponse};
+use crate::db2q::proto::queue::v1::q_svc::{PushBatchRequest, PushBatchResponse};
+use crate::db2q::proto::queue::v1::q_svc::{RedriveRequest, RedriveResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ReportFailureRequest, ReportFailureResponse};
 use crate::db2q::proto::queue::v1::queue_service_server::QueueService;
 
+use crate::db2q::proto::queue::v1::group_service_server::GroupService;
+
+use crate::db2q::proto::queue::v1::grp_svc::{CommitOffsetRequest, CommitOffsetResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{DeleteGroupRequest, DeleteGroupResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{GetOffsetRequest, GetOffsetResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{ListGroupsRequest, ListGroupsResponse};
+
 pub struct RwRequest {
     pub writable: bool,
     pub reply: Sender<()>,
@@ -107,6 +122,7 @@ where
 {
     type KeysStream = <I as QueueService>::KeysStream;
     type WaitNextStream = <I as QueueService>::WaitNextStream;
+    type SubscribeStream = <I as QueueService>::SubscribeStream;
 
     async fn push_back(
         &self,
@@ -119,6 +135,28 @@ where
         self.internal.push_back(q).await
     }
 
+    async fn push_batch(
+        &self,
+        req: Request<PushBatchRequest>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.push_batch(q).await
+    }
+
+    async fn push_batch_stream(
+        &self,
+        req: Request<Streaming<PushBatchRequest>>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.push_batch_stream(q).await
+    }
+
     async fn pop_front(
         &self,
         req: Request<PopFrontRequest>,
@@ -145,9 +183,115 @@ where
         self.internal.wait_next(req).await
     }
 
+    async fn subscribe(
+        &self,
+        req: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        self.internal.subscribe(req).await
+    }
+
     async fn keys(&self, req: Request<KeysRequest>) -> Result<Response<Self::KeysStream>, Status> {
         self.internal.keys(req).await
     }
+
+    async fn lease(&self, req: Request<LeaseRequest>) -> Result<Response<LeaseResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.lease(q).await
+    }
+
+    async fn ack(&self, req: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.ack(q).await
+    }
+
+    async fn nack(&self, req: Request<NackRequest>) -> Result<Response<NackResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.nack(q).await
+    }
+
+    async fn extend_lease(
+        &self,
+        req: Request<ExtendLeaseRequest>,
+    ) -> Result<Response<ExtendLeaseResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.extend_lease(q).await
+    }
+
+    async fn report_failure(
+        &self,
+        req: Request<ReportFailureRequest>,
+    ) -> Result<Response<ReportFailureResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.report_failure(q).await
+    }
+
+    async fn redrive(
+        &self,
+        req: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.redrive(q).await
+    }
+}
+
+#[tonic::async_trait]
+impl<I> GroupService for RwQueueSvc<I>
+where
+    I: Send + Sync + 'static + GroupService,
+{
+    async fn commit_offset(
+        &self,
+        req: Request<CommitOffsetRequest>,
+    ) -> Result<Response<CommitOffsetResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.commit_offset(q).await
+    }
+
+    async fn get_offset(
+        &self,
+        req: Request<GetOffsetRequest>,
+    ) -> Result<Response<GetOffsetResponse>, Status> {
+        self.internal.get_offset(req).await
+    }
+
+    async fn list_groups(
+        &self,
+        req: Request<ListGroupsRequest>,
+    ) -> Result<Response<ListGroupsResponse>, Status> {
+        self.internal.list_groups(req).await
+    }
+
+    async fn delete_group(
+        &self,
+        req: Request<DeleteGroupRequest>,
+    ) -> Result<Response<DeleteGroupResponse>, Status> {
+        let writable: bool = self.is_writable().await?;
+        let q: Request<_> = writable
+            .then_some(req)
+            .ok_or_else(|| Status::failed_precondition("read only queue"))?;
+        self.internal.delete_group(q).await
+    }
 }
 
 impl RwSvc {
@@ -1,70 +1,111 @@
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use tonic::{Request, Response, Status};
+use tonic::{Request, Response, Status, Streaming};
 
 use crate::db2q::proto::queue::v1::queue_service_server::QueueService;
 use crate::db2q::proto::queue::v1::topic_service_server::TopicService;
 
+use crate::db2q::proto::queue::v1::group_service_server::GroupService;
+
 use crate::db2q::proto::queue::v1::q_svc::KeysRequest;
+use crate::db2q::proto::queue::v1::q_svc::SubscribeRequest;
 use crate::db2q::proto::queue::v1::q_svc::WaitNextRequest;
+use crate::db2q::proto::queue::v1::q_svc::{AckRequest, AckResponse};
 use crate::db2q::proto::queue::v1::q_svc::{CountRequest, CountResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ExtendLeaseRequest, ExtendLeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{LeaseRequest, LeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{NackRequest, NackResponse};
 use crate::db2q::proto::queue::v1::q_svc::{NextRequest, NextResponse};
 use crate::db2q::proto::queue::v1::q_svc::{PopFrontRequest, PopFrontResponse};
 use crate::db2q::proto::queue::v1::q_svc::{PushBackRequest, PushBackResponse};
+use crate::db2q::proto::queue::v1::q_svc::{PushBatchRequest, PushBatchResponse};
+use crate::db2q::proto::queue::v1::q_svc::{RedriveRequest, RedriveResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ReportFailureRequest, ReportFailureResponse};
 
 use crate::db2q::proto::queue::v1::topic_svc::{CreateRequest, CreateResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{DescribeRequest, DescribeResponse};
 use crate::db2q::proto::queue::v1::topic_svc::{DropRequest, DropResponse};
 use crate::db2q::proto::queue::v1::topic_svc::{ListRequest, ListResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{UpdateRetentionRequest, UpdateRetentionResponse};
+
+use crate::db2q::proto::queue::v1::grp_svc::{CommitOffsetRequest, CommitOffsetResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{DeleteGroupRequest, DeleteGroupResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{GetOffsetRequest, GetOffsetResponse};
+use crate::db2q::proto::queue::v1::grp_svc::{ListGroupsRequest, ListGroupsResponse};
 
-struct Svc<Q, T> {
+struct Svc<Q, T, G> {
     q_svc: Arc<Q>,
     t_svc: Arc<T>,
+    g_svc: Arc<G>,
 }
 
-pub struct Locked<Q, T> {
-    locked: Mutex<Svc<Q, T>>,
+// the group service is optional; () if not used
+pub struct Locked<Q, T, G = ()> {
+    locked: Mutex<Svc<Q, T, G>>,
 }
 
 #[tonic::async_trait]
-impl<Q, T> QueueService for Locked<Q, T>
+impl<Q, T, G> QueueService for Locked<Q, T, G>
 where
     Q: Sync + Send + 'static + QueueService,
     T: Sync + Send + 'static,
+    G: Sync + Send + 'static,
 {
     type KeysStream = <Q as QueueService>::KeysStream;
     type WaitNextStream = <Q as QueueService>::WaitNextStream;
+    type SubscribeStream = <Q as QueueService>::SubscribeStream;
 
     async fn push_back(
         &self,
         req: Request<PushBackRequest>,
     ) -> Result<Response<PushBackResponse>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let q: &Q = &s.q_svc;
         q.push_back(req).await
     }
 
+    async fn push_batch(
+        &self,
+        req: Request<PushBatchRequest>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.push_batch(req).await
+    }
+
+    async fn push_batch_stream(
+        &self,
+        req: Request<Streaming<PushBatchRequest>>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.push_batch_stream(req).await
+    }
+
     async fn pop_front(
         &self,
         req: Request<PopFrontRequest>,
     ) -> Result<Response<PopFrontResponse>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let q: &Q = &s.q_svc;
         q.pop_front(req).await
     }
 
     async fn count(&self, req: Request<CountRequest>) -> Result<Response<CountResponse>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let q: &Q = &s.q_svc;
         q.count(req).await
     }
 
     async fn next(&self, req: Request<NextRequest>) -> Result<Response<NextResponse>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let q: &Q = &s.q_svc;
         q.next(req).await
     }
@@ -74,46 +115,171 @@ where
         req: Request<WaitNextRequest>,
     ) -> Result<Response<Self::WaitNextStream>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let q: &Q = &s.q_svc;
         q.wait_next(req).await
     }
 
+    async fn subscribe(
+        &self,
+        req: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.subscribe(req).await
+    }
+
     async fn keys(&self, req: Request<KeysRequest>) -> Result<Response<Self::KeysStream>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let q: &Q = &s.q_svc;
         q.keys(req).await
     }
+
+    async fn lease(&self, req: Request<LeaseRequest>) -> Result<Response<LeaseResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.lease(req).await
+    }
+
+    async fn ack(&self, req: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.ack(req).await
+    }
+
+    async fn nack(&self, req: Request<NackRequest>) -> Result<Response<NackResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.nack(req).await
+    }
+
+    async fn extend_lease(
+        &self,
+        req: Request<ExtendLeaseRequest>,
+    ) -> Result<Response<ExtendLeaseResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.extend_lease(req).await
+    }
+
+    async fn report_failure(
+        &self,
+        req: Request<ReportFailureRequest>,
+    ) -> Result<Response<ReportFailureResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.report_failure(req).await
+    }
+
+    async fn redrive(
+        &self,
+        req: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let q: &Q = &s.q_svc;
+        q.redrive(req).await
+    }
 }
 
 #[tonic::async_trait]
-impl<Q, T> TopicService for Locked<Q, T>
+impl<Q, T, G> TopicService for Locked<Q, T, G>
 where
     Q: Sync + Send + 'static,
     T: Sync + Send + 'static + TopicService,
+    G: Sync + Send + 'static,
 {
     async fn create(
         &self,
         req: Request<CreateRequest>,
     ) -> Result<Response<CreateResponse>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let t: &T = &s.t_svc;
         t.create(req).await
     }
     async fn drop(&self, req: Request<DropRequest>) -> Result<Response<DropResponse>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let t: &T = &s.t_svc;
         t.drop(req).await
     }
     async fn list(&self, req: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
         let guard = self.locked.lock().await;
-        let s: &Svc<_, _> = &guard;
+        let s: &Svc<_, _, _> = &guard;
         let t: &T = &s.t_svc;
         t.list(req).await
     }
+    async fn update_retention(
+        &self,
+        req: Request<UpdateRetentionRequest>,
+    ) -> Result<Response<UpdateRetentionResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let t: &T = &s.t_svc;
+        t.update_retention(req).await
+    }
+    async fn describe(
+        &self,
+        req: Request<DescribeRequest>,
+    ) -> Result<Response<DescribeResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let t: &T = &s.t_svc;
+        t.describe(req).await
+    }
+}
+
+#[tonic::async_trait]
+impl<Q, T, G> GroupService for Locked<Q, T, G>
+where
+    Q: Sync + Send + 'static,
+    T: Sync + Send + 'static,
+    G: Sync + Send + 'static + GroupService,
+{
+    async fn commit_offset(
+        &self,
+        req: Request<CommitOffsetRequest>,
+    ) -> Result<Response<CommitOffsetResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let g: &G = &s.g_svc;
+        g.commit_offset(req).await
+    }
+    async fn get_offset(
+        &self,
+        req: Request<GetOffsetRequest>,
+    ) -> Result<Response<GetOffsetResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let g: &G = &s.g_svc;
+        g.get_offset(req).await
+    }
+    async fn list_groups(
+        &self,
+        req: Request<ListGroupsRequest>,
+    ) -> Result<Response<ListGroupsResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let g: &G = &s.g_svc;
+        g.list_groups(req).await
+    }
+    async fn delete_group(
+        &self,
+        req: Request<DeleteGroupRequest>,
+    ) -> Result<Response<DeleteGroupResponse>, Status> {
+        let guard = self.locked.lock().await;
+        let s: &Svc<_, _, _> = &guard;
+        let g: &G = &s.g_svc;
+        g.delete_group(req).await
+    }
 }
 
 pub fn locked_q_topic_svc_new<Q, T>(q: &Arc<Q>, t: &Arc<T>) -> impl QueueService + TopicService
@@ -125,6 +291,26 @@ where
         locked: Mutex::new(Svc {
             q_svc: q.clone(),
             t_svc: t.clone(),
+            g_svc: Arc::new(()),
+        }),
+    }
+}
+
+pub fn locked_q_topic_group_svc_new<Q, T, G>(
+    q: &Arc<Q>,
+    t: &Arc<T>,
+    g: &Arc<G>,
+) -> impl QueueService + TopicService + GroupService
+where
+    Q: Sync + Send + 'static + QueueService,
+    T: Sync + Send + 'static + TopicService,
+    G: Sync + Send + 'static + GroupService,
+{
+    Locked {
+        locked: Mutex::new(Svc {
+            q_svc: q.clone(),
+            t_svc: t.clone(),
+            g_svc: g.clone(),
         }),
     }
 }
@@ -1,15 +1,23 @@
 use core::ops::Deref;
 
-use tonic::{Request, Response, Status};
+use tonic::{Request, Response, Status, Streaming};
 
 use crate::db2q::proto::queue::v1::queue_service_server::QueueService;
 
 use crate::db2q::proto::queue::v1::q_svc::KeysRequest;
+use crate::db2q::proto::queue::v1::q_svc::SubscribeRequest;
 use crate::db2q::proto::queue::v1::q_svc::WaitNextRequest;
+use crate::db2q::proto::queue::v1::q_svc::{AckRequest, AckResponse};
 use crate::db2q::proto::queue::v1::q_svc::{CountRequest, CountResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ExtendLeaseRequest, ExtendLeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{LeaseRequest, LeaseResponse};
+use crate::db2q::proto::queue::v1::q_svc::{NackRequest, NackResponse};
 use crate::db2q::proto::queue::v1::q_svc::{NextRequest, NextResponse};
 use crate::db2q::proto::queue::v1::q_svc::{PopFrontRequest, PopFrontResponse};
 use crate::db2q::proto::queue::v1::q_svc::{PushBackRequest, PushBackResponse};
+use crate::db2q::proto::queue::v1::q_svc::{PushBatchRequest, PushBatchResponse};
+use crate::db2q::proto::queue::v1::q_svc::{RedriveRequest, RedriveResponse};
+use crate::db2q::proto::queue::v1::q_svc::{ReportFailureRequest, ReportFailureResponse};
 
 #[tonic::async_trait]
 impl<Q> QueueService for Q
@@ -21,6 +29,8 @@ where
 
     type WaitNextStream = <<Q as Deref>::Target as QueueService>::WaitNextStream;
 
+    type SubscribeStream = <<Q as Deref>::Target as QueueService>::SubscribeStream;
+
     async fn push_back(
         &self,
         req: Request<PushBackRequest>,
@@ -28,6 +38,20 @@ where
         self.deref().push_back(req).await
     }
 
+    async fn push_batch(
+        &self,
+        req: Request<PushBatchRequest>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        self.deref().push_batch(req).await
+    }
+
+    async fn push_batch_stream(
+        &self,
+        req: Request<Streaming<PushBatchRequest>>,
+    ) -> Result<Response<PushBatchResponse>, Status> {
+        self.deref().push_batch_stream(req).await
+    }
+
     async fn pop_front(
         &self,
         req: Request<PopFrontRequest>,
@@ -50,7 +74,47 @@ where
         self.deref().wait_next(req).await
     }
 
+    async fn subscribe(
+        &self,
+        req: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        self.deref().subscribe(req).await
+    }
+
     async fn keys(&self, req: Request<KeysRequest>) -> Result<Response<Self::KeysStream>, Status> {
         self.deref().keys(req).await
     }
+
+    async fn lease(&self, req: Request<LeaseRequest>) -> Result<Response<LeaseResponse>, Status> {
+        self.deref().lease(req).await
+    }
+
+    async fn ack(&self, req: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        self.deref().ack(req).await
+    }
+
+    async fn nack(&self, req: Request<NackRequest>) -> Result<Response<NackResponse>, Status> {
+        self.deref().nack(req).await
+    }
+
+    async fn extend_lease(
+        &self,
+        req: Request<ExtendLeaseRequest>,
+    ) -> Result<Response<ExtendLeaseResponse>, Status> {
+        self.deref().extend_lease(req).await
+    }
+
+    async fn report_failure(
+        &self,
+        req: Request<ReportFailureRequest>,
+    ) -> Result<Response<ReportFailureResponse>, Status> {
+        self.deref().report_failure(req).await
+    }
+
+    async fn redrive(
+        &self,
+        req: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        self.deref().redrive(req).await
+    }
 }
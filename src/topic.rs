@@ -1,2 +1,3 @@
 pub mod cmd;
 pub mod svc;
+pub mod topic2table;
@@ -2,3 +2,8 @@ pub mod create;
 pub mod drop;
 
 pub mod list;
+
+pub mod retention;
+pub mod update_retention;
+
+pub mod describe;
@@ -1,12 +1,36 @@
+use core::time::Duration;
+
 use tonic::Status;
 
 use crate::uuid::Uuid;
 
 use crate::db2q::proto::queue::v1::topic_svc::CreateRequest;
 
+use super::retention::{retention_checked, Retention};
+
+pub struct DeadLetter {
+    topic_id: Uuid,
+    max_attempts: u64,
+}
+
+impl DeadLetter {
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_max_attempts(&self) -> u64 {
+        self.max_attempts
+    }
+}
+
 pub struct CreateReq {
     request_id: Uuid,
     topic_id: Uuid,
+    dead_letter: Option<DeadLetter>,
+    safe_tail: bool,
+    default_ttl: Option<Duration>,
+    retention: Option<Retention>,
+    priority: bool,
 }
 
 impl CreateReq {
@@ -17,6 +41,26 @@ impl CreateReq {
     pub fn as_topic_id(&self) -> Uuid {
         self.topic_id
     }
+
+    pub fn as_dead_letter(&self) -> Option<&DeadLetter> {
+        self.dead_letter.as_ref()
+    }
+
+    pub fn as_safe_tail(&self) -> bool {
+        self.safe_tail
+    }
+
+    pub fn as_default_ttl(&self) -> Option<Duration> {
+        self.default_ttl
+    }
+
+    pub fn as_retention(&self) -> Option<&Retention> {
+        self.retention.as_ref()
+    }
+
+    pub fn as_priority(&self) -> bool {
+        self.priority
+    }
 }
 
 impl TryFrom<&CreateRequest> for CreateReq {
@@ -30,9 +74,51 @@ impl TryFrom<&CreateRequest> for CreateReq {
         let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
             Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
         })?;
+        let dead_letter: Option<DeadLetter> = match g.dead_letter.as_ref() {
+            None => None,
+            Some(d) => {
+                let dlq_id: Uuid = d.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+                    Status::invalid_argument(format!(
+                        "dead letter topic id missing. request id: {request_id}"
+                    ))
+                })?;
+                match dlq_id.as_u128() == topic_id.as_u128() {
+                    true => Err(Status::invalid_argument(format!(
+                        "a topic can not be its own dead letter topic. request id: {request_id}"
+                    ))),
+                    false => Ok(()),
+                }?;
+                match d.max_attempts {
+                    0 => Err(Status::invalid_argument(format!(
+                        "max attempts must be positive. request id: {request_id}"
+                    ))),
+                    _ => Ok(Some(DeadLetter {
+                        topic_id: dlq_id,
+                        max_attempts: d.max_attempts,
+                    })),
+                }?
+            }
+        };
+        let safe_tail: bool = g.safe_tail;
+        let default_ttl: Option<Duration> = match g.default_ttl.clone() {
+            None => None,
+            Some(d) => Some(Duration::try_from(d).map_err(|e| {
+                Status::invalid_argument(format!(
+                    "invalid default ttl. request id: {request_id}: {e}"
+                ))
+            })?),
+        }
+        .filter(|d: &Duration| !d.is_zero());
+        let retention: Option<Retention> = retention_checked(g.retention.as_ref(), request_id)?;
+        let priority: bool = g.priority;
         Ok(Self {
             request_id,
             topic_id,
+            dead_letter,
+            safe_tail,
+            default_ttl,
+            retention,
+            priority,
         })
     }
 }
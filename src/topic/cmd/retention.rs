@@ -0,0 +1,56 @@
+use core::time::Duration;
+
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::topic_svc::Retention as RetentionPb;
+
+pub struct Retention {
+    max_messages: Option<u64>,
+    max_age: Option<Duration>,
+    max_bytes: Option<u64>,
+}
+
+impl Retention {
+    pub fn as_max_messages(&self) -> Option<u64> {
+        self.max_messages
+    }
+
+    pub fn as_max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    pub fn as_max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+}
+
+// None if no limit set
+#[allow(clippy::result_large_err)]
+pub fn retention_checked(
+    o: Option<&RetentionPb>,
+    request_id: Uuid,
+) -> Result<Option<Retention>, Status> {
+    let g: &RetentionPb = match o {
+        None => return Ok(None),
+        Some(g) => g,
+    };
+    let max_messages: Option<u64> = Some(g.max_messages).filter(|u: &u64| 0 < *u);
+    let max_age: Option<Duration> = match g.max_age.clone() {
+        None => None,
+        Some(d) => Some(Duration::try_from(d).map_err(|e| {
+            Status::invalid_argument(format!("invalid max age. request id: {request_id}: {e}"))
+        })?),
+    }
+    .filter(|d: &Duration| !d.is_zero());
+    let max_bytes: Option<u64> = Some(g.max_bytes).filter(|u: &u64| 0 < *u);
+    match (max_messages, max_age, max_bytes) {
+        (None, None, None) => Ok(None),
+        _ => Ok(Some(Retention {
+            max_messages,
+            max_age,
+            max_bytes,
+        })),
+    }
+}
@@ -0,0 +1,47 @@
+use tonic::Status;
+
+use crate::uuid::Uuid;
+
+use crate::db2q::proto::queue::v1::topic_svc::UpdateRetentionRequest;
+
+use super::retention::{retention_checked, Retention};
+
+pub struct UpdateRetentionReq {
+    request_id: Uuid,
+    topic_id: Uuid,
+    retention: Option<Retention>,
+}
+
+impl UpdateRetentionReq {
+    pub fn as_request_id(&self) -> Uuid {
+        self.request_id
+    }
+
+    pub fn as_topic_id(&self) -> Uuid {
+        self.topic_id
+    }
+
+    pub fn as_retention(&self) -> Option<&Retention> {
+        self.retention.as_ref()
+    }
+}
+
+impl TryFrom<&UpdateRetentionRequest> for UpdateRetentionReq {
+    type Error = Status;
+    fn try_from(g: &UpdateRetentionRequest) -> Result<Self, Self::Error> {
+        let request_id: Uuid = g
+            .request_id
+            .as_ref()
+            .map(Uuid::from)
+            .ok_or_else(|| Status::invalid_argument("request id missing"))?;
+        let topic_id: Uuid = g.topic_id.as_ref().map(Uuid::from).ok_or_else(|| {
+            Status::invalid_argument(format!("topic id missing. request id: {request_id}"))
+        })?;
+        let retention: Option<Retention> = retention_checked(g.retention.as_ref(), request_id)?;
+        Ok(Self {
+            request_id,
+            topic_id,
+            retention,
+        })
+    }
+}
@@ -5,8 +5,10 @@ use tonic::{Request, Response, Status};
 use crate::db2q::proto::queue::v1::topic_service_server::TopicService;
 
 use crate::db2q::proto::queue::v1::topic_svc::{CreateRequest, CreateResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{DescribeRequest, DescribeResponse};
 use crate::db2q::proto::queue::v1::topic_svc::{DropRequest, DropResponse};
 use crate::db2q::proto::queue::v1::topic_svc::{ListRequest, ListResponse};
+use crate::db2q::proto::queue::v1::topic_svc::{UpdateRetentionRequest, UpdateRetentionResponse};
 
 #[tonic::async_trait]
 impl<T> TopicService for T
@@ -28,4 +30,18 @@ where
     async fn list(&self, req: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
         self.deref().list(req).await
     }
+
+    async fn update_retention(
+        &self,
+        req: Request<UpdateRetentionRequest>,
+    ) -> Result<Response<UpdateRetentionResponse>, Status> {
+        self.deref().update_retention(req).await
+    }
+
+    async fn describe(
+        &self,
+        req: Request<DescribeRequest>,
+    ) -> Result<Response<DescribeResponse>, Status> {
+        self.deref().describe(req).await
+    }
 }
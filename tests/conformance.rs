@@ -0,0 +1,24 @@
+use core::time::Duration;
+use std::sync::Arc;
+
+use db2q::conformance::{backend_new, run_all};
+
+use db2q::mem::count::svc::count_svc_new;
+use db2q::mem::queue::svc::queue_svc_new;
+use db2q::mem::store::{store_new, Store};
+use db2q::mem::sweep::sweeper_new;
+use db2q::mem::topic::svc::topic_svc_new;
+
+const WORKER_INTERVAL: Duration = Duration::from_millis(100);
+
+#[tokio::test]
+async fn mem() -> Result<(), String> {
+    let store: Arc<Store> = store_new();
+    sweeper_new(&store, WORKER_INTERVAL);
+    let backend = backend_new(
+        queue_svc_new(&store),
+        topic_svc_new(&store),
+        count_svc_new(&store),
+    );
+    run_all(&backend).await
+}